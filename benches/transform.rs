@@ -0,0 +1,178 @@
+use criterion::{criterion_group, criterion_main, BatchSize, Criterion};
+use moniker::{FreeVar, Ignore, Var};
+
+use std::rc::Rc;
+
+use some_embedded_scripting_language::{
+    cont_expr::{t_k, KExpr},
+    eval::{run, run_with_pool, EnvPool},
+    expr::Expr,
+    literals::Literal,
+    prelude,
+};
+
+// a left-leaning application spine `(...((f 1) 1)... 1)`, the shape that
+// makes t_k build its deepest continuation chains
+fn deep_app_spine(depth: usize) -> Expr {
+    let f = FreeVar::fresh_named("f");
+    let mut expr = Expr::Var(Var::Free(f));
+
+    for _ in 0..depth {
+        expr = Expr::App(
+            Rc::new(expr),
+            Rc::new(Expr::Lit(Ignore(Literal::Int(1)))),
+        );
+    }
+
+    expr
+}
+
+// a balanced application tree: wide rather than deep
+fn wide_app_tree(depth: usize) -> Expr {
+    if depth == 0 {
+        Expr::Var(Var::Free(FreeVar::fresh_named("f")))
+    } else {
+        Expr::App(
+            Rc::new(wide_app_tree(depth - 1)),
+            Rc::new(wide_app_tree(depth - 1)),
+        )
+    }
+}
+
+fn lower(expr: Expr) -> some_embedded_scripting_language::cont_expr::CCall {
+    t_k(
+        expr,
+        Rc::new(KExpr::Var(Var::Free(FreeVar::fresh_named("halt")))),
+    )
+}
+
+fn bench_lowering(c: &mut Criterion) {
+    c.bench_function("t_k deep spine", |b| {
+        b.iter_batched(|| deep_app_spine(500), lower, BatchSize::SmallInput)
+    });
+
+    c.bench_function("t_k wide tree", |b| {
+        b.iter_batched(|| wide_app_tree(9), lower, BatchSize::SmallInput)
+    });
+}
+
+fn bench_flattening(c: &mut Criterion) {
+    c.bench_function("into_fexpr deep spine", |b| {
+        b.iter_batched(
+            || lower(deep_app_spine(200)),
+            |call| call.into_fexpr(),
+            BatchSize::SmallInput,
+        )
+    });
+}
+
+fn bench_eval(c: &mut Criterion) {
+    // church-numeral iteration: n closure applications through the
+    // trampoline per run
+    c.bench_function("eval church 200", |b| {
+        b.iter_batched(
+            || {
+                Expr::App(
+                    Rc::new(Expr::App(
+                        Rc::new(prelude::church_num(200)),
+                        Rc::new(prelude::identity()),
+                    )),
+                    Rc::new(Expr::Lit(Ignore(Literal::Int(1)))),
+                )
+            },
+            |expr| run(expr).unwrap(),
+            BatchSize::SmallInput,
+        )
+    });
+}
+
+// an arithmetic-heavy loop: church-numeral iteration of `+1`, so every
+// step extends an environment the pooled evaluator can recycle
+fn add_loop(n: usize) -> Expr {
+    let x = FreeVar::fresh_named("x");
+    let step = prelude::lam(
+        x.clone(),
+        Expr::Bin(
+            Ignore(some_embedded_scripting_language::cont_expr::BinOp::Add),
+            Rc::new(Expr::Var(Var::Free(x))),
+            Rc::new(Expr::Lit(Ignore(Literal::Int(1)))),
+        ),
+    );
+
+    Expr::App(
+        Rc::new(Expr::App(Rc::new(prelude::church_num(n)), Rc::new(step))),
+        Rc::new(Expr::Lit(Ignore(Literal::Int(0)))),
+    )
+}
+
+// an arithmetic-heavy loop: each step does a multiply, a subtract and
+// an add, so the run is dominated by moving integer `Value`s around —
+// the shape the immediate (unboxed) representation is for
+fn arith_loop(n: usize) -> Expr {
+    let x = FreeVar::fresh_named("x");
+    let op = |op, a, b| Expr::Bin(Ignore(op), Rc::new(a), Rc::new(b));
+
+    use some_embedded_scripting_language::cont_expr::BinOp;
+    let step = prelude::lam(
+        x.clone(),
+        op(
+            BinOp::Add,
+            op(
+                BinOp::Sub,
+                op(
+                    BinOp::Mul,
+                    Expr::Var(Var::Free(x.clone())),
+                    Expr::Lit(Ignore(Literal::Int(3))),
+                ),
+                op(
+                    BinOp::Mul,
+                    Expr::Var(Var::Free(x)),
+                    Expr::Lit(Ignore(Literal::Int(2))),
+                ),
+            ),
+            Expr::Lit(Ignore(Literal::Int(1))),
+        ),
+    );
+
+    Expr::App(
+        Rc::new(Expr::App(Rc::new(prelude::church_num(n)), Rc::new(step))),
+        Rc::new(Expr::Lit(Ignore(Literal::Int(1)))),
+    )
+}
+
+fn bench_eval_arith(c: &mut Criterion) {
+    c.bench_function("eval arith loop 200", |b| {
+        b.iter_batched(
+            || arith_loop(200),
+            |expr| run(expr).unwrap(),
+            BatchSize::SmallInput,
+        )
+    });
+}
+
+fn bench_eval_pooled(c: &mut Criterion) {
+    c.bench_function("eval add loop 200 default", |b| {
+        b.iter_batched(|| add_loop(200), |expr| run(expr).unwrap(), BatchSize::SmallInput)
+    });
+
+    // the pool outlives the iterations, so after the first run every
+    // environment extension reuses a recycled table
+    let pool = EnvPool::new();
+    c.bench_function("eval add loop 200 pooled", |b| {
+        b.iter_batched(
+            || add_loop(200),
+            |expr| run_with_pool(expr, None, &pool).unwrap(),
+            BatchSize::SmallInput,
+        )
+    });
+}
+
+criterion_group!(
+    benches,
+    bench_lowering,
+    bench_flattening,
+    bench_eval,
+    bench_eval_pooled,
+    bench_eval_arith
+);
+criterion_main!(benches);
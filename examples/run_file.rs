@@ -0,0 +1,57 @@
+// Reads a script in the textual `FExpr` format, reads it back into the
+// direct-style source language, lowers it through the CPS transform, and
+// prints both the colored CPS term and the final value:
+//
+//     cargo run --example run_file --features eval -- examples/sample.scm
+//
+// With no argument it runs the bundled sample script.
+
+use std::convert::TryFrom;
+use std::process::exit;
+use std::rc::Rc;
+
+use moniker::{FreeVar, Var};
+use termcolor::{ColorChoice, StandardStream};
+
+use some_embedded_scripting_language::cont_expr::{t_k, KExpr};
+use some_embedded_scripting_language::error::Error;
+use some_embedded_scripting_language::eval::{run_ccall, Env, Value};
+use some_embedded_scripting_language::expr::Expr;
+use some_embedded_scripting_language::text;
+
+fn main() {
+    let path = std::env::args()
+        .nth(1)
+        .unwrap_or_else(|| "examples/sample.scm".to_owned());
+
+    let source = match std::fs::read_to_string(&path) {
+        Ok(source) => source,
+        Err(e) => {
+            eprintln!("cannot read {}: {}", path, e);
+            exit(1);
+        }
+    };
+
+    if let Err(e) = compile_and_run(&source) {
+        eprintln!("{}", e);
+        exit(1);
+    }
+}
+
+fn compile_and_run(source: &str) -> Result<(), Error> {
+    let flat = text::parse(source)?;
+    let expr = Expr::try_from(flat)?;
+
+    let halt = FreeVar::fresh_named("halt");
+    let call = t_k(expr, Rc::new(KExpr::Var(Var::Free(halt.clone()))));
+
+    let stdout = StandardStream::stdout(ColorChoice::Auto);
+    call.pretty_print(stdout.lock())
+        .expect("writing to stdout failed");
+    println!();
+
+    let value = run_ccall(call, Env::new().insert(halt, Value::Halt))?;
+    println!("=> {:?}", value);
+
+    Ok(())
+}
@@ -0,0 +1,4 @@
+(call2 (lam2 x#0 k#1
+         (call2 (prim binary add) x#0 (lam1 p#2 (call2 p#2 x#0 k#1))))
+       21
+       halt)
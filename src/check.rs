@@ -0,0 +1,321 @@
+// Static checks that run over a whole source program before lowering.
+// Unlike the evaluator, which stops at the first failure, the checker
+// walks the entire tree and collects every problem it can see, so a user
+// fixing a program gets all of its diagnostics in one pass. The walk
+// always keeps going: a bad subterm still has its children checked.
+
+use moniker::{FreeVar, Ignore, Var};
+
+use std::collections::HashMap;
+
+use crate::expr::Expr;
+use crate::literals::Literal;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Diagnostic {
+    // a variable with no enclosing binder; evaluation would fail the
+    // moment it is looked up
+    UnboundVar(Var<String>),
+    // the callee of an application is a literal, which can never be a
+    // function
+    CalledALiteral(Literal),
+    // a condition position holds a literal other than a boolean
+    NonBoolCondition(Literal),
+    // the argument list of an `apply` is a literal other than a list
+    AppliedANonList(Literal),
+    // an error marker left behind by the recovering parser; the program
+    // aborts if it ever reaches one
+    ErrorMarker(String),
+}
+
+// Checks a program that should have no free variables at all. Bound
+// occurrences are `Var::Bound` in a raw traversal, so every `Var::Free`
+// the walk meets is unbound.
+pub fn check_closed(expr: &Expr) -> Vec<Diagnostic> {
+    check_with(expr, &[])
+}
+
+// Like `check_closed`, for programs that will run with host bindings:
+// free occurrences of the variables in `bound` are fine.
+pub fn check_with(expr: &Expr, bound: &[FreeVar<String>]) -> Vec<Diagnostic> {
+    let mut out = Vec::new();
+    visit(expr, bound, &mut out);
+    out
+}
+
+fn visit(expr: &Expr, bound: &[FreeVar<String>], out: &mut Vec<Diagnostic>) {
+    match expr {
+        Expr::Var(v @ Var::Free(fv)) => {
+            if !bound.contains(fv) {
+                out.push(Diagnostic::UnboundVar(v.clone()));
+            }
+        }
+        Expr::Var(Var::Bound(_)) | Expr::Lit(_) | Expr::Fail => {}
+        Expr::Error(Ignore(msg)) => out.push(Diagnostic::ErrorMarker(msg.clone())),
+        Expr::Lam(s) | Expr::LamRest(s) => visit(&s.unsafe_body, bound, out),
+        Expr::Fix(s) => visit(&s.unsafe_body, bound, out),
+        Expr::App(f, e) => {
+            if let Expr::Lit(Ignore(l)) = &**f {
+                out.push(Diagnostic::CalledALiteral(l.clone()));
+            }
+            visit(f, bound, out);
+            visit(e, bound, out);
+        }
+        Expr::Apply(f, l) => {
+            if let Expr::Lit(Ignore(lit)) = &**f {
+                out.push(Diagnostic::CalledALiteral(lit.clone()));
+            }
+            if let Expr::Lit(Ignore(lit)) = &**l {
+                if !matches!(lit, Literal::List(_)) {
+                    out.push(Diagnostic::AppliedANonList(lit.clone()));
+                }
+            }
+            visit(f, bound, out);
+            visit(l, bound, out);
+        }
+        Expr::Let(v, s) => {
+            visit(v, bound, out);
+            visit(&s.unsafe_body, bound, out);
+        }
+        Expr::Assert(c, _) => {
+            check_condition(c, out);
+            visit(c, bound, out);
+        }
+        Expr::Bin(_, a, b) => {
+            visit(a, bound, out);
+            visit(b, bound, out);
+        }
+        Expr::Not(e) => {
+            check_condition(e, out);
+            visit(e, bound, out);
+        }
+        Expr::Cast(_, e) | Expr::Delay(e) | Expr::Force(e) => visit(e, bound, out),
+        Expr::Tuple(es) => {
+            for e in es {
+                visit(e, bound, out);
+            }
+        }
+        Expr::Proj(_, e) => visit(e, bound, out),
+        Expr::If(c, t, e) => {
+            check_condition(c, out);
+            visit(c, bound, out);
+            visit(t, bound, out);
+            visit(e, bound, out);
+        }
+        Expr::Cond(clauses, els) => {
+            for (test, body) in clauses {
+                check_condition(test, out);
+                visit(test, bound, out);
+                visit(body, bound, out);
+            }
+            visit(els, bound, out);
+        }
+        Expr::While(c, b) => {
+            check_condition(c, out);
+            visit(c, bound, out);
+            visit(b, bound, out);
+        }
+        Expr::TryFinally(b, cl) | Expr::Amb(b, cl) => {
+            visit(b, bound, out);
+            visit(cl, bound, out);
+        }
+        Expr::LetRecMany(s) => {
+            let (defs, body) = &s.unsafe_body;
+            for d in defs {
+                visit(d, bound, out);
+            }
+            visit(body, bound, out);
+        }
+    }
+}
+
+// Counts how many times each binder in `expr` is referenced, in a
+// single raw traversal. Binders keep their `FreeVar` identity in the
+// scope pattern, so the keys match what `resolve` reports for the
+// occurrences; a count of zero is the "dead binding" signal an editor
+// lint wants.
+pub fn use_counts(expr: &Expr) -> HashMap<FreeVar<String>, usize> {
+    let mut counts = HashMap::new();
+    count(expr, &mut Vec::new(), &mut counts);
+    counts
+}
+
+fn count(
+    expr: &Expr,
+    scopes: &mut Vec<Vec<FreeVar<String>>>,
+    counts: &mut HashMap<FreeVar<String>, usize>,
+) {
+    match expr {
+        Expr::Var(Var::Bound(bv)) => {
+            // the offset counts scopes outward from the occurrence, the
+            // stack grows inward from the root; within a scope the
+            // binder index picks the variable (only `LetRecMany` binds
+            // more than one)
+            let idx = scopes.len() - 1 - bv.scope.0 as usize;
+            let binder = scopes[idx][bv.binder.0 as usize].clone();
+            *counts.entry(binder).or_insert(0) += 1;
+        }
+        Expr::Var(Var::Free(v)) => {
+            // a free occurrence only refers to a binder when the term
+            // was hand-built from a shared `FreeVar` without closing
+            if let Some(c) = counts.get_mut(v) {
+                *c += 1;
+            }
+        }
+        Expr::Lit(_) | Expr::Error(_) | Expr::Fail => {}
+        Expr::Lam(s) | Expr::LamRest(s) | Expr::Fix(s) => {
+            let binder = s.unsafe_pattern.0.clone();
+            counts.entry(binder.clone()).or_insert(0);
+            scopes.push(vec![binder]);
+            count(&s.unsafe_body, scopes, counts);
+            scopes.pop();
+        }
+        Expr::App(a, b)
+        | Expr::Apply(a, b)
+        | Expr::Bin(_, a, b)
+        | Expr::TryFinally(a, b)
+        | Expr::Amb(a, b) => {
+            count(a, scopes, counts);
+            count(b, scopes, counts);
+        }
+        Expr::Assert(e, _)
+        | Expr::Not(e)
+        | Expr::Cast(_, e)
+        | Expr::Delay(e)
+        | Expr::Force(e)
+        | Expr::Proj(_, e) => count(e, scopes, counts),
+        Expr::Let(v, s) => {
+            count(v, scopes, counts);
+            let binder = s.unsafe_pattern.0.clone();
+            counts.entry(binder.clone()).or_insert(0);
+            scopes.push(vec![binder]);
+            count(&s.unsafe_body, scopes, counts);
+            scopes.pop();
+        }
+        Expr::LetRecMany(s) => {
+            let binders: Vec<_> = s.unsafe_pattern.iter().map(|b| b.0.clone()).collect();
+            for binder in &binders {
+                counts.entry(binder.clone()).or_insert(0);
+            }
+            let (defs, body) = &s.unsafe_body;
+            scopes.push(binders);
+            for d in defs {
+                count(d, scopes, counts);
+            }
+            count(body, scopes, counts);
+            scopes.pop();
+        }
+        Expr::Tuple(es) => {
+            for e in es {
+                count(e, scopes, counts);
+            }
+        }
+        Expr::If(c, t, e) => {
+            count(c, scopes, counts);
+            count(t, scopes, counts);
+            count(e, scopes, counts);
+        }
+        Expr::Cond(clauses, els) => {
+            for (test, body) in clauses {
+                count(test, scopes, counts);
+                count(body, scopes, counts);
+            }
+            count(els, scopes, counts);
+        }
+        Expr::While(c, b) => {
+            count(c, scopes, counts);
+            count(b, scopes, counts);
+        }
+    }
+}
+
+fn check_condition(c: &Expr, out: &mut Vec<Diagnostic>) {
+    if let Expr::Lit(Ignore(l)) = c {
+        if !matches!(l, Literal::Bool(_)) {
+            out.push(Diagnostic::NonBoolCondition(l.clone()));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::rc::Rc;
+
+    use crate::cont_expr::BinOp;
+    use crate::prelude::{app, fresh, lam, lit, var};
+
+    #[test]
+    fn both_unbound_variables_are_reported() {
+        let x = fresh("x");
+        let y = fresh("y");
+
+        let term = Expr::Bin(
+            Ignore(BinOp::Add),
+            Rc::new(var(&x)),
+            Rc::new(var(&y)),
+        );
+
+        let diags = check_closed(&term);
+        assert_eq!(diags.len(), 2);
+        assert!(diags.contains(&Diagnostic::UnboundVar(Var::Free(x))));
+        assert!(diags.contains(&Diagnostic::UnboundVar(Var::Free(y))));
+    }
+
+    #[test]
+    fn bound_and_host_variables_are_fine() {
+        let x = fresh("x");
+        let halt = fresh("halt");
+
+        let term = app(lam(x.clone(), var(&x)), var(&halt));
+
+        assert_eq!(check_closed(&term).len(), 1);
+        assert!(check_with(&term, &[halt]).is_empty());
+    }
+
+    #[test]
+    fn the_walk_recovers_past_an_error() {
+        // (1 x): the literal callee is an error, and the unbound argument
+        // behind it is still found
+        let x = fresh("x");
+        let term = app(lit(Literal::Int(1)), var(&x));
+
+        let diags = check_closed(&term);
+        assert_eq!(
+            diags,
+            vec![
+                Diagnostic::CalledALiteral(Literal::Int(1)),
+                Diagnostic::UnboundVar(Var::Free(x)),
+            ]
+        );
+    }
+
+    #[test]
+    fn unused_parameters_are_counted_as_zero() {
+        let x = fresh("x");
+        let y = fresh("y");
+
+        // λx. λy. x — y is dead, x is used once
+        let term = lam(x.clone(), lam(y.clone(), var(&x)));
+
+        let counts = use_counts(&term);
+        assert_eq!(counts.get(&y), Some(&0));
+        assert_eq!(counts.get(&x), Some(&1));
+    }
+
+    #[test]
+    fn every_reference_to_a_binder_is_counted() {
+        let f = fresh("f");
+        let x = fresh("x");
+
+        // λf. λx. f (f x)
+        let term = lam(
+            f.clone(),
+            lam(x.clone(), app(var(&f), app(var(&f), var(&x)))),
+        );
+
+        let counts = use_counts(&term);
+        assert_eq!(counts.get(&f), Some(&2));
+        assert_eq!(counts.get(&x), Some(&1));
+    }
+}
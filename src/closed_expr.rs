@@ -0,0 +1,375 @@
+use moniker::BoundTerm;
+use moniker::{Binder, FreeVar, Ignore, Scope, Var};
+
+use std::collections::HashMap;
+use std::rc::Rc;
+
+use crate::cont_expr::PrimOp;
+use crate::flat_expr::FExpr;
+use crate::literals::Literal;
+use crate::utils::{clone_rc, grow_stack};
+
+// Closure conversion: lambdas become closed code paired with an explicit
+// record of the variables they capture. `EnvRef(i)` reads the i-th slot
+// of the nearest enclosing closure's capture record, so the code under a
+// `Clos` binder mentions no variables from outer scopes and the whole
+// term is amenable to serialization.
+
+#[derive(Debug, Clone, BoundTerm)]
+pub enum ClosedFExpr {
+    // closed code plus the capture record: each entry is evaluated in
+    // the enclosing scope (always a `Var` or an `EnvRef`) and stored in
+    // the environment when the closure is built
+    ClosOne(
+        Vec<Rc<ClosedFExpr>>,
+        Scope<Binder<String>, Rc<ClosedFExpr>>,
+    ),
+    ClosTwo(
+        Vec<Rc<ClosedFExpr>>,
+        Scope<Binder<String>, Scope<Binder<String>, Rc<ClosedFExpr>>>,
+    ),
+    Fix(Scope<Binder<String>, Rc<ClosedFExpr>>),
+    // the i-th slot of the enclosing closure's environment
+    EnvRef(usize),
+    Var(Var<String>),
+    Lit(Ignore<Literal>),
+    Prim(Ignore<PrimOp>),
+    CallOne(Rc<ClosedFExpr>, Rc<ClosedFExpr>),
+    CallTwo(Rc<ClosedFExpr>, Rc<ClosedFExpr>, Rc<ClosedFExpr>),
+    If(Rc<ClosedFExpr>, Rc<ClosedFExpr>, Rc<ClosedFExpr>),
+}
+
+// Free variables of a term in first-occurrence order, deduplicated.
+// Anything still bound by an unopened scope shows up as `Var::Bound`
+// and is skipped.
+fn free_vars(expr: &FExpr) -> Vec<FreeVar<String>> {
+    let mut vars = Vec::new();
+    expr.visit_vars(&mut |v| {
+        if let Var::Free(fv) = v {
+            if !vars.contains(fv) {
+                vars.push(fv.clone());
+            }
+        }
+    });
+    vars
+}
+
+// Memoizes free-variable sets per `Rc` identity, so passes that query
+// the same (shared) subterms over and over stay linear overall instead
+// of re-walking the tree on every call. The cache keys on addresses, so
+// scope one to a single pipeline run over terms that stay alive for its
+// duration.
+#[derive(Default)]
+pub struct FreeVarCache {
+    cached: HashMap<*const FExpr, Rc<Vec<FreeVar<String>>>>,
+    misses: usize,
+}
+
+impl FreeVarCache {
+    pub fn new() -> FreeVarCache {
+        FreeVarCache::default()
+    }
+
+    pub fn free_vars(&mut self, expr: &Rc<FExpr>) -> Rc<Vec<FreeVar<String>>> {
+        let key = Rc::as_ptr(expr);
+        if let Some(hit) = self.cached.get(&key) {
+            return hit.clone();
+        }
+
+        self.misses += 1;
+        let vars = Rc::new(self.compute(expr));
+        self.cached.insert(key, vars.clone());
+        vars
+    }
+
+    // how many terms have actually been traversed; a hit leaves this
+    // untouched
+    pub fn misses(&self) -> usize {
+        self.misses
+    }
+
+    // Recurses through the raw scopes: anything bound below shows up as
+    // `Var::Bound` in the body and so never enters a set, exactly as in
+    // `free_vars` above. Sub-results land in the cache on the way up.
+    fn compute(&mut self, expr: &FExpr) -> Vec<FreeVar<String>> {
+        match expr {
+            FExpr::LamOne(s) | FExpr::Fix(s) => (*self.free_vars(&s.unsafe_body)).clone(),
+            FExpr::LamTwo(s) => (*self.free_vars(&s.unsafe_body.unsafe_body)).clone(),
+            FExpr::Var(Var::Free(v)) => vec![v.clone()],
+            FExpr::Var(Var::Bound(_)) | FExpr::Lit(_) | FExpr::Prim(_) => Vec::new(),
+            FExpr::CallOne(f, v) => union(&[self.free_vars(f), self.free_vars(v)]),
+            FExpr::CallTwo(f, v, c) => {
+                union(&[self.free_vars(f), self.free_vars(v), self.free_vars(c)])
+            }
+            FExpr::If(c, t, e) => {
+                union(&[self.free_vars(c), self.free_vars(t), self.free_vars(e)])
+            }
+        }
+    }
+}
+
+// First-occurrence-ordered union, matching the order `free_vars` yields.
+fn union(sets: &[Rc<Vec<FreeVar<String>>>]) -> Vec<FreeVar<String>> {
+    let mut vars = Vec::new();
+    for set in sets {
+        for v in set.iter() {
+            if !vars.contains(v) {
+                vars.push(v.clone());
+            }
+        }
+    }
+    vars
+}
+
+// A reference to `var` as seen from a scope whose closure environment
+// holds `env`: either a slot of that environment or a genuinely free
+// variable of the whole program.
+fn reference(var: &FreeVar<String>, env: &[FreeVar<String>]) -> ClosedFExpr {
+    match env.iter().position(|e| e == var) {
+        Some(i) => ClosedFExpr::EnvRef(i),
+        None => ClosedFExpr::Var(Var::Free(var.clone())),
+    }
+}
+
+pub fn closure_convert(expr: FExpr) -> ClosedFExpr {
+    convert(expr, &[])
+}
+
+fn convert(expr: FExpr, env: &[FreeVar<String>]) -> ClosedFExpr {
+    grow_stack(|| match expr {
+        FExpr::LamOne(s) => {
+            let (Binder(param), body) = s.unbind();
+            let body = clone_rc(body);
+
+            let mut captures = free_vars(&body);
+            captures.retain(|v| *v != param);
+
+            let capture_refs = captures.iter().map(|v| Rc::new(reference(v, env))).collect();
+            let body = convert(body, &captures);
+
+            ClosedFExpr::ClosOne(capture_refs, Scope::new(Binder(param), Rc::new(body)))
+        }
+        FExpr::LamTwo(s) => {
+            let (Binder(param), rest) = s.unbind();
+            let (Binder(cont), body) = rest.unbind();
+            let body = clone_rc(body);
+
+            let mut captures = free_vars(&body);
+            captures.retain(|v| *v != param && *v != cont);
+
+            let capture_refs = captures.iter().map(|v| Rc::new(reference(v, env))).collect();
+            let body = convert(body, &captures);
+
+            ClosedFExpr::ClosTwo(
+                capture_refs,
+                Scope::new(
+                    Binder(param),
+                    Scope::new(Binder(cont), Rc::new(body)),
+                ),
+            )
+        }
+        // `fix` is transparent to capture: its binder stays a plain
+        // variable, bound again on the way out
+        FExpr::Fix(s) => {
+            let (binder, body) = s.unbind();
+            let body = convert(clone_rc(body), env);
+
+            ClosedFExpr::Fix(Scope::new(binder, Rc::new(body)))
+        }
+        FExpr::Var(Var::Free(v)) => reference(&v, env),
+        FExpr::Var(v) => ClosedFExpr::Var(v),
+        FExpr::Lit(l) => ClosedFExpr::Lit(l),
+        FExpr::Prim(p) => ClosedFExpr::Prim(p),
+        FExpr::CallOne(f, v) => ClosedFExpr::CallOne(
+            Rc::new(convert(clone_rc(f), env)),
+            Rc::new(convert(clone_rc(v), env)),
+        ),
+        FExpr::CallTwo(f, v, c) => ClosedFExpr::CallTwo(
+            Rc::new(convert(clone_rc(f), env)),
+            Rc::new(convert(clone_rc(v), env)),
+            Rc::new(convert(clone_rc(c), env)),
+        ),
+        FExpr::If(c, t, e) => ClosedFExpr::If(
+            Rc::new(convert(clone_rc(c), env)),
+            Rc::new(convert(clone_rc(t), env)),
+            Rc::new(convert(clone_rc(e), env)),
+        ),
+    })
+}
+
+impl ClosedFExpr {
+    // Undoes closure conversion by reading capture records back into
+    // variable references. Converting and reifying yields a term
+    // alpha-equal to the input, so evaluation behaviour is unchanged.
+    pub fn reify(self) -> FExpr {
+        reify_in(self, &[])
+    }
+}
+
+fn reify_in(expr: ClosedFExpr, env: &[FreeVar<String>]) -> FExpr {
+    grow_stack(|| match expr {
+        ClosedFExpr::ClosOne(captures, s) => {
+            let names = capture_names(&captures, env);
+            let (param, body) = s.unbind();
+
+            FExpr::LamOne(Scope::new(
+                param,
+                Rc::new(reify_in(clone_rc(body), &names)),
+            ))
+        }
+        ClosedFExpr::ClosTwo(captures, s) => {
+            let names = capture_names(&captures, env);
+            let (param, rest) = s.unbind();
+            let (cont, body) = rest.unbind();
+
+            FExpr::LamTwo(Scope::new(
+                param,
+                Scope::new(cont, Rc::new(reify_in(clone_rc(body), &names))),
+            ))
+        }
+        ClosedFExpr::Fix(s) => {
+            let (binder, body) = s.unbind();
+
+            FExpr::Fix(Scope::new(binder, Rc::new(reify_in(clone_rc(body), env))))
+        }
+        ClosedFExpr::EnvRef(i) => FExpr::Var(Var::Free(env[i].clone())),
+        ClosedFExpr::Var(v) => FExpr::Var(v),
+        ClosedFExpr::Lit(l) => FExpr::Lit(l),
+        ClosedFExpr::Prim(p) => FExpr::Prim(p),
+        ClosedFExpr::CallOne(f, v) => FExpr::CallOne(
+            Rc::new(reify_in(clone_rc(f), env)),
+            Rc::new(reify_in(clone_rc(v), env)),
+        ),
+        ClosedFExpr::CallTwo(f, v, c) => FExpr::CallTwo(
+            Rc::new(reify_in(clone_rc(f), env)),
+            Rc::new(reify_in(clone_rc(v), env)),
+            Rc::new(reify_in(clone_rc(c), env)),
+        ),
+        ClosedFExpr::If(c, t, e) => FExpr::If(
+            Rc::new(reify_in(clone_rc(c), env)),
+            Rc::new(reify_in(clone_rc(t), env)),
+            Rc::new(reify_in(clone_rc(e), env)),
+        ),
+    })
+}
+
+// Resolves a capture record back to the names its slots hold.
+fn capture_names(captures: &[Rc<ClosedFExpr>], env: &[FreeVar<String>]) -> Vec<FreeVar<String>> {
+    captures
+        .iter()
+        .map(|c| match &**c {
+            ClosedFExpr::Var(Var::Free(v)) => v.clone(),
+            ClosedFExpr::EnvRef(i) => env[*i].clone(),
+            c => panic!("malformed capture record entry: {:?}", c),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fvar(v: &FreeVar<String>) -> Rc<FExpr> {
+        Rc::new(FExpr::Var(Var::Free(v.clone())))
+    }
+
+    #[test]
+    fn capture_round_trips_through_conversion() {
+        let y = FreeVar::fresh_named("y");
+        let x = FreeVar::fresh_named("x");
+        let k = FreeVar::fresh_named("k");
+        let halt = FreeVar::fresh_named("halt");
+
+        // (lambda (y k) (k (lambda (x k2) (k2 y)))) applied shape: the
+        // inner lambda captures y from the outer one
+        let k2 = FreeVar::fresh_named("k2");
+        let inner = FExpr::LamTwo(Scope::new(
+            Binder(x),
+            Scope::new(
+                Binder(k2.clone()),
+                Rc::new(FExpr::CallOne(fvar(&k2), fvar(&y))),
+            ),
+        ));
+        let outer = FExpr::LamTwo(Scope::new(
+            Binder(y),
+            Scope::new(
+                Binder(k.clone()),
+                Rc::new(FExpr::CallOne(fvar(&k), Rc::new(inner))),
+            ),
+        ));
+        let term = FExpr::CallTwo(
+            Rc::new(outer),
+            Rc::new(FExpr::Lit(Ignore(Literal::Int(42)))),
+            fvar(&halt),
+        );
+
+        let converted = closure_convert(term.clone());
+
+        // the inner closure's capture record holds exactly the one
+        // variable it closes over
+        fn find_inner(e: &ClosedFExpr) -> Option<&Vec<Rc<ClosedFExpr>>> {
+            match e {
+                ClosedFExpr::ClosTwo(caps, s) => {
+                    find_inner(&s.unsafe_body.unsafe_body).or(Some(caps))
+                }
+                ClosedFExpr::ClosOne(caps, s) => find_inner(&s.unsafe_body).or(Some(caps)),
+                ClosedFExpr::CallOne(f, v) => find_inner(f).or_else(|| find_inner(v)),
+                ClosedFExpr::CallTwo(f, v, c) => find_inner(f)
+                    .or_else(|| find_inner(v))
+                    .or_else(|| find_inner(c)),
+                _ => None,
+            }
+        }
+        let inner_caps = find_inner(&converted).expect("term contains a closure");
+        assert_eq!(inner_caps.len(), 1);
+
+        assert!(FExpr::term_eq(&converted.reify(), &term));
+    }
+
+    #[test]
+    fn cached_free_vars_traverse_each_term_once() {
+        let y = FreeVar::fresh_named("y");
+        let k = FreeVar::fresh_named("k");
+
+        let call = Rc::new(FExpr::CallOne(fvar(&k), fvar(&y)));
+        let term = Rc::new(FExpr::CallTwo(call.clone(), call.clone(), fvar(&k)));
+
+        let mut cache = FreeVarCache::new();
+        let vars = cache.free_vars(&term);
+        assert_eq!(*vars, free_vars(&term));
+
+        // the shared call was walked once, and asking again (for the
+        // whole term or any cached subterm) walks nothing new
+        let after_first = cache.misses();
+        cache.free_vars(&term);
+        cache.free_vars(&call);
+        assert_eq!(cache.misses(), after_first);
+    }
+
+    #[test]
+    fn closed_code_has_no_outer_variables() {
+        let y = FreeVar::fresh_named("y");
+        let k = FreeVar::fresh_named("k");
+
+        // (lambda (x k) y) with y free: the body of the converted code
+        // reads its environment, not y directly
+        let x = FreeVar::fresh_named("x");
+        let lam = FExpr::LamTwo(Scope::new(
+            Binder(x),
+            Scope::new(Binder(k), Rc::new(FExpr::Var(Var::Free(y.clone())))),
+        ));
+
+        match closure_convert(lam) {
+            ClosedFExpr::ClosTwo(caps, s) => {
+                assert_eq!(caps.len(), 1);
+                assert!(matches!(*s.unsafe_body.unsafe_body, ClosedFExpr::EnvRef(0)));
+                // the capture itself refers to y in the enclosing scope
+                match &*caps[0] {
+                    ClosedFExpr::Var(Var::Free(v)) => assert_eq!(*v, y),
+                    c => panic!("expected a variable capture, got {:?}", c),
+                }
+            }
+            c => panic!("expected a closure, got {:?}", c),
+        }
+    }
+}
@@ -1,27 +1,188 @@
 use moniker::BoundTerm;
 use moniker::{Binder, FreeVar, Ignore, Scope, Var};
 
-use pretty::{BoxAllocator, DocAllocator, DocBuilder};
+#[cfg(feature = "pretty")]
+use pretty::{Arena, DocAllocator, DocBuilder};
+#[cfg(feature = "pretty")]
 use termcolor::{Color, ColorSpec, WriteColor};
 
-use std::{io::Result, rc::Rc};
+#[cfg(feature = "pretty")]
+use std::io::Result;
+#[cfg(feature = "pretty")]
+use std::collections::HashMap;
+use std::{collections::HashSet, fmt, rc::Rc};
 
-use crate::{utils::clone_rc, expr::Expr, flat_expr::FExpr, literals::Literal};
+#[cfg(feature = "pretty")]
+use crate::render::PrettyConfig;
+use crate::utils::grow_stack;
+use crate::{expr::Expr, expr::LetRecScope, flat_expr::FExpr, literals::Literal, utils::clone_rc};
+
+// Binary built-ins; in CPS these are curried, consuming one argument
+// per `UCall`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum BinOp {
+    // indexes a string literal, producing a char
+    CharAt,
+    // integer division, erroring on a zero divisor
+    Div,
+    Add,
+    Sub,
+    Mul,
+    // literal equality, producing a bool
+    Eq,
+}
+
+impl fmt::Display for BinOp {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            BinOp::CharAt => write!(f, "char-at"),
+            BinOp::Div => write!(f, "div"),
+            BinOp::Add => write!(f, "add"),
+            BinOp::Sub => write!(f, "sub"),
+            BinOp::Mul => write!(f, "mul"),
+            BinOp::Eq => write!(f, "eq"),
+        }
+    }
+}
+
+// The literal conversions `Expr::Cast` can ask for; anything else is a
+// lowering-time non-starter, and a mismatched operand is a runtime
+// error.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum CastKind {
+    // widens exactly; every u64 is representable as an f64 up to 2^53,
+    // beyond which the nearest float is taken
+    IntToFloat,
+    // decimal rendering of the integer
+    IntToString,
+    // false is 0, true is 1
+    BoolToInt,
+}
+
+impl fmt::Display for CastKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            CastKind::IntToFloat => write!(f, "int->float"),
+            CastKind::IntToString => write!(f, "int->string"),
+            CastKind::BoolToInt => write!(f, "bool->int"),
+        }
+    }
+}
+
+// Built-in operations introduced by lowering; applied like any other
+// function in a `UCall`, but implemented by the evaluator.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum PrimOp {
+    // aborts with the message unless the argument is `true`
+    Assert(String),
+    // a binary operation awaiting its first argument
+    Binary(BinOp),
+    // a binary operation partially applied to its first argument; only
+    // produced at runtime, never by lowering
+    BinaryWith(BinOp, Literal),
+    // boolean negation, erroring on non-boolean input
+    Not,
+    // tags a closure as variadic, so `apply` hands it every remaining
+    // argument as one list instead of unrolling further
+    Rest,
+    // variadic application awaiting its argument list
+    Apply,
+    // variadic application holding its list, awaiting the function; only
+    // produced at runtime, never by lowering
+    ApplyWith(Literal),
+    // collects `n` components into a tuple, one curried application at a
+    // time
+    Tuple(usize),
+    // projects component `i` (zero-based) out of a tuple
+    Proj(usize),
+    // an explicit literal conversion, erroring on a mismatched operand
+    Cast(CastKind),
+    // wraps a closure into a thunk whose body has not run yet
+    Delay,
+    // runs a thunk's body, at most once: the first force caches the
+    // result and later forces answer from the cache
+    Force,
+    // runs a `try/finally` pair — a 2-tuple of body and cleanup thunks —
+    // routing both the normal result and an unwinding error through the
+    // cleanup before they continue
+    Finally,
+    // nondeterministic choice over a 2-tuple of thunks: runs the first,
+    // stashing the second on the backtrack stack for a later `fail`
+    Amb,
+    // backtracks to the most recent pending `amb` alternative
+    Fail,
+}
+
+impl fmt::Display for PrimOp {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PrimOp::Assert(msg) => write!(f, "assert[{:?}]", msg),
+            PrimOp::Binary(op) => write!(f, "{}", op),
+            PrimOp::BinaryWith(op, l) => write!(f, "{}[{:?}]", op, l),
+            PrimOp::Not => write!(f, "not"),
+            PrimOp::Cast(kind) => write!(f, "cast {}", kind),
+            PrimOp::Delay => write!(f, "delay"),
+            PrimOp::Force => write!(f, "force"),
+            PrimOp::Finally => write!(f, "finally"),
+            PrimOp::Amb => write!(f, "amb"),
+            PrimOp::Fail => write!(f, "fail"),
+            PrimOp::Rest => write!(f, "rest"),
+            PrimOp::Apply => write!(f, "apply"),
+            PrimOp::ApplyWith(l) => write!(f, "apply[{:?}]", l),
+            PrimOp::Tuple(n) => write!(f, "tuple[{}]", n),
+            PrimOp::Proj(i) => write!(f, "proj[{}]", i),
+        }
+    }
+}
 
 #[derive(Debug, Clone, BoundTerm)]
 pub enum UExpr {
     Lam(Scope<Binder<String>, Scope<Binder<String>, Rc<CCall>>>),
+    // a recursive value: the binder names the whole fix term within the
+    // inner lambda's body
+    Fix(Scope<Binder<String>, Rc<UExpr>>),
     Var(Var<String>),
     Lit(Ignore<Literal>),
+    Prim(Ignore<PrimOp>),
 }
 
 impl UExpr {
+    // The binders this node introduces, outermost first: a `Lam` binds
+    // its user parameter and then its continuation parameter. A thin
+    // accessor over the scope patterns, as `Expr::bound_vars`.
+    pub fn bound_vars(&self) -> Vec<FreeVar<String>> {
+        match self {
+            UExpr::Lam(s) => vec![
+                s.unsafe_pattern.0.clone(),
+                s.unsafe_body.unsafe_pattern.0.clone(),
+            ],
+            UExpr::Fix(s) => vec![s.unsafe_pattern.0.clone()],
+            UExpr::Var(_) | UExpr::Lit(_) | UExpr::Prim(_) => Vec::new(),
+        }
+    }
+
+    #[cfg(feature = "pretty")]
     pub fn pretty<'a, D>(&'a self, allocator: &'a D) -> DocBuilder<'a, D, ColorSpec>
     where
         D: DocAllocator<'a, ColorSpec>,
         D::Doc: Clone,
     {
-        match self {
+        self.pretty_with(allocator, &PrettyConfig::default())
+    }
+
+    #[cfg(feature = "pretty")]
+    // As `pretty`, with the rendering options threaded through; see
+    // `render::PrettyConfig` for what each knob does.
+    pub fn pretty_with<'a, D>(
+        &'a self,
+        allocator: &'a D,
+        config: &PrettyConfig,
+    ) -> DocBuilder<'a, D, ColorSpec>
+    where
+        D: DocAllocator<'a, ColorSpec>,
+        D::Doc: Clone,
+    {
+        grow_stack(|| match self {
             UExpr::Lam(s) => {
                 let Scope {
                     unsafe_pattern: pat,
@@ -44,8 +205,8 @@ impl UExpr {
                     .parens();
                 let body_pret = allocator
                     .line_()
-                    .append(body.pretty(allocator))
-                    .nest(1)
+                    .append(body.pretty_with(allocator, config))
+                    .nest(config.nest_offset())
                     .group();
 
                 allocator
@@ -57,13 +218,40 @@ impl UExpr {
                     .append(body_pret)
                     .parens()
             }
+            UExpr::Fix(s) => {
+                let Scope {
+                    unsafe_pattern: pat,
+                    unsafe_body: body,
+                } = &s;
+
+                let pat_pret = allocator
+                    .as_string(pat)
+                    .annotate(ColorSpec::new().set_fg(Some(Color::Green)).clone());
+                let body_pret = allocator
+                    .line_()
+                    .append(body.pretty_with(allocator, config))
+                    .nest(config.nest_offset())
+                    .group();
+
+                allocator
+                    .text("fix")
+                    .annotate(ColorSpec::new().set_fg(Some(Color::Magenta)).clone())
+                    .append(allocator.space())
+                    .append(pat_pret)
+                    .append(allocator.space())
+                    .append(body_pret)
+                    .parens()
+            }
             UExpr::Var(s) => allocator.as_string(s),
-            UExpr::Lit(Ignore(l)) => l.pretty(allocator),
-        }
+            UExpr::Lit(Ignore(l)) => l.pretty_with(allocator, config),
+            UExpr::Prim(Ignore(p)) => allocator
+                .as_string(p)
+                .annotate(ColorSpec::new().set_fg(Some(Color::Cyan)).clone()),
+        })
     }
 
     pub fn into_fexpr(self) -> FExpr {
-        match self {
+        grow_stack(|| match self {
             UExpr::Lam(s) => {
                 let Scope {
                     unsafe_pattern: pat,
@@ -78,12 +266,164 @@ impl UExpr {
                     unsafe_pattern: pat,
                     unsafe_body: Scope {
                         unsafe_pattern: cont,
-                        unsafe_body: Rc::new(clone_rc(body).into_fexpr()),
+                        unsafe_body: Rc::new(clone_rc(body).into_fexpr_inner()),
                     },
                 })
             }
+            UExpr::Fix(s) => {
+                let Scope {
+                    unsafe_pattern: pat,
+                    unsafe_body: body,
+                } = s;
+
+                FExpr::Fix(Scope {
+                    unsafe_pattern: pat,
+                    unsafe_body: Rc::new(clone_rc(body).into_fexpr()),
+                })
+            }
             UExpr::Var(s) => FExpr::Var(s),
             UExpr::Lit(l) => FExpr::Lit(l),
+            UExpr::Prim(p) => FExpr::Prim(p),
+        })
+    }
+
+    // A copy sharing no `Rc` allocation with the original, unlike
+    // `clone`, which is a refcount bump; see `CCall::shares_with`.
+    pub fn deep_clone(&self) -> UExpr {
+        grow_stack(|| match self {
+            UExpr::Lam(s) => UExpr::Lam(Scope {
+                unsafe_pattern: s.unsafe_pattern.clone(),
+                unsafe_body: Scope {
+                    unsafe_pattern: s.unsafe_body.unsafe_pattern.clone(),
+                    unsafe_body: Rc::new(s.unsafe_body.unsafe_body.deep_clone()),
+                },
+            }),
+            UExpr::Fix(s) => UExpr::Fix(Scope {
+                unsafe_pattern: s.unsafe_pattern.clone(),
+                unsafe_body: Rc::new(s.unsafe_body.deep_clone()),
+            }),
+            v @ (UExpr::Var(_) | UExpr::Lit(_) | UExpr::Prim(_)) => v.clone(),
+        })
+    }
+}
+
+impl UExpr {
+    // checked constructor: builds the nested scopes for a user lambda
+    pub fn lam(param: FreeVar<String>, cont: FreeVar<String>, body: CCall) -> UExpr {
+        UExpr::Lam(Scope::new(
+            Binder(param),
+            Scope::new(Binder(cont), Rc::new(body)),
+        ))
+    }
+
+    // Unbinds both scopes of a `Lam` at once, yielding the user
+    // parameter, the continuation parameter `m` introduced for it, and
+    // the body with both parameters free. The three parts agree about
+    // names, unlike separate calls to the accessors below.
+    pub fn lam_parts(&self) -> Option<(FreeVar<String>, FreeVar<String>, Rc<CCall>)> {
+        match self {
+            UExpr::Lam(s) => {
+                let (Binder(param), inner) = s.clone().unbind();
+                let (Binder(cont), body) = inner.unbind();
+                Some((param, cont, body))
+            }
+            _ => None,
+        }
+    }
+
+    // Each of these unbinds freshly, so the variable `body` returns
+    // mentions won't be the ones `user_param`/`cont_param` return; reach
+    // for `lam_parts` when that matters.
+    pub fn user_param(&self) -> Option<FreeVar<String>> {
+        self.lam_parts().map(|(param, _, _)| param)
+    }
+
+    pub fn cont_param(&self) -> Option<FreeVar<String>> {
+        self.lam_parts().map(|(_, cont, _)| cont)
+    }
+
+    pub fn body(&self) -> Option<Rc<CCall>> {
+        self.lam_parts().map(|(_, _, body)| body)
+    }
+
+    pub fn subst_user<N: PartialEq<Var<String>>>(self, name: &N, rep: UExpr) -> UExpr {
+        match self {
+            UExpr::Lam(s) => {
+                let Scope {
+                    unsafe_pattern: pat,
+                    unsafe_body:
+                        Scope {
+                            unsafe_pattern: cont,
+                            unsafe_body: body,
+                        },
+                } = s;
+
+                let body = Rc::new(clone_rc(body).subst_user(name, rep));
+
+                UExpr::Lam(Scope {
+                    unsafe_pattern: pat,
+                    unsafe_body: Scope {
+                        unsafe_pattern: cont,
+                        unsafe_body: body,
+                    },
+                })
+            }
+            UExpr::Fix(s) => {
+                let Scope {
+                    unsafe_pattern: pat,
+                    unsafe_body: body,
+                } = s;
+
+                UExpr::Fix(Scope {
+                    unsafe_pattern: pat,
+                    unsafe_body: Rc::new(clone_rc(body).subst_user(name, rep)),
+                })
+            }
+            UExpr::Var(v) => {
+                if name.eq(&v) {
+                    rep
+                } else {
+                    UExpr::Var(v)
+                }
+            }
+            l @ (UExpr::Lit(_) | UExpr::Prim(_)) => l,
+        }
+    }
+
+    pub fn subst_cont<N: PartialEq<Var<String>>>(self, name: &N, rep: KExpr) -> UExpr {
+        match self {
+            UExpr::Lam(s) => {
+                let Scope {
+                    unsafe_pattern: pat,
+                    unsafe_body:
+                        Scope {
+                            unsafe_pattern: cont,
+                            unsafe_body: body,
+                        },
+                } = s;
+
+                let body = Rc::new(clone_rc(body).subst_cont(name, rep));
+
+                UExpr::Lam(Scope {
+                    unsafe_pattern: pat,
+                    unsafe_body: Scope {
+                        unsafe_pattern: cont,
+                        unsafe_body: body,
+                    },
+                })
+            }
+            UExpr::Fix(s) => {
+                let Scope {
+                    unsafe_pattern: pat,
+                    unsafe_body: body,
+                } = s;
+
+                UExpr::Fix(Scope {
+                    unsafe_pattern: pat,
+                    unsafe_body: Rc::new(clone_rc(body).subst_cont(name, rep)),
+                })
+            }
+            v @ (UExpr::Var(_) | UExpr::Lit(_) | UExpr::Prim(_)) => v,
         }
     }
 }
@@ -96,12 +436,37 @@ pub enum KExpr {
 }
 
 impl KExpr {
+    // As `UExpr::bound_vars`: the single parameter of a continuation
+    // lambda, or nothing.
+    pub fn bound_vars(&self) -> Vec<FreeVar<String>> {
+        match self {
+            KExpr::Lam(s) => vec![s.unsafe_pattern.0.clone()],
+            KExpr::Var(_) | KExpr::Lit(_) => Vec::new(),
+        }
+    }
+
+    #[cfg(feature = "pretty")]
     pub fn pretty<'a, D>(&'a self, allocator: &'a D) -> DocBuilder<'a, D, ColorSpec>
     where
         D: DocAllocator<'a, ColorSpec>,
         D::Doc: Clone,
     {
-        match self {
+        self.pretty_with(allocator, &PrettyConfig::default())
+    }
+
+    #[cfg(feature = "pretty")]
+    // As `pretty`, with the rendering options threaded through; see
+    // `render::PrettyConfig` for what each knob does.
+    pub fn pretty_with<'a, D>(
+        &'a self,
+        allocator: &'a D,
+        config: &PrettyConfig,
+    ) -> DocBuilder<'a, D, ColorSpec>
+    where
+        D: DocAllocator<'a, ColorSpec>,
+        D::Doc: Clone,
+    {
+        grow_stack(|| match self {
             KExpr::Lam(s) => {
                 let Scope {
                     unsafe_pattern: pat,
@@ -114,8 +479,8 @@ impl KExpr {
                     .parens();
                 let body_pret = allocator
                     .line_()
-                    .append(body.pretty(allocator))
-                    .nest(1)
+                    .append(body.pretty_with(allocator, config))
+                    .nest(config.nest_offset())
                     .group();
 
                 allocator
@@ -128,12 +493,12 @@ impl KExpr {
                     .parens()
             }
             KExpr::Var(s) => allocator.as_string(s),
-            KExpr::Lit(Ignore(l)) => l.pretty(allocator),
-        }
+            KExpr::Lit(Ignore(l)) => l.pretty_with(allocator, config),
+        })
     }
 
     pub fn into_fexpr(self) -> FExpr {
-        match self {
+        grow_stack(|| match self {
             KExpr::Lam(s) => {
                 let Scope {
                     unsafe_pattern: pat,
@@ -142,11 +507,74 @@ impl KExpr {
 
                 FExpr::LamOne(Scope {
                     unsafe_pattern: pat,
-                    unsafe_body: Rc::new(clone_rc(body).into_fexpr()),
+                    unsafe_body: Rc::new(clone_rc(body).into_fexpr_inner()),
                 })
             }
             KExpr::Var(s) => FExpr::Var(s),
             KExpr::Lit(l) => FExpr::Lit(l),
+        })
+    }
+
+    // See `UExpr::deep_clone`.
+    pub fn deep_clone(&self) -> KExpr {
+        grow_stack(|| match self {
+            KExpr::Lam(s) => KExpr::Lam(Scope {
+                unsafe_pattern: s.unsafe_pattern.clone(),
+                unsafe_body: Rc::new(s.unsafe_body.deep_clone()),
+            }),
+            v @ (KExpr::Var(_) | KExpr::Lit(_)) => v.clone(),
+        })
+    }
+}
+
+impl KExpr {
+    // checked constructor: builds the scope for a continuation lambda
+    pub fn lam(param: FreeVar<String>, body: CCall) -> KExpr {
+        KExpr::Lam(Scope::new(Binder(param), Rc::new(body)))
+    }
+
+    pub fn subst_user<N: PartialEq<Var<String>>>(self, name: &N, rep: UExpr) -> KExpr {
+        match self {
+            KExpr::Lam(s) => {
+                let Scope {
+                    unsafe_pattern: pat,
+                    unsafe_body: body,
+                } = s;
+
+                let body = Rc::new(clone_rc(body).subst_user(name, rep));
+
+                KExpr::Lam(Scope {
+                    unsafe_pattern: pat,
+                    unsafe_body: body,
+                })
+            }
+            v @ (KExpr::Var(_) | KExpr::Lit(_)) => v,
+        }
+    }
+
+    pub fn subst_cont<N: PartialEq<Var<String>>>(self, name: &N, rep: KExpr) -> KExpr {
+        match self {
+            KExpr::Lam(s) => {
+                let Scope {
+                    unsafe_pattern: pat,
+                    unsafe_body: body,
+                } = s;
+
+                let body = Rc::new(clone_rc(body).subst_cont(name, rep));
+
+                KExpr::Lam(Scope {
+                    unsafe_pattern: pat,
+                    unsafe_body: body,
+                })
+            }
+            KExpr::Var(v) => {
+                if name.eq(&v) {
+                    rep
+                } else {
+                    KExpr::Var(v)
+                }
+            }
+            l @ KExpr::Lit(_) => l,
         }
     }
 }
@@ -155,19 +583,63 @@ impl KExpr {
 pub enum CCall {
     UCall(Rc<UExpr>, Rc<UExpr>, Rc<KExpr>),
     KCall(Rc<KExpr>, Rc<UExpr>),
+    // branches on an already-evaluated condition value
+    If(Rc<UExpr>, Rc<CCall>, Rc<CCall>),
 }
 
 impl CCall {
+    #[cfg(feature = "pretty")]
     pub fn pretty<'a, D>(&'a self, allocator: &'a D) -> DocBuilder<'a, D, ColorSpec>
     where
         D: DocAllocator<'a, ColorSpec>,
         D::Doc: Clone,
     {
-        match self {
+        self.pretty_with(allocator, &PrettyConfig::default())
+    }
+
+    // As `pretty`, but marks user calls in tail position (those that pass
+    // the enclosing continuation straight through as a variable) with `↰`,
+    // showing where tail-call optimization would apply.
+    #[cfg(feature = "pretty")]
+    pub fn pretty_tail_hints<'a, D>(&'a self, allocator: &'a D) -> DocBuilder<'a, D, ColorSpec>
+    where
+        D: DocAllocator<'a, ColorSpec>,
+        D::Doc: Clone,
+    {
+        self.pretty_with(
+            allocator,
+            &PrettyConfig {
+                tail_hints: true,
+                ..PrettyConfig::default()
+            },
+        )
+    }
+
+    #[cfg(feature = "pretty")]
+    // As `pretty`, with the rendering options threaded through; see
+    // `render::PrettyConfig` for what each knob does.
+    pub fn pretty_with<'a, D>(
+        &'a self,
+        allocator: &'a D,
+        config: &PrettyConfig,
+    ) -> DocBuilder<'a, D, ColorSpec>
+    where
+        D: DocAllocator<'a, ColorSpec>,
+        D::Doc: Clone,
+    {
+        grow_stack(|| match self {
             CCall::UCall(f, v, c) => {
-                let f_pret = f.pretty(allocator);
-                let v_pret = v.pretty(allocator);
-                let c_pret = c.pretty(allocator);
+                let f_pret = f.pretty_with(allocator, config);
+                let v_pret = v.pretty_with(allocator, config);
+                let c_pret = c.pretty_with(allocator, config);
+
+                let hint = if config.tail_hints && matches!(**c, KExpr::Var(_)) {
+                    allocator
+                        .text(" ↰")
+                        .annotate(ColorSpec::new().set_fg(Some(Color::Cyan)).clone())
+                } else {
+                    allocator.nil()
+                };
 
                 f_pret
                     .annotate(ColorSpec::new().set_fg(Some(Color::Blue)).clone())
@@ -175,12 +647,13 @@ impl CCall {
                     .append(v_pret)
                     .append(allocator.space())
                     .append(c_pret)
+                    .append(hint)
                     .parens()
             }
 
             CCall::KCall(f, c) => {
-                let f_pret = f.pretty(allocator);
-                let c_pret = c.pretty(allocator);
+                let f_pret = f.pretty_with(allocator, config);
+                let c_pret = c.pretty_with(allocator, config);
 
                 f_pret
                     .annotate(ColorSpec::new().set_fg(Some(Color::Blue)).clone())
@@ -188,19 +661,180 @@ impl CCall {
                     .append(c_pret)
                     .parens()
             }
-        }
+
+            CCall::If(c, t, e) => {
+                let c_pret = c.pretty_with(allocator, config);
+                let t_pret = allocator
+                    .line_()
+                    .append(t.pretty_with(allocator, config))
+                    .nest(config.nest_offset());
+                let e_pret = allocator
+                    .line_()
+                    .append(e.pretty_with(allocator, config))
+                    .nest(config.nest_offset());
+
+                allocator
+                    .text("if")
+                    .annotate(ColorSpec::new().set_fg(Some(Color::Magenta)).clone())
+                    .append(allocator.space())
+                    .append(c_pret)
+                    .append(t_pret)
+                    .append(e_pret)
+                    .group()
+                    .parens()
+            }
+        })
     }
 
+    #[cfg(feature = "pretty")]
     pub fn pretty_print(&self, out: impl WriteColor) -> Result<()> {
-        let allocator = BoxAllocator;
+        self.pretty_print_width(70, out)
+    }
+
+    // Renders wrapped to `width` columns; a width of 0 means no wrapping,
+    // producing a single line. Callers wanting terminal-width output can
+    // pass the result of a terminal-size query here directly.
+    #[cfg(feature = "pretty")]
+    pub fn pretty_print_width(&self, width: usize, out: impl WriteColor) -> Result<()> {
+        self.pretty_print_conf(
+            &PrettyConfig {
+                width,
+                ..PrettyConfig::default()
+            },
+            out,
+        )
+    }
+
+    // Renders the whole term on one line, breaks replaced by spaces —
+    // handy when the output is destined for a log and needs to stay
+    // greppable. Shorthand for `pretty_print_width(0, out)`.
+    #[cfg(feature = "pretty")]
+    pub fn pretty_print_oneline(&self, out: impl WriteColor) -> Result<()> {
+        self.pretty_print_width(0, out)
+    }
+
+    #[cfg(feature = "pretty")]
+    pub fn pretty_print_tail_hints(&self, out: impl WriteColor) -> Result<()> {
+        self.pretty_print_conf(
+            &PrettyConfig {
+                tail_hints: true,
+                ..PrettyConfig::default()
+            },
+            out,
+        )
+    }
+
+    // Renders with the full set of options in `config`.
+    #[cfg(feature = "pretty")]
+    pub fn pretty_print_conf(&self, config: &PrettyConfig, out: impl WriteColor) -> Result<()> {
+        let allocator = Arena::new();
+        let doc = self.pretty_with(&allocator, config).1;
+
+        let mut out = crate::render::IndentWriter::new(out, config.indent);
+        if config.color && out.supports_color() {
+            doc.render_colored(config.render_width(), out)?;
+        } else {
+            doc.render(config.render_width(), &mut out)?;
+        }
 
-        self.pretty(&allocator).1.render_colored(70, out)?;
+        Ok(())
+    }
+
+    // Renders with shared subterms floated out, GHC-dump style: every
+    // lambda that occurs more than once (as hash-consing produces) is
+    // bound once as `let $n = ...` and merely named at its reuse sites,
+    // so heavily-shared programs stay readable instead of exploding.
+    #[cfg(feature = "pretty")]
+    pub fn pretty_print_shared(&self, mut out: impl WriteColor) -> Result<()> {
+        let mut u_counts: HashMap<*const UExpr, usize> = HashMap::new();
+        let mut k_counts: HashMap<*const KExpr, usize> = HashMap::new();
+        for t in self.subterms() {
+            match t {
+                SubTerm::U(u @ UExpr::Lam(_)) => {
+                    *u_counts.entry(u as *const UExpr).or_insert(0) += 1
+                }
+                SubTerm::K(k @ KExpr::Lam(_)) => {
+                    *k_counts.entry(k as *const KExpr).or_insert(0) += 1
+                }
+                _ => {}
+            }
+        }
+
+        // name the shared nodes in discovery order, outermost first
+        let mut names = SharedNames::default();
+        let mut order = Vec::new();
+        for t in self.subterms_deduped() {
+            match t {
+                SubTerm::U(u) if u_counts.get(&(u as *const UExpr)).copied().unwrap_or(0) > 1 => {
+                    let name = FreeVar::fresh_named(format!("${}", order.len()));
+                    names.u.insert(u as *const UExpr, name);
+                    order.push(t);
+                }
+                SubTerm::K(k) if k_counts.get(&(k as *const KExpr)).copied().unwrap_or(0) > 1 => {
+                    let name = FreeVar::fresh_named(format!("${}", order.len()));
+                    names.k.insert(k as *const KExpr, name);
+                    order.push(t);
+                }
+                _ => {}
+            }
+        }
+
+        // definitions reference later (inner) definitions by name too
+        let defs: Vec<(FreeVar<String>, Floated)> = order
+            .into_iter()
+            .map(|t| match t {
+                SubTerm::U(u) => (
+                    names.u[&(u as *const UExpr)].clone(),
+                    Floated::U(rebuild_u(u, &names)),
+                ),
+                SubTerm::K(k) => (
+                    names.k[&(k as *const KExpr)].clone(),
+                    Floated::K(rebuild_k(k, &names)),
+                ),
+                SubTerm::C(_) => unreachable!(),
+            })
+            .collect();
+        let main = replace_c(self, &names);
+
+        let allocator = Arena::new();
+        let mut doc = allocator.nil();
+        for (name, def) in &defs {
+            let body = match def {
+                Floated::U(u) => u.pretty(&allocator),
+                Floated::K(k) => k.pretty(&allocator),
+            };
+
+            doc = doc
+                .append(allocator.text("let "))
+                .append(allocator.as_string(name))
+                .append(allocator.text(" ="))
+                .append(allocator.line().append(body).nest(2).group())
+                .append(allocator.hardline());
+        }
+        let doc = doc
+            .append(allocator.text("in"))
+            .append(allocator.hardline())
+            .append(main.pretty(&allocator))
+            .1;
+
+        if out.supports_color() {
+            doc.render_colored(70, out)?;
+        } else {
+            doc.render(70, &mut out)?;
+        }
 
         Ok(())
     }
 
     pub fn into_fexpr(self) -> FExpr {
-        match self {
+        #[cfg(feature = "tracing")]
+        let _span = tracing::debug_span!("flatten").entered();
+
+        self.into_fexpr_inner()
+    }
+
+    fn into_fexpr_inner(self) -> FExpr {
+        grow_stack(|| match self {
             CCall::UCall(f, v, c) => FExpr::CallTwo(
                 Rc::new(clone_rc(f).into_fexpr()),
                 Rc::new(clone_rc(v).into_fexpr()),
@@ -210,28 +844,1258 @@ impl CCall {
                 Rc::new(clone_rc(f).into_fexpr()),
                 Rc::new(clone_rc(v).into_fexpr()),
             ),
-        }
+            CCall::If(c, t, e) => FExpr::If(
+                Rc::new(clone_rc(c).into_fexpr()),
+                Rc::new(clone_rc(t).into_fexpr_inner()),
+                Rc::new(clone_rc(e).into_fexpr_inner()),
+            ),
+        })
     }
-}
 
-pub fn t_k(expr: Expr, k: Rc<KExpr>) -> CCall {
-    match expr {
-        e @ (Expr::Lam(_) | Expr::Var(_) | Expr::Lit(_)) => CCall::KCall(k, Rc::new(m(e))),
-        Expr::App(f, e) => {
-            let rv_v = FreeVar::fresh_named("rv");
-            let cont = Rc::new(KExpr::Lam(Scope::new(
-                Binder(rv_v.clone()),
-                Rc::new(CCall::KCall(k, Rc::new(UExpr::Var(Var::Free(rv_v))))),
-            )));
+    // A copy sharing no `Rc` allocation with the original. `clone` on
+    // these types is a refcount bump that shares every child node, which
+    // is usually what's wanted; this rebuilds them all.
+    pub fn deep_clone(&self) -> CCall {
+        grow_stack(|| match self {
+            CCall::UCall(f, v, k) => CCall::UCall(
+                Rc::new(f.deep_clone()),
+                Rc::new(v.deep_clone()),
+                Rc::new(k.deep_clone()),
+            ),
+            CCall::KCall(k, v) => {
+                CCall::KCall(Rc::new(k.deep_clone()), Rc::new(v.deep_clone()))
+            }
+            CCall::If(c, t, e) => CCall::If(
+                Rc::new(c.deep_clone()),
+                Rc::new(t.deep_clone()),
+                Rc::new(e.deep_clone()),
+            ),
+        })
+    }
 
-            let f_v = FreeVar::fresh_named("f");
-            let e_v = FreeVar::fresh_named("e");
+    // Whether any node is reachable from both `self` and `other` — the
+    // observable difference between a shallow `clone` and a `deep_clone`,
+    // and the property hash-consing exists to create. Roots compare too,
+    // so aliases of one value trivially share.
+    pub fn shares_with(&self, other: &CCall) -> bool {
+        let mut u: HashSet<*const UExpr> = HashSet::new();
+        let mut k: HashSet<*const KExpr> = HashSet::new();
+        let mut c: HashSet<*const CCall> = HashSet::new();
 
-            t_k(
-                clone_rc(f),
+        for t in self.subterms_deduped() {
+            match t {
+                SubTerm::U(n) => {
+                    u.insert(n as *const UExpr);
+                }
+                SubTerm::K(n) => {
+                    k.insert(n as *const KExpr);
+                }
+                SubTerm::C(n) => {
+                    c.insert(n as *const CCall);
+                }
+            }
+        }
+
+        other.subterms_deduped().any(|t| match t {
+            SubTerm::U(n) => u.contains(&(n as *const UExpr)),
+            SubTerm::K(n) => k.contains(&(n as *const KExpr)),
+            SubTerm::C(n) => c.contains(&(n as *const CCall)),
+        })
+    }
+}
+
+// `From` conversions for the flattening path, so CPS terms compose with
+// generic code bound by `Into<FExpr>`; each delegates to `into_fexpr`
+impl From<UExpr> for FExpr {
+    fn from(expr: UExpr) -> FExpr {
+        expr.into_fexpr()
+    }
+}
+
+impl From<KExpr> for FExpr {
+    fn from(expr: KExpr) -> FExpr {
+        expr.into_fexpr()
+    }
+}
+
+impl From<CCall> for FExpr {
+    fn from(call: CCall) -> FExpr {
+        call.into_fexpr()
+    }
+}
+
+impl CCall {
+    // checked constructors: the enum's types already separate user and
+    // continuation positions, so these just catch terms that could never
+    // be applied (a literal in head or continuation position)
+    pub fn ucall(f: UExpr, v: UExpr, k: KExpr) -> CCall {
+        debug_assert!(
+            !matches!(f, UExpr::Lit(_)),
+            "the head of a UCall must be a lambda or a variable"
+        );
+        debug_assert!(
+            !matches!(k, KExpr::Lit(_)),
+            "the continuation of a UCall must be a lambda or a variable"
+        );
+
+        CCall::UCall(Rc::new(f), Rc::new(v), Rc::new(k))
+    }
+
+    pub fn kcall(k: KExpr, v: UExpr) -> CCall {
+        debug_assert!(
+            !matches!(k, KExpr::Lit(_)),
+            "the head of a KCall must be a lambda or a variable"
+        );
+
+        CCall::KCall(Rc::new(k), Rc::new(v))
+    }
+
+    pub fn subst_user<N: PartialEq<Var<String>>>(self, name: &N, rep: UExpr) -> CCall {
+        match self {
+            CCall::UCall(f, v, c) => CCall::UCall(
+                Rc::new(clone_rc(f).subst_user(name, rep.clone())),
+                Rc::new(clone_rc(v).subst_user(name, rep.clone())),
+                Rc::new(clone_rc(c).subst_user(name, rep)),
+            ),
+            CCall::KCall(f, v) => CCall::KCall(
+                Rc::new(clone_rc(f).subst_user(name, rep.clone())),
+                Rc::new(clone_rc(v).subst_user(name, rep)),
+            ),
+            CCall::If(c, t, e) => CCall::If(
+                Rc::new(clone_rc(c).subst_user(name, rep.clone())),
+                Rc::new(clone_rc(t).subst_user(name, rep.clone())),
+                Rc::new(clone_rc(e).subst_user(name, rep)),
+            ),
+        }
+    }
+
+    pub fn subst_cont<N: PartialEq<Var<String>>>(self, name: &N, rep: KExpr) -> CCall {
+        match self {
+            CCall::UCall(f, v, c) => CCall::UCall(
+                Rc::new(clone_rc(f).subst_cont(name, rep.clone())),
+                Rc::new(clone_rc(v).subst_cont(name, rep.clone())),
+                Rc::new(clone_rc(c).subst_cont(name, rep)),
+            ),
+            CCall::KCall(f, v) => CCall::KCall(
+                Rc::new(clone_rc(f).subst_cont(name, rep.clone())),
+                Rc::new(clone_rc(v).subst_cont(name, rep)),
+            ),
+            CCall::If(c, t, e) => CCall::If(
+                Rc::new(clone_rc(c).subst_cont(name, rep.clone())),
+                Rc::new(clone_rc(t).subst_cont(name, rep.clone())),
+                Rc::new(clone_rc(e).subst_cont(name, rep)),
+            ),
+        }
+    }
+    // Performs exactly one reduction step — the leftmost-outermost
+    // redex, which in CPS is always the call at the root — and returns
+    // the contractum, or `None` when the term is a final value or
+    // stuck. A fix in head position unrolls by one turn; a primitive in
+    // head position is stuck here, since only the evaluator knows its
+    // semantics. This is the fine-grained counterpart to running the
+    // term: iterating it replays evaluation one visible step at a time.
+    pub fn reduce_once(self) -> Option<CCall> {
+        match self {
+            CCall::KCall(k, v) => match clone_rc(k) {
+                KExpr::Lam(s) => {
+                    let (Binder(param), body) = s.unbind();
+                    Some(clone_rc(body).subst_user(&param, clone_rc(v)))
+                }
+                KExpr::Var(_) | KExpr::Lit(_) => None,
+            },
+            CCall::UCall(f, v, c) => match clone_rc(f) {
+                UExpr::Lam(s) => {
+                    let (Binder(param), inner) = s.unbind();
+                    let (Binder(cont), body) = inner.unbind();
+                    Some(
+                        clone_rc(body)
+                            .subst_user(&param, clone_rc(v))
+                            .subst_cont(&cont, clone_rc(c)),
+                    )
+                }
+                UExpr::Fix(s) => {
+                    let (Binder(fix), inner) = s.unbind();
+                    let again = UExpr::Fix(Scope::new(Binder(fix.clone()), inner.clone()));
+                    let unrolled = clone_rc(inner).subst_user(&fix, again);
+                    Some(CCall::UCall(Rc::new(unrolled), v, c))
+                }
+                UExpr::Var(_) | UExpr::Lit(_) | UExpr::Prim(_) => None,
+            },
+            CCall::If(cond, t, e) => match &*cond {
+                UExpr::Lit(Ignore(Literal::Bool(true))) => Some(clone_rc(t)),
+                UExpr::Lit(Ignore(Literal::Bool(false))) => Some(clone_rc(e)),
+                _ => None,
+            },
+        }
+    }
+}
+
+// The pieces of a lowered application, recovered by `match_app_cps`:
+// the function and argument values of the original `(f e)` and the
+// continuation the call's result flows into.
+#[derive(Debug, Clone)]
+pub struct AppParts {
+    pub func: Rc<UExpr>,
+    pub arg: Rc<UExpr>,
+    pub cont: Rc<KExpr>,
+}
+
+// Recognizes the exact shape `t_k` gives an application of two values:
+//
+//   (k-call (k-lam f. (k-call (k-lam e. (u-call f e cont)) arg)) func)
+//
+// and hands back the interesting pieces, saving passes over the lowered
+// tree a double unbind and three nested matches. Only matches when the
+// administrative binders are used exactly where the lowering puts them
+// and nowhere else, so the returned `arg` and `cont` stand on their own.
+pub fn match_app_cps(call: &CCall) -> Option<AppParts> {
+    let (outer, func) = match call {
+        CCall::KCall(k, u) => match &**k {
+            KExpr::Lam(s) => (s.clone(), u.clone()),
+            _ => return None,
+        },
+        _ => return None,
+    };
+    let (Binder(f_v), inner) = outer.unbind();
+
+    let (inner, arg) = match &*inner {
+        CCall::KCall(k, u) => match &**k {
+            KExpr::Lam(s) => (s.clone(), u.clone()),
+            _ => return None,
+        },
+        _ => return None,
+    };
+    let (Binder(e_v), body) = inner.unbind();
+
+    let (f, e, cont) = match &*body {
+        CCall::UCall(f, e, cont) => (f, e, cont),
+        _ => return None,
+    };
+    match (&**f, &**e) {
+        (UExpr::Var(Var::Free(f_use)), UExpr::Var(Var::Free(e_use)))
+            if *f_use == f_v && *e_use == e_v => {}
+        _ => return None,
+    }
+
+    // a stray occurrence of either binder in the argument or the
+    // continuation would dangle once they are pulled out of their scopes
+    if mentions(&*arg, &f_v) || mentions(&**cont, &f_v) || mentions(&**cont, &e_v) {
+        return None;
+    }
+
+    Some(AppParts {
+        func,
+        arg,
+        cont: cont.clone(),
+    })
+}
+
+fn mentions(term: &impl BoundTerm<String>, var: &FreeVar<String>) -> bool {
+    let mut found = false;
+    term.visit_vars(&mut |v| {
+        if let Var::Free(fv) = v {
+            found |= fv == var;
+        }
+    });
+    found
+}
+
+// A catamorphism (bottom-up fold) over the three CPS sorts: each
+// constructor has a combining function receiving the already-folded
+// results of its children, so passes like node counts or statistics are
+// a handful of one-liners instead of a full traversal. Scopes are folded
+// raw — the binder pattern is handed over next to the folded body, to
+// use or ignore as the fold pleases.
+pub trait Cata {
+    type U;
+    type K;
+    type C;
+
+    fn c_ucall(&mut self, f: Self::U, v: Self::U, k: Self::K) -> Self::C;
+    fn c_kcall(&mut self, k: Self::K, v: Self::U) -> Self::C;
+    fn c_if(&mut self, c: Self::U, t: Self::C, e: Self::C) -> Self::C;
+
+    fn u_lam(&mut self, param: &Binder<String>, cont: &Binder<String>, body: Self::C) -> Self::U;
+    fn u_fix(&mut self, binder: &Binder<String>, body: Self::U) -> Self::U;
+    fn u_var(&mut self, var: &Var<String>) -> Self::U;
+    fn u_lit(&mut self, lit: &Literal) -> Self::U;
+    fn u_prim(&mut self, prim: &PrimOp) -> Self::U;
+
+    fn k_lam(&mut self, param: &Binder<String>, body: Self::C) -> Self::K;
+    fn k_var(&mut self, var: &Var<String>) -> Self::K;
+    fn k_lit(&mut self, lit: &Literal) -> Self::K;
+}
+
+impl CCall {
+    pub fn fold<F: Cata>(&self, f: &mut F) -> F::C {
+        grow_stack(|| match self {
+            CCall::UCall(fun, v, k) => {
+                let fun = fun.fold(f);
+                let v = v.fold(f);
+                let k = k.fold(f);
+                f.c_ucall(fun, v, k)
+            }
+            CCall::KCall(k, v) => {
+                let k = k.fold(f);
+                let v = v.fold(f);
+                f.c_kcall(k, v)
+            }
+            CCall::If(c, t, e) => {
+                let c = c.fold(f);
+                let t = t.fold(f);
+                let e = e.fold(f);
+                f.c_if(c, t, e)
+            }
+        })
+    }
+}
+
+impl UExpr {
+    pub fn fold<F: Cata>(&self, f: &mut F) -> F::U {
+        grow_stack(|| match self {
+            UExpr::Lam(s) => {
+                let body = s.unsafe_body.unsafe_body.fold(f);
+                f.u_lam(&s.unsafe_pattern, &s.unsafe_body.unsafe_pattern, body)
+            }
+            UExpr::Fix(s) => {
+                let body = s.unsafe_body.fold(f);
+                f.u_fix(&s.unsafe_pattern, body)
+            }
+            UExpr::Var(v) => f.u_var(v),
+            UExpr::Lit(Ignore(l)) => f.u_lit(l),
+            UExpr::Prim(Ignore(p)) => f.u_prim(p),
+        })
+    }
+}
+
+impl KExpr {
+    pub fn fold<F: Cata>(&self, f: &mut F) -> F::K {
+        grow_stack(|| match self {
+            KExpr::Lam(s) => {
+                let body = s.unsafe_body.fold(f);
+                f.k_lam(&s.unsafe_pattern, body)
+            }
+            KExpr::Var(v) => f.k_var(v),
+            KExpr::Lit(Ignore(l)) => f.k_lit(l),
+        })
+    }
+}
+
+// Support for `pretty_print_shared`: the names handed out to shared
+// nodes, and the floated copies of their definitions with references
+// substituted in.
+#[cfg(feature = "pretty")]
+#[derive(Default)]
+struct SharedNames {
+    u: HashMap<*const UExpr, FreeVar<String>>,
+    k: HashMap<*const KExpr, FreeVar<String>>,
+}
+
+#[cfg(feature = "pretty")]
+enum Floated {
+    U(UExpr),
+    K(KExpr),
+}
+
+// The rebuild/replace pair differ only at the root: `rebuild_*` copies
+// the node itself with shared children swapped for their names, while
+// `replace_*` first checks whether the node is itself shared.
+#[cfg(feature = "pretty")]
+fn rebuild_u(expr: &UExpr, names: &SharedNames) -> UExpr {
+    match expr {
+        UExpr::Lam(s) => UExpr::Lam(Scope {
+            unsafe_pattern: s.unsafe_pattern.clone(),
+            unsafe_body: Scope {
+                unsafe_pattern: s.unsafe_body.unsafe_pattern.clone(),
+                unsafe_body: Rc::new(replace_c(&s.unsafe_body.unsafe_body, names)),
+            },
+        }),
+        UExpr::Fix(s) => UExpr::Fix(Scope {
+            unsafe_pattern: s.unsafe_pattern.clone(),
+            unsafe_body: replace_u(&s.unsafe_body, names),
+        }),
+        e @ (UExpr::Var(_) | UExpr::Lit(_) | UExpr::Prim(_)) => e.clone(),
+    }
+}
+
+#[cfg(feature = "pretty")]
+fn rebuild_k(expr: &KExpr, names: &SharedNames) -> KExpr {
+    match expr {
+        KExpr::Lam(s) => KExpr::Lam(Scope {
+            unsafe_pattern: s.unsafe_pattern.clone(),
+            unsafe_body: Rc::new(replace_c(&s.unsafe_body, names)),
+        }),
+        e @ (KExpr::Var(_) | KExpr::Lit(_)) => e.clone(),
+    }
+}
+
+#[cfg(feature = "pretty")]
+fn replace_u(expr: &Rc<UExpr>, names: &SharedNames) -> Rc<UExpr> {
+    match names.u.get(&Rc::as_ptr(expr)) {
+        Some(name) => Rc::new(UExpr::Var(Var::Free(name.clone()))),
+        None => Rc::new(rebuild_u(expr, names)),
+    }
+}
+
+#[cfg(feature = "pretty")]
+fn replace_k(expr: &Rc<KExpr>, names: &SharedNames) -> Rc<KExpr> {
+    match names.k.get(&Rc::as_ptr(expr)) {
+        Some(name) => Rc::new(KExpr::Var(Var::Free(name.clone()))),
+        None => Rc::new(rebuild_k(expr, names)),
+    }
+}
+
+#[cfg(feature = "pretty")]
+fn replace_c(call: &CCall, names: &SharedNames) -> CCall {
+    match call {
+        CCall::UCall(f, v, c) => CCall::UCall(
+            replace_u(f, names),
+            replace_u(v, names),
+            replace_k(c, names),
+        ),
+        CCall::KCall(f, v) => CCall::KCall(replace_k(f, names), replace_u(v, names)),
+        CCall::If(c, t, e) => CCall::If(
+            replace_u(c, names),
+            Rc::new(replace_c(t, names)),
+            Rc::new(replace_c(e, names)),
+        ),
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum SubTerm<'a> {
+    U(&'a UExpr),
+    K(&'a KExpr),
+    C(&'a CCall),
+}
+
+// Pre-order traversal over every nested `UExpr`/`KExpr`/`CCall`. With a
+// `visited` set (see `subterms_deduped`), subterms behind an `Rc` that
+// has already been yielded are skipped, so shared structure is walked
+// only once.
+pub struct SubTerms<'a> {
+    stack: Vec<SubTerm<'a>>,
+    visited: Option<HashSet<*const ()>>,
+}
+
+impl<'a> SubTerms<'a> {
+    fn push_rc<T>(&mut self, rc: &'a Rc<T>, wrap: impl FnOnce(&'a T) -> SubTerm<'a>) {
+        if let Some(visited) = &mut self.visited {
+            if !visited.insert(Rc::as_ptr(rc) as *const ()) {
+                return;
+            }
+        }
+
+        self.stack.push(wrap(rc));
+    }
+}
+
+impl<'a> Iterator for SubTerms<'a> {
+    type Item = SubTerm<'a>;
+
+    fn next(&mut self) -> Option<SubTerm<'a>> {
+        let term = self.stack.pop()?;
+
+        // children are pushed in reverse so they pop out left-to-right
+        match term {
+            SubTerm::C(CCall::UCall(f, v, k)) => {
+                self.push_rc(k, SubTerm::K);
+                self.push_rc(v, SubTerm::U);
+                self.push_rc(f, SubTerm::U);
+            }
+            SubTerm::C(CCall::KCall(k, v)) => {
+                self.push_rc(v, SubTerm::U);
+                self.push_rc(k, SubTerm::K);
+            }
+            SubTerm::C(CCall::If(c, t, e)) => {
+                self.push_rc(e, SubTerm::C);
+                self.push_rc(t, SubTerm::C);
+                self.push_rc(c, SubTerm::U);
+            }
+            SubTerm::U(UExpr::Lam(s)) => {
+                self.push_rc(&s.unsafe_body.unsafe_body, SubTerm::C);
+            }
+            SubTerm::U(UExpr::Fix(s)) => {
+                self.push_rc(&s.unsafe_body, SubTerm::U);
+            }
+            SubTerm::K(KExpr::Lam(s)) => {
+                self.push_rc(&s.unsafe_body, SubTerm::C);
+            }
+            SubTerm::U(UExpr::Var(_) | UExpr::Lit(_) | UExpr::Prim(_))
+            | SubTerm::K(KExpr::Var(_) | KExpr::Lit(_)) => {}
+        }
+
+        Some(term)
+    }
+}
+
+impl CCall {
+    pub fn subterms(&self) -> SubTerms<'_> {
+        SubTerms {
+            stack: vec![SubTerm::C(self)],
+            visited: None,
+        }
+    }
+
+    pub fn subterms_deduped(&self) -> SubTerms<'_> {
+        SubTerms {
+            stack: vec![SubTerm::C(self)],
+            visited: Some(HashSet::new()),
+        }
+    }
+}
+
+// Why a CPS term is malformed: the user and continuation namespaces
+// crossed somewhere, or a scope's binding structure is inconsistent.
+#[derive(Debug, Clone, PartialEq)]
+pub enum CpsError {
+    // a continuation binder's variable appeared in user position
+    ContVarInUserPosition(Var<String>),
+    // a user binder's variable appeared in continuation position
+    UserVarInContPosition(Var<String>),
+    // a bound variable pointing outside its enclosing scopes, or at a
+    // binder its scope doesn't have; unbinding such a term panics deep
+    // inside moniker, so it is rejected up front instead
+    MalformedScope(Var<String>),
+}
+
+impl fmt::Display for CpsError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            CpsError::ContVarInUserPosition(v) => {
+                write!(f, "continuation variable {} used in user position", v)
+            }
+            CpsError::UserVarInContPosition(v) => {
+                write!(f, "user variable {} used in continuation position", v)
+            }
+            CpsError::MalformedScope(v) => {
+                write!(f, "bound variable {} escapes its scope", v)
+            }
+        }
+    }
+}
+
+impl std::error::Error for CpsError {}
+
+// What a scope level binds; the traversal pushes one entry per `Scope`,
+// so a bound occurrence's `ScopeOffset` indexes this stack from its top.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum BinderKind {
+    User,
+    Cont,
+}
+
+impl CCall {
+    // Checks that the user and continuation namespaces never cross:
+    // every bound variable in `UExpr` position resolves to a user binder
+    // (a lambda's value parameter or a fix), and every bound variable in
+    // `KExpr` position to a continuation binder (a `UExpr::Lam`'s second
+    // parameter). Custom lowering and rewrite passes that mix the two up
+    // produce terms the evaluator would misinterpret; this catches them
+    // early. Free variables can't be classified — the halt continuation
+    // and host bindings are free by design — so they pass. Bound
+    // variables whose indices don't fit the scopes around them are
+    // rejected as `MalformedScope`.
+    pub fn check_cps_wellformed(&self) -> std::result::Result<(), CpsError> {
+        wf_c(self, &mut Vec::new())
+    }
+
+    // As `into_fexpr`, but validates the term first, so malformed input
+    // from custom construction surfaces as an error rather than a panic
+    // inside moniker's unbind.
+    pub fn try_into_fexpr(self) -> std::result::Result<FExpr, CpsError> {
+        self.check_cps_wellformed()?;
+        Ok(self.into_fexpr())
+    }
+}
+
+fn wf_kind(
+    scopes: &[BinderKind],
+    v: &Var<String>,
+) -> std::result::Result<Option<BinderKind>, CpsError> {
+    match v {
+        Var::Bound(bv) => {
+            // every scope in a CPS term binds exactly one variable
+            if (bv.scope.0 as usize) >= scopes.len() || bv.binder.to_usize() != 0 {
+                return Err(CpsError::MalformedScope(v.clone()));
+            }
+            Ok(Some(scopes[scopes.len() - 1 - bv.scope.0 as usize]))
+        }
+        Var::Free(_) => Ok(None),
+    }
+}
+
+fn wf_c(call: &CCall, scopes: &mut Vec<BinderKind>) -> std::result::Result<(), CpsError> {
+    grow_stack(|| match call {
+        CCall::UCall(f, v, k) => {
+            wf_u(f, scopes)?;
+            wf_u(v, scopes)?;
+            wf_k(k, scopes)
+        }
+        CCall::KCall(k, v) => {
+            wf_k(k, scopes)?;
+            wf_u(v, scopes)
+        }
+        CCall::If(c, t, e) => {
+            wf_u(c, scopes)?;
+            wf_c(t, scopes)?;
+            wf_c(e, scopes)
+        }
+    })
+}
+
+fn wf_u(expr: &UExpr, scopes: &mut Vec<BinderKind>) -> std::result::Result<(), CpsError> {
+    match expr {
+        UExpr::Var(v) => match wf_kind(scopes, v)? {
+            Some(BinderKind::Cont) => Err(CpsError::ContVarInUserPosition(v.clone())),
+            _ => Ok(()),
+        },
+        UExpr::Lit(_) | UExpr::Prim(_) => Ok(()),
+        UExpr::Lam(s) => {
+            scopes.push(BinderKind::User);
+            scopes.push(BinderKind::Cont);
+            let result = wf_c(&s.unsafe_body.unsafe_body, scopes);
+            scopes.pop();
+            scopes.pop();
+            result
+        }
+        UExpr::Fix(s) => {
+            scopes.push(BinderKind::User);
+            let result = wf_u(&s.unsafe_body, scopes);
+            scopes.pop();
+            result
+        }
+    }
+}
+
+fn wf_k(expr: &KExpr, scopes: &mut Vec<BinderKind>) -> std::result::Result<(), CpsError> {
+    match expr {
+        KExpr::Var(v) => match wf_kind(scopes, v)? {
+            Some(BinderKind::User) => Err(CpsError::UserVarInContPosition(v.clone())),
+            _ => Ok(()),
+        },
+        KExpr::Lit(_) => Ok(()),
+        KExpr::Lam(s) => {
+            scopes.push(BinderKind::User);
+            let result = wf_c(&s.unsafe_body, scopes);
+            scopes.pop();
+            result
+        }
+    }
+}
+
+pub fn t_k(expr: Expr, k: Rc<KExpr>) -> CCall {
+    #[cfg(feature = "tracing")]
+    let _span = tracing::debug_span!("cps_transform", input_size = expr.size_hint()).entered();
+
+    t_k_inner(expr, k)
+}
+
+// What a lowering cost: node counts on both sides of the transform,
+// wall-clock time, and how many fresh administrative variables the
+// output picked up. Surfaced by `transform_with_stats` so users tuning
+// a program can see the CPS expansion without external instrumentation.
+#[derive(Debug, Clone)]
+pub struct TransformReport {
+    pub input_nodes: usize,
+    pub output_nodes: usize,
+    pub duration: std::time::Duration,
+    pub fresh_vars_created: usize,
+}
+
+// As `t_k`, also measuring the work: the report's node counts come from
+// `Expr::size_hint` and a fold over the output, and the fresh-variable
+// count is the number of binders in the output that were not binders in
+// the input — exactly the administrative continuations and desugaring
+// variables the transform invented.
+pub fn transform_with_stats(expr: Expr, k: Rc<KExpr>) -> (CCall, TransformReport) {
+    let input_nodes = expr.size_hint();
+    let input_binders: HashSet<FreeVar<String>> =
+        crate::check::use_counts(&expr).into_keys().collect();
+
+    let start = std::time::Instant::now();
+    let call = t_k(expr, k);
+    let duration = start.elapsed();
+
+    let mut metrics = Metrics {
+        binders: Vec::new(),
+    };
+    let output_nodes = call.fold(&mut metrics);
+    let fresh_vars_created = metrics
+        .binders
+        .iter()
+        .filter(|b| !input_binders.contains(b))
+        .count();
+
+    (
+        call,
+        TransformReport {
+            input_nodes,
+            output_nodes,
+            duration,
+            fresh_vars_created,
+        },
+    )
+}
+
+// A node-counting fold that also remembers every binder it passes, for
+// the fresh-variable half of the report.
+struct Metrics {
+    binders: Vec<FreeVar<String>>,
+}
+
+impl Cata for Metrics {
+    type U = usize;
+    type K = usize;
+    type C = usize;
+
+    fn c_ucall(&mut self, f: usize, v: usize, k: usize) -> usize {
+        1 + f + v + k
+    }
+
+    fn c_kcall(&mut self, k: usize, v: usize) -> usize {
+        1 + k + v
+    }
+
+    fn c_if(&mut self, c: usize, t: usize, e: usize) -> usize {
+        1 + c + t + e
+    }
+
+    fn u_lam(&mut self, param: &Binder<String>, cont: &Binder<String>, body: usize) -> usize {
+        self.binders.push(param.0.clone());
+        self.binders.push(cont.0.clone());
+        1 + body
+    }
+
+    fn u_fix(&mut self, binder: &Binder<String>, body: usize) -> usize {
+        self.binders.push(binder.0.clone());
+        1 + body
+    }
+
+    fn u_var(&mut self, _: &Var<String>) -> usize {
+        1
+    }
+
+    fn u_lit(&mut self, _: &Literal) -> usize {
+        1
+    }
+
+    fn u_prim(&mut self, _: &PrimOp) -> usize {
+        1
+    }
+
+    fn k_lam(&mut self, param: &Binder<String>, body: usize) -> usize {
+        self.binders.push(param.0.clone());
+        1 + body
+    }
+
+    fn k_var(&mut self, _: &Var<String>) -> usize {
+        1
+    }
+
+    fn k_lit(&mut self, _: &Literal) -> usize {
+        1
+    }
+}
+
+// The transform with a caller-supplied top-level continuation, for
+// composing lowered fragments into larger programs: `halt` can be a
+// bare variable the host binds, or a `KExpr::Lam` that post-processes
+// the result (applying it to a printer, say) before handing it on.
+pub fn t_with_halt(expr: Expr, halt: Rc<KExpr>) -> CCall {
+    t_k(expr, halt)
+}
+
+// Call-by-name lowering. The `App` rule in `t_k` evaluates the argument
+// before the call, so every program gets call-by-value semantics; here
+// the argument is packed into a thunk instead — `App(f, e)` lowers the
+// way `App(f, Delay(e))` would — and every occurrence of a lambda
+// parameter forces the thunk it now holds, so an argument's effects
+// (and divergence) happen only if the parameter is actually used. The
+// thunk's at-most-once cache makes this call-by-need rather than
+// textbook call-by-name: a parameter used twice runs its argument once.
+pub fn t_k_by_name(expr: Expr, k: Rc<KExpr>) -> CCall {
+    t_k(by_name(&expr, &mut Vec::new()), k)
+}
+
+// The source-to-source half of `t_k_by_name`: `Delay` around `App`
+// arguments, `Force` around the occurrences of `Lam` parameters. The
+// scopes are rebuilt raw, like `Expr::rename_free`, with a stack
+// recording which binders hold thunks — only `Lam` parameters do. A
+// `fix` variable names the closure itself, a rest parameter holds an
+// already-built list, and `let`/`letrec` keep their strict sequencing.
+fn by_name(expr: &Expr, thunked: &mut Vec<Vec<bool>>) -> Expr {
+    match expr {
+        Expr::Var(Var::Bound(bv)) => {
+            let idx = thunked.len() - 1 - bv.scope.0 as usize;
+            if thunked[idx][bv.binder.0 as usize] {
+                Expr::Force(Rc::new(expr.clone()))
+            } else {
+                expr.clone()
+            }
+        }
+        Expr::Var(Var::Free(_)) | Expr::Lit(_) | Expr::Error(_) | Expr::Fail => expr.clone(),
+        Expr::Lam(s) => {
+            thunked.push(vec![true]);
+            let body = by_name(&s.unsafe_body, thunked);
+            thunked.pop();
+            Expr::Lam(Scope {
+                unsafe_pattern: s.unsafe_pattern.clone(),
+                unsafe_body: Rc::new(body),
+            })
+        }
+        Expr::LamRest(s) => {
+            thunked.push(vec![false]);
+            let body = by_name(&s.unsafe_body, thunked);
+            thunked.pop();
+            Expr::LamRest(Scope {
+                unsafe_pattern: s.unsafe_pattern.clone(),
+                unsafe_body: Rc::new(body),
+            })
+        }
+        Expr::Fix(s) => {
+            thunked.push(vec![false]);
+            let body = by_name(&s.unsafe_body, thunked);
+            thunked.pop();
+            Expr::Fix(Scope {
+                unsafe_pattern: s.unsafe_pattern.clone(),
+                unsafe_body: Rc::new(body),
+            })
+        }
+        Expr::App(f, e) => Expr::App(
+            Rc::new(by_name(f, thunked)),
+            Rc::new(Expr::Delay(Rc::new(by_name(e, thunked)))),
+        ),
+        Expr::Apply(f, l) => Expr::Apply(
+            Rc::new(by_name(f, thunked)),
+            Rc::new(by_name(l, thunked)),
+        ),
+        Expr::Let(v, s) => {
+            let v = by_name(v, thunked);
+            thunked.push(vec![false]);
+            let body = by_name(&s.unsafe_body, thunked);
+            thunked.pop();
+            Expr::Let(
+                Rc::new(v),
+                Scope {
+                    unsafe_pattern: s.unsafe_pattern.clone(),
+                    unsafe_body: Rc::new(body),
+                },
+            )
+        }
+        Expr::Assert(c, msg) => Expr::Assert(Rc::new(by_name(c, thunked)), msg.clone()),
+        Expr::Not(e) => Expr::Not(Rc::new(by_name(e, thunked))),
+        Expr::Cast(kind, e) => Expr::Cast(*kind, Rc::new(by_name(e, thunked))),
+        Expr::Delay(e) => Expr::Delay(Rc::new(by_name(e, thunked))),
+        Expr::Force(e) => Expr::Force(Rc::new(by_name(e, thunked))),
+        Expr::Tuple(es) => {
+            Expr::Tuple(es.iter().map(|e| Rc::new(by_name(e, thunked))).collect())
+        }
+        Expr::Proj(i, e) => Expr::Proj(*i, Rc::new(by_name(e, thunked))),
+        Expr::Bin(op, a, b) => Expr::Bin(
+            *op,
+            Rc::new(by_name(a, thunked)),
+            Rc::new(by_name(b, thunked)),
+        ),
+        Expr::If(c, t, e) => Expr::If(
+            Rc::new(by_name(c, thunked)),
+            Rc::new(by_name(t, thunked)),
+            Rc::new(by_name(e, thunked)),
+        ),
+        Expr::Cond(clauses, els) => Expr::Cond(
+            clauses
+                .iter()
+                .map(|(test, body)| {
+                    (
+                        Rc::new(by_name(test, thunked)),
+                        Rc::new(by_name(body, thunked)),
+                    )
+                })
+                .collect(),
+            Rc::new(by_name(els, thunked)),
+        ),
+        Expr::While(c, b) => Expr::While(
+            Rc::new(by_name(c, thunked)),
+            Rc::new(by_name(b, thunked)),
+        ),
+        Expr::TryFinally(b, f) => Expr::TryFinally(
+            Rc::new(by_name(b, thunked)),
+            Rc::new(by_name(f, thunked)),
+        ),
+        Expr::Amb(a, b) => Expr::Amb(
+            Rc::new(by_name(a, thunked)),
+            Rc::new(by_name(b, thunked)),
+        ),
+        Expr::LetRecMany(s) => {
+            let (defs, body) = &s.unsafe_body;
+            thunked.push(vec![false; s.unsafe_pattern.len()]);
+            let defs = defs.iter().map(|d| Rc::new(by_name(d, thunked))).collect();
+            let body = by_name(body, thunked);
+            thunked.pop();
+            Expr::LetRecMany(Scope {
+                unsafe_pattern: s.unsafe_pattern.clone(),
+                unsafe_body: (defs, Rc::new(body)),
+            })
+        }
+    }
+}
+
+fn t_k_inner(expr: Expr, k: Rc<KExpr>) -> CCall {
+    match expr {
+        e @ (Expr::Lam(_) | Expr::Fix(_) | Expr::Var(_) | Expr::Lit(_)) => {
+            CCall::KCall(k, Rc::new(m(e)))
+        }
+        Expr::If(c, t, e) => {
+            let c_v = FreeVar::fresh_named("cond");
+
+            let body = if matches!(*k, KExpr::Var(_)) {
+                // the continuation is already a bare variable, so both
+                // branches can name it directly — no join point needed
+                CCall::If(
+                    Rc::new(UExpr::Var(Var::Free(c_v.clone()))),
+                    Rc::new(t_k_inner(clone_rc(t), k.clone())),
+                    Rc::new(t_k_inner(clone_rc(e), k)),
+                )
+            } else {
+                // bind the outer continuation to a variable so both
+                // branches can share it without duplicating the whole term
+                let j_v = FreeVar::fresh_named("j");
+                let branches = CCall::If(
+                    Rc::new(UExpr::Var(Var::Free(c_v.clone()))),
+                    Rc::new(t_k_inner(clone_rc(t), Rc::new(KExpr::Var(Var::Free(j_v.clone()))))),
+                    Rc::new(t_k_inner(clone_rc(e), Rc::new(KExpr::Var(Var::Free(j_v.clone()))))),
+                );
+                CCall::UCall(
+                    Rc::new(UExpr::lam(FreeVar::fresh_named("_"), j_v, branches)),
+                    Rc::new(UExpr::Lit(Ignore(Literal::Void))),
+                    k,
+                )
+            };
+
+            t_k_inner(
+                clone_rc(c),
+                Rc::new(KExpr::Lam(Scope::new(Binder(c_v), Rc::new(body)))),
+            )
+        }
+        // evaluate the bound value into the source binder itself, so the
+        // continuation parameter carries the user's name and read-back
+        // can tell this binding apart from an administrative one
+        Expr::Let(v, s) => {
+            let (Binder(x), body) = s.unbind();
+            t_k_inner(
+                clone_rc(v),
+                Rc::new(KExpr::Lam(Scope::new(
+                    Binder(x),
+                    Rc::new(t_k_inner(clone_rc(body), k)),
+                ))),
+            )
+        }
+        // desugar to nested `If`s; the `If` lowering above already binds
+        // the outer continuation to a join point, so bodies share it
+        Expr::Cond(clauses, els) => t_k_inner(desugar_cond(clauses, els), k),
+        // desugar to a `fix` that re-enters itself in tail position
+        Expr::While(c, b) => t_k_inner(desugar_while(c, b), k),
+        Expr::LetRecMany(s) => t_k_inner(desugar_letrec_many(s), k),
+        // clone_rc moves the node out via Rc::try_unwrap when it's the
+        // only owner, so on a freshly-built tree these recursions don't
+        // clone; shared subtrees get a shallow (refcount-bumping) clone
+        Expr::Assert(c, Ignore(msg)) => {
+            let b_v = FreeVar::fresh_named("b");
+
+            t_k_inner(
+                clone_rc(c),
+                Rc::new(KExpr::Lam(Scope::new(
+                    Binder(b_v.clone()),
+                    Rc::new(CCall::UCall(
+                        Rc::new(UExpr::Prim(Ignore(PrimOp::Assert(msg)))),
+                        Rc::new(UExpr::Var(Var::Free(b_v))),
+                        k,
+                    )),
+                ))),
+            )
+        }
+        // a marker aborts the moment control reaches it, through the
+        // assert primitive with the parser's message
+        Expr::Error(Ignore(msg)) => CCall::UCall(
+            Rc::new(UExpr::Prim(Ignore(PrimOp::Assert(msg)))),
+            Rc::new(UExpr::Lit(Ignore(Literal::Bool(false)))),
+            k,
+        ),
+        Expr::LamRest(s) => {
+            // lower the underlying lambda as an ordinary value, then tag
+            // it through the rest primitive so `apply` knows to stop
+            // unrolling at it
+            let (p, t) = s.unbind();
+            let k_v = FreeVar::fresh_named("k");
+            let body = t_c(clone_rc(t), k_v.clone());
+
+            CCall::UCall(
+                Rc::new(UExpr::Prim(Ignore(PrimOp::Rest))),
+                Rc::new(UExpr::Lam(Scope::new(p, Scope::new(Binder(k_v), Rc::new(body))))),
+                k,
+            )
+        }
+        Expr::Not(e) => {
+            let b_v = FreeVar::fresh_named("b");
+
+            t_k_inner(
+                clone_rc(e),
+                Rc::new(KExpr::Lam(Scope::new(
+                    Binder(b_v.clone()),
+                    Rc::new(CCall::UCall(
+                        Rc::new(UExpr::Prim(Ignore(PrimOp::Not))),
+                        Rc::new(UExpr::Var(Var::Free(b_v))),
+                        k,
+                    )),
+                ))),
+            )
+        }
+        Expr::Cast(Ignore(kind), e) => {
+            let v_v = FreeVar::fresh_named("v");
+
+            t_k_inner(
+                clone_rc(e),
+                Rc::new(KExpr::Lam(Scope::new(
+                    Binder(v_v.clone()),
+                    Rc::new(CCall::UCall(
+                        Rc::new(UExpr::Prim(Ignore(PrimOp::Cast(kind)))),
+                        Rc::new(UExpr::Var(Var::Free(v_v))),
+                        k,
+                    )),
+                ))),
+            )
+        }
+        // the body is packed into a lambda unevaluated, so the thunk
+        // captures it without running it
+        Expr::Delay(e) => {
+            let arg_v = FreeVar::fresh_named("_");
+            let lam = Expr::Lam(Scope::new(Binder(arg_v), e));
+
+            CCall::UCall(
+                Rc::new(UExpr::Prim(Ignore(PrimOp::Delay))),
+                Rc::new(m(lam)),
+                k,
+            )
+        }
+        Expr::Force(e) => {
+            let t_v = FreeVar::fresh_named("t");
+
+            t_k_inner(
+                clone_rc(e),
+                Rc::new(KExpr::Lam(Scope::new(
+                    Binder(t_v.clone()),
+                    Rc::new(CCall::UCall(
+                        Rc::new(UExpr::Prim(Ignore(PrimOp::Force))),
+                        Rc::new(UExpr::Var(Var::Free(t_v))),
+                        k,
+                    )),
+                ))),
+            )
+        }
+        // both pieces are packed into thunks — the cleanup must not run
+        // yet, and the body has to run under the primitive's wrapper so
+        // its completion (or an unwinding error) triggers the cleanup
+        Expr::TryFinally(b, f) => {
+            let pair = Expr::Tuple(vec![
+                Rc::new(Expr::Lam(Scope::new(Binder(FreeVar::fresh_named("_")), b))),
+                Rc::new(Expr::Lam(Scope::new(Binder(FreeVar::fresh_named("_")), f))),
+            ]);
+            let t_v = FreeVar::fresh_named("t");
+
+            t_k_inner(
+                pair,
+                Rc::new(KExpr::Lam(Scope::new(
+                    Binder(t_v.clone()),
+                    Rc::new(CCall::UCall(
+                        Rc::new(UExpr::Prim(Ignore(PrimOp::Finally))),
+                        Rc::new(UExpr::Var(Var::Free(t_v))),
+                        k,
+                    )),
+                ))),
+            )
+        }
+        // both alternatives are packed into thunks — only the chosen one
+        // may run, and the other has to wait on the backtrack stack with
+        // the continuation captured here
+        Expr::Amb(a, b) => {
+            let pair = Expr::Tuple(vec![
+                Rc::new(Expr::Lam(Scope::new(Binder(FreeVar::fresh_named("_")), a))),
+                Rc::new(Expr::Lam(Scope::new(Binder(FreeVar::fresh_named("_")), b))),
+            ]);
+            let t_v = FreeVar::fresh_named("t");
+
+            t_k_inner(
+                pair,
+                Rc::new(KExpr::Lam(Scope::new(
+                    Binder(t_v.clone()),
+                    Rc::new(CCall::UCall(
+                        Rc::new(UExpr::Prim(Ignore(PrimOp::Amb))),
+                        Rc::new(UExpr::Var(Var::Free(t_v))),
+                        k,
+                    )),
+                ))),
+            )
+        }
+        // the continuation is discarded at runtime, but the call still
+        // names it so a `fail` with no pending choice can report a frame
+        Expr::Fail => CCall::UCall(
+            Rc::new(UExpr::Prim(Ignore(PrimOp::Fail))),
+            Rc::new(UExpr::Lit(Ignore(Literal::Void))),
+            k,
+        ),
+        Expr::Tuple(es) => {
+            let n = es.len();
+
+            // nothing to evaluate, but the collector still needs one
+            // application to produce the (empty) tuple, so feed it void
+            if n == 0 {
+                return CCall::UCall(
+                    Rc::new(UExpr::Prim(Ignore(PrimOp::Tuple(0)))),
+                    Rc::new(UExpr::Lit(Ignore(Literal::Void))),
+                    k,
+                );
+            }
+
+            let t_vs: Vec<FreeVar<String>> =
+                (0..n).map(|_| FreeVar::fresh_named("t")).collect();
+            let q_vs: Vec<FreeVar<String>> =
+                (1..n).map(|_| FreeVar::fresh_named("q")).collect();
+
+            // the application chain, built from the last component
+            // inward: each partial application is named and immediately
+            // fed the next component
+            let mut cont = k;
+            for i in (1..n).rev() {
+                let call = CCall::UCall(
+                    Rc::new(UExpr::Var(Var::Free(q_vs[i - 1].clone()))),
+                    Rc::new(UExpr::Var(Var::Free(t_vs[i].clone()))),
+                    cont,
+                );
+                cont = Rc::new(KExpr::Lam(Scope::new(
+                    Binder(q_vs[i - 1].clone()),
+                    Rc::new(call),
+                )));
+            }
+            let mut call = CCall::UCall(
+                Rc::new(UExpr::Prim(Ignore(PrimOp::Tuple(n)))),
+                Rc::new(UExpr::Var(Var::Free(t_vs[0].clone()))),
+                cont,
+            );
+
+            // evaluate the components left to right, wrapping outward
+            // from the chain
+            for (e, t_v) in es.into_iter().zip(t_vs).rev() {
+                call = t_k_inner(
+                    clone_rc(e),
+                    Rc::new(KExpr::Lam(Scope::new(Binder(t_v), Rc::new(call)))),
+                );
+            }
+
+            call
+        }
+        Expr::Proj(Ignore(i), e) => {
+            let t_v = FreeVar::fresh_named("t");
+
+            t_k_inner(
+                clone_rc(e),
+                Rc::new(KExpr::Lam(Scope::new(
+                    Binder(t_v.clone()),
+                    Rc::new(CCall::UCall(
+                        Rc::new(UExpr::Prim(Ignore(PrimOp::Proj(i)))),
+                        Rc::new(UExpr::Var(Var::Free(t_v))),
+                        k,
+                    )),
+                ))),
+            )
+        }
+        Expr::Bin(Ignore(op), a, b) => {
+            let a_v = FreeVar::fresh_named("a");
+            let b_v = FreeVar::fresh_named("b");
+            let p_v = FreeVar::fresh_named("p");
+
+            // evaluate the left operand, then the right, then feed them
+            // one at a time through the curried primitive
+            t_k_inner(
+                clone_rc(a),
+                Rc::new(KExpr::Lam(Scope::new(
+                    Binder(a_v.clone()),
+                    Rc::new(t_k_inner(
+                        clone_rc(b),
+                        Rc::new(KExpr::Lam(Scope::new(
+                            Binder(b_v.clone()),
+                            Rc::new(CCall::UCall(
+                                Rc::new(UExpr::Prim(Ignore(PrimOp::Binary(op)))),
+                                Rc::new(UExpr::Var(Var::Free(a_v))),
+                                Rc::new(KExpr::Lam(Scope::new(
+                                    Binder(p_v.clone()),
+                                    Rc::new(CCall::UCall(
+                                        Rc::new(UExpr::Var(Var::Free(p_v))),
+                                        Rc::new(UExpr::Var(Var::Free(b_v))),
+                                        k,
+                                    )),
+                                ))),
+                            )),
+                        ))),
+                    )),
+                ))),
+            )
+        }
+        Expr::Apply(f, l) => {
+            let f_v = FreeVar::fresh_named("f");
+            let l_v = FreeVar::fresh_named("l");
+            let p_v = FreeVar::fresh_named("p");
+
+            // evaluate the function, then the list; the prim consumes the
+            // list first so the partial application holds only a literal,
+            // and the evaluator unrolls it when the function arrives
+            t_k_inner(
+                clone_rc(f),
                 Rc::new(KExpr::Lam(Scope::new(
                     Binder(f_v.clone()),
-                    Rc::new(t_k(
+                    Rc::new(t_k_inner(
+                        clone_rc(l),
+                        Rc::new(KExpr::Lam(Scope::new(
+                            Binder(l_v.clone()),
+                            Rc::new(CCall::UCall(
+                                Rc::new(UExpr::Prim(Ignore(PrimOp::Apply))),
+                                Rc::new(UExpr::Var(Var::Free(l_v))),
+                                Rc::new(KExpr::Lam(Scope::new(
+                                    Binder(p_v.clone()),
+                                    Rc::new(CCall::UCall(
+                                        Rc::new(UExpr::Var(Var::Free(p_v))),
+                                        Rc::new(UExpr::Var(Var::Free(f_v))),
+                                        k,
+                                    )),
+                                ))),
+                            )),
+                        ))),
+                    )),
+                ))),
+            )
+        }
+        Expr::App(f, e) => {
+            // when the continuation is already a bare variable, the usual
+            // eta wrapper (rv. k rv) would allocate a continuation per
+            // call; passing it straight through keeps source-level tail
+            // calls from stacking continuations
+            let cont = if matches!(*k, KExpr::Var(_)) {
+                k
+            } else {
+                let rv_v = FreeVar::fresh_named("rv");
+                Rc::new(KExpr::Lam(Scope::new(
+                    Binder(rv_v.clone()),
+                    Rc::new(CCall::KCall(k, Rc::new(UExpr::Var(Var::Free(rv_v))))),
+                )))
+            };
+
+            let f_v = FreeVar::fresh_named("f");
+            let e_v = FreeVar::fresh_named("e");
+
+            t_k_inner(
+                clone_rc(f),
+                Rc::new(KExpr::Lam(Scope::new(
+                    Binder(f_v.clone()),
+                    Rc::new(t_k_inner(
                         clone_rc(e),
                         Rc::new(KExpr::Lam(Scope::new(
                             Binder(e_v.clone()),
@@ -248,19 +2112,133 @@ pub fn t_k(expr: Expr, k: Rc<KExpr>) -> CCall {
     }
 }
 
+fn desugar_cond(clauses: Vec<(Rc<Expr>, Rc<Expr>)>, els: Rc<Expr>) -> Expr {
+    clauses
+        .into_iter()
+        .rev()
+        .fold(clone_rc(els), |acc, (test, body)| {
+            Expr::If(test, body, Rc::new(acc))
+        })
+}
+
+// (fix loop. λ_. if c then (let _ = body in loop void) else void) void
+//
+// All three binders are fresh, so nothing in the condition or body can
+// be captured. The re-entry `loop void` sits in tail position of both
+// the `let` and the `if`, so its lowered call passes the outer
+// continuation through unchanged and the loop runs in constant stack.
+fn desugar_while(c: Rc<Expr>, b: Rc<Expr>) -> Expr {
+    let loop_v = FreeVar::fresh_named("loop");
+    let arg_v = FreeVar::fresh_named("_");
+    let junk_v = FreeVar::fresh_named("_");
+
+    let void = || Rc::new(Expr::Lit(Ignore(Literal::Void)));
+
+    let again = Expr::App(Rc::new(Expr::Var(Var::Free(loop_v.clone()))), void());
+    let tick = Expr::Let(b, Scope::new(Binder(junk_v), Rc::new(again)));
+    let step = Expr::If(c, Rc::new(tick), void());
+    let lam = Expr::Lam(Scope::new(Binder(arg_v), Rc::new(step)));
+
+    Expr::App(
+        Rc::new(Expr::Fix(Scope::new(Binder(loop_v), Rc::new(lam)))),
+        void(),
+    )
+}
+
+// letrec f1 = e1 … fn = en in body
+// ⇒ let group = fix group. λ_. (e1', …, en') in body'
+//
+// where each definition ei' sees fi as the eta-expansion
+// λx. (proj i (group void)) x — re-entering the group builds the tuple
+// of closures afresh, so by the time a cross-reference is *called* the
+// whole group exists — and the body sees fi as proj i (group void),
+// evaluated once. Every introduced binder is fresh, so nothing in the
+// definitions or body can be captured.
+fn desugar_letrec_many(s: LetRecScope) -> Expr {
+    let (binders, (defs, body)) = s.unbind();
+    let group_v = FreeVar::fresh_named("group");
+    let arg_v = FreeVar::fresh_named("_");
+
+    let void = || Rc::new(Expr::Lit(Ignore(Literal::Void)));
+    let group = |group_v: &FreeVar<String>| {
+        Rc::new(Expr::App(
+            Rc::new(Expr::Var(Var::Free(group_v.clone()))),
+            void(),
+        ))
+    };
+
+    // the tuple of definitions, each name let-bound to its eta-expansion
+    let tuple = Rc::new(Expr::Tuple(defs));
+    let wrapped = binders
+        .iter()
+        .enumerate()
+        .rev()
+        .fold(tuple, |acc, (i, Binder(fi))| {
+            let x = FreeVar::fresh_named("x");
+            let eta = Expr::Lam(Scope::new(
+                Binder(x.clone()),
+                Rc::new(Expr::App(
+                    Rc::new(Expr::Proj(Ignore(i), group(&group_v))),
+                    Rc::new(Expr::Var(Var::Free(x))),
+                )),
+            ));
+            Rc::new(Expr::Let(Rc::new(eta), Scope::new(Binder(fi.clone()), acc)))
+        });
+
+    let fixed = Expr::Fix(Scope::new(
+        Binder(group_v.clone()),
+        Rc::new(Expr::Lam(Scope::new(Binder(arg_v), wrapped))),
+    ));
+
+    // the body gets the finished closures, projected out once
+    let body = binders
+        .iter()
+        .enumerate()
+        .rev()
+        .fold(body, |acc, (i, Binder(fi))| {
+            Rc::new(Expr::Let(
+                Rc::new(Expr::Proj(Ignore(i), group(&group_v))),
+                Scope::new(Binder(fi.clone()), acc),
+            ))
+        });
+
+    Expr::Let(Rc::new(fixed), Scope::new(Binder(group_v), body))
+}
+
 fn t_c(expr: Expr, c: FreeVar<String>) -> CCall {
     let c_v = Rc::new(KExpr::Var(Var::Free(c)));
     match expr {
-        e @ (Expr::Lam(_) | Expr::Var(_) | Expr::Lit(_)) => CCall::KCall(c_v, Rc::new(m(e))),
+        e @ (Expr::Lam(_) | Expr::Fix(_) | Expr::Var(_) | Expr::Lit(_)) => {
+            CCall::KCall(c_v, Rc::new(m(e)))
+        }
+        e @ (Expr::Assert(_, _)
+        | Expr::Bin(_, _, _)
+        | Expr::Not(_)
+        | Expr::Cast(_, _)
+        | Expr::Error(_)
+        | Expr::Delay(_)
+        | Expr::Force(_)
+        | Expr::Tuple(_)
+        | Expr::Proj(_, _)
+        | Expr::Let(_, _)
+        | Expr::LamRest(_)
+        | Expr::If(_, _, _)
+        | Expr::Cond(_, _)
+        | Expr::While(_, _)
+        | Expr::LetRecMany(_)
+        | Expr::TryFinally(_, _)
+        | Expr::Amb(_, _)
+        | Expr::Fail
+        | Expr::Apply(_, _)) => t_k_inner(e, c_v),
         Expr::App(f, e) => {
             let f_v = FreeVar::fresh_named("f");
             let e_v = FreeVar::fresh_named("e");
 
-            t_k(
+            t_k_inner(
                 clone_rc(f),
                 Rc::new(KExpr::Lam(Scope::new(
                     Binder(f_v.clone()),
-                    Rc::new(t_k(
+                    Rc::new(t_k_inner(
                         clone_rc(e),
                         Rc::new(KExpr::Lam(Scope::new(
                             Binder(e_v.clone()),
@@ -285,8 +2263,727 @@ fn m(expr: Expr) -> UExpr {
             let body = t_c(clone_rc(t), k.clone());
             UExpr::Lam(Scope::new(p, Scope::new(Binder(k), Rc::new(body))))
         }
+        Expr::Fix(s) => {
+            let (f, t) = s.unbind();
+            UExpr::Fix(Scope::new(f, Rc::new(m(clone_rc(t)))))
+        }
         Expr::Var(v) => UExpr::Var(v),
         Expr::Lit(v) => UExpr::Lit(v),
         _ => unreachable!(),
     }
 }
+
+#[cfg(all(test, feature = "pretty"))]
+mod tests {
+    use super::*;
+    use termcolor::Buffer;
+
+    #[test]
+    fn a_lowered_lambda_binds_its_parameter_and_its_continuation() {
+        let halt = FreeVar::fresh_named("halt");
+        let call = t_k(
+            crate::expr!(lam x -> var x),
+            Rc::new(KExpr::Var(Var::Free(halt))),
+        );
+
+        // the lowering hands the lambda value straight to the
+        // continuation, so the `UExpr::Lam` sits in a tail call
+        let lam = match &call {
+            CCall::KCall(_, v) => v,
+            call => panic!("expected a tail call, got {:?}", call),
+        };
+
+        let names: Vec<String> = lam
+            .bound_vars()
+            .iter()
+            .map(|v| v.pretty_name.as_deref().unwrap_or("_").to_owned())
+            .collect();
+        assert_eq!(names, ["x", "k"]);
+    }
+
+    #[test]
+    fn catamorphism_node_count_matches_the_traversal() {
+        struct NodeCount;
+
+        impl Cata for NodeCount {
+            type U = usize;
+            type K = usize;
+            type C = usize;
+
+            fn c_ucall(&mut self, f: usize, v: usize, k: usize) -> usize {
+                f + v + k + 1
+            }
+            fn c_kcall(&mut self, k: usize, v: usize) -> usize {
+                k + v + 1
+            }
+            fn c_if(&mut self, c: usize, t: usize, e: usize) -> usize {
+                c + t + e + 1
+            }
+            fn u_lam(&mut self, _: &Binder<String>, _: &Binder<String>, body: usize) -> usize {
+                body + 1
+            }
+            fn u_fix(&mut self, _: &Binder<String>, body: usize) -> usize {
+                body + 1
+            }
+            fn u_var(&mut self, _: &Var<String>) -> usize {
+                1
+            }
+            fn u_lit(&mut self, _: &Literal) -> usize {
+                1
+            }
+            fn u_prim(&mut self, _: &PrimOp) -> usize {
+                1
+            }
+            fn k_lam(&mut self, _: &Binder<String>, body: usize) -> usize {
+                body + 1
+            }
+            fn k_var(&mut self, _: &Var<String>) -> usize {
+                1
+            }
+            fn k_lit(&mut self, _: &Literal) -> usize {
+                1
+            }
+        }
+
+        let x = FreeVar::fresh_named("x");
+        let halt = FreeVar::fresh_named("halt");
+        let term = t_k(
+            Expr::If(
+                Rc::new(Expr::Lit(Ignore(Literal::Bool(true)))),
+                Rc::new(Expr::App(
+                    Rc::new(Expr::Lam(Scope::new(
+                        Binder(x.clone()),
+                        Rc::new(Expr::Var(Var::Free(x))),
+                    ))),
+                    Rc::new(Expr::Lit(Ignore(Literal::Int(1)))),
+                )),
+                Rc::new(Expr::Lit(Ignore(Literal::Int(2)))),
+            ),
+            Rc::new(KExpr::Var(Var::Free(halt))),
+        );
+
+        assert_eq!(term.fold(&mut NodeCount), term.subterms().count());
+    }
+
+    #[test]
+    fn shared_subterms_float_to_one_definition() {
+        let x = FreeVar::fresh_named("x");
+        let halt = FreeVar::fresh_named("halt");
+        let cond = |v: &FreeVar<String>| Rc::new(UExpr::Var(Var::Free(v.clone())));
+
+        // one continuation lambda, used from three different call sites
+        let k_shared = Rc::new(KExpr::Lam(Scope::new(
+            Binder(x.clone()),
+            Rc::new(CCall::KCall(
+                Rc::new(KExpr::Var(Var::Free(halt))),
+                Rc::new(UExpr::Var(Var::Free(x))),
+            )),
+        )));
+        let use_site = |v: &FreeVar<String>| {
+            Rc::new(CCall::KCall(
+                k_shared.clone(),
+                Rc::new(UExpr::Var(Var::Free(v.clone()))),
+            ))
+        };
+
+        let a = FreeVar::fresh_named("a");
+        let b = FreeVar::fresh_named("b");
+        let c = FreeVar::fresh_named("c");
+        let term = CCall::If(
+            cond(&a),
+            Rc::new(CCall::If(cond(&b), use_site(&a), use_site(&b))),
+            use_site(&c),
+        );
+
+        let mut buf = Buffer::no_color();
+        term.pretty_print_shared(&mut buf).unwrap();
+        let rendered = String::from_utf8(buf.into_inner()).unwrap();
+
+        // one definition, referenced by name at all three use sites
+        assert_eq!(rendered.matches("let ").count(), 1);
+        let name = rendered
+            .strip_prefix("let ")
+            .unwrap()
+            .split_whitespace()
+            .next()
+            .unwrap();
+        assert_eq!(rendered.matches(name).count(), 4);
+    }
+
+    #[test]
+    fn bare_var_continuations_skip_the_if_join_point() {
+        let halt = FreeVar::fresh_named("halt");
+        let x = FreeVar::fresh_named("x");
+        let branch = |b| Rc::new(Expr::Lit(Ignore(Literal::Bool(b))));
+        let cond = Expr::If(branch(true), branch(true), branch(false));
+
+        // against a bare variable the branches just name it...
+        let direct = t_k(cond.clone(), Rc::new(KExpr::Var(Var::Free(halt.clone()))));
+        assert!(!direct
+            .subterms()
+            .any(|t| matches!(t, SubTerm::U(UExpr::Lam(_)))));
+
+        // ...whereas a non-variable continuation still gets bound once
+        let wrapper = Rc::new(KExpr::Lam(Scope::new(
+            Binder(x.clone()),
+            Rc::new(CCall::KCall(
+                Rc::new(KExpr::Var(Var::Free(halt))),
+                Rc::new(UExpr::Var(Var::Free(x))),
+            )),
+        )));
+        let joined = t_k(cond, wrapper.clone());
+        assert!(joined
+            .subterms()
+            .any(|t| matches!(t, SubTerm::U(UExpr::Lam(_)))));
+
+        // the join point costs nodes beyond the wrapper's own
+        let wrapper_nodes = 3;
+        assert!(joined.subterms().count() > direct.subterms().count() + wrapper_nodes);
+    }
+
+    #[test]
+    fn lam_accessors_recover_the_lowered_structure() {
+        let x = FreeVar::fresh_named("x");
+        let lam = Expr::Lam(Scope::new(
+            Binder(x.clone()),
+            Rc::new(Expr::Var(Var::Free(x))),
+        ));
+        let lowered = m(lam);
+
+        assert_eq!(
+            lowered.user_param().unwrap().pretty_name.as_deref(),
+            Some("x")
+        );
+        assert_eq!(
+            lowered.cont_param().unwrap().pretty_name.as_deref(),
+            Some("k")
+        );
+
+        // identity lowers to (k x): the body calls the continuation
+        // binder with the user parameter
+        let (param, cont, body) = lowered.lam_parts().unwrap();
+        match &*body {
+            CCall::KCall(k, v) => {
+                assert!(matches!(&**k, KExpr::Var(Var::Free(fv)) if *fv == cont));
+                assert!(matches!(&**v, UExpr::Var(Var::Free(fv)) if *fv == param));
+            }
+            c => panic!("expected a continuation call, got {:?}", c),
+        }
+
+        assert!(UExpr::Lit(Ignore(Literal::Void)).lam_parts().is_none());
+    }
+
+    #[test]
+    fn subterms_walks_in_preorder() {
+        let f = FreeVar::fresh_named("f");
+        let x = FreeVar::fresh_named("x");
+        let k = FreeVar::fresh_named("k");
+
+        // (f x k)
+        let call = CCall::ucall(
+            UExpr::Var(Var::Free(f)),
+            UExpr::Var(Var::Free(x)),
+            KExpr::Var(Var::Free(k)),
+        );
+
+        assert_eq!(call.subterms().count(), 4);
+    }
+
+    #[test]
+    fn deduped_subterms_skip_shared_rcs() {
+        let f = Rc::new(UExpr::Var(Var::Free(FreeVar::fresh_named("f"))));
+        let k = Rc::new(KExpr::Var(Var::Free(FreeVar::fresh_named("k"))));
+
+        // the same Rc in both function and argument position
+        let call = CCall::UCall(f.clone(), f, k);
+
+        assert_eq!(call.subterms().count(), 4);
+        assert_eq!(call.subterms_deduped().count(), 3);
+    }
+
+    #[test]
+    fn no_color_sink_gets_no_escape_codes() {
+        // hold the env lock: the colored half below needs `NO_COLOR`
+        // to stay unset while it renders
+        let _guard = crate::render::COLOR_ENV_LOCK
+            .lock()
+            .unwrap_or_else(|e| e.into_inner());
+
+        let term = CCall::kcall(
+            KExpr::Var(Var::Free(FreeVar::fresh_named("halt"))),
+            UExpr::Lit(Ignore(Literal::Int(1))),
+        );
+
+        let mut plain = Buffer::no_color();
+        term.pretty_print(&mut plain).unwrap();
+        assert!(!plain.as_slice().contains(&0x1b));
+
+        let mut colored = Buffer::ansi();
+        term.pretty_print(&mut colored).unwrap();
+        assert!(colored.as_slice().contains(&0x1b));
+    }
+
+    #[test]
+    fn ccall_converts_into_fexpr_generically() {
+        fn flatten(t: impl Into<FExpr>) -> FExpr {
+            t.into()
+        }
+
+        let halt = FreeVar::fresh_named("halt");
+        let call = CCall::kcall(
+            KExpr::Var(Var::Free(halt)),
+            UExpr::Lit(Ignore(Literal::Int(1))),
+        );
+
+        let via_into = flatten(call.clone());
+        assert!(FExpr::term_eq(&via_into, &call.into_fexpr()));
+    }
+
+    #[test]
+    fn tail_hints_mark_only_tail_calls() {
+        let f = FreeVar::fresh_named("f");
+        let x = FreeVar::fresh_named("x");
+
+        // λx. f (f x): the inner call returns to a continuation lambda,
+        // the outer call passes the enclosing continuation straight through
+        let expr = Expr::Lam(Scope::new(
+            Binder(x.clone()),
+            Rc::new(Expr::App(
+                Rc::new(Expr::Var(Var::Free(f.clone()))),
+                Rc::new(Expr::App(
+                    Rc::new(Expr::Var(Var::Free(f))),
+                    Rc::new(Expr::Var(Var::Free(x))),
+                )),
+            )),
+        ));
+
+        let lowered = m(expr);
+        let body = match &lowered {
+            UExpr::Lam(s) => s.unsafe_body.unsafe_body.clone(),
+            u => panic!("expected a lambda, got {:?}", u),
+        };
+
+        let mut buf = Buffer::no_color();
+        body.pretty_print_tail_hints(&mut buf).unwrap();
+        let hinted = String::from_utf8(buf.into_inner()).unwrap();
+        assert_eq!(hinted.matches('↰').count(), 1);
+
+        let mut buf = Buffer::no_color();
+        body.pretty_print(&mut buf).unwrap();
+        let plain = String::from_utf8(buf.into_inner()).unwrap();
+        assert!(!plain.contains('↰'));
+    }
+
+    #[test]
+    fn width_zero_renders_on_one_line() {
+        let halt = FreeVar::fresh_named("halt");
+
+        // enough nested bindings to overflow a 70-column line
+        let mut term = CCall::kcall(
+            KExpr::Var(Var::Free(halt)),
+            UExpr::Lit(Ignore(Literal::Int(0))),
+        );
+        for i in 0..20 {
+            term = CCall::kcall(
+                KExpr::lam(FreeVar::fresh_named(format!("binding{}", i)), term),
+                UExpr::Lit(Ignore(Literal::Int(i))),
+            );
+        }
+
+        let mut wrapped = Buffer::no_color();
+        term.pretty_print(&mut wrapped).unwrap();
+        assert!(wrapped.as_slice().contains(&b'\n'));
+
+        let mut flat = Buffer::no_color();
+        term.pretty_print_width(0, &mut flat).unwrap();
+        assert!(!flat.as_slice().contains(&b'\n'));
+    }
+
+    #[test]
+    fn smart_constructors_match_raw_variants() {
+        let x = FreeVar::fresh_named("x");
+        let k = FreeVar::fresh_named("k");
+        let halt = FreeVar::fresh_named("halt");
+
+        let built = CCall::ucall(
+            UExpr::lam(
+                x.clone(),
+                k.clone(),
+                CCall::kcall(
+                    KExpr::Var(Var::Free(k.clone())),
+                    UExpr::Var(Var::Free(x.clone())),
+                ),
+            ),
+            UExpr::Lit(Ignore(Literal::Int(1))),
+            KExpr::Var(Var::Free(halt.clone())),
+        );
+
+        let raw = CCall::UCall(
+            Rc::new(UExpr::Lam(Scope::new(
+                Binder(x.clone()),
+                Scope::new(
+                    Binder(k.clone()),
+                    Rc::new(CCall::KCall(
+                        Rc::new(KExpr::Var(Var::Free(k))),
+                        Rc::new(UExpr::Var(Var::Free(x))),
+                    )),
+                ),
+            ))),
+            Rc::new(UExpr::Lit(Ignore(Literal::Int(1)))),
+            Rc::new(KExpr::Var(Var::Free(halt))),
+        );
+
+        assert!(CCall::term_eq(&built, &raw));
+    }
+
+    #[test]
+    fn the_transform_output_is_cps_wellformed() {
+        let x = FreeVar::fresh_named("x");
+        let halt = FreeVar::fresh_named("halt");
+        let term = t_k(
+            Expr::App(
+                Rc::new(Expr::Lam(Scope::new(
+                    Binder(x.clone()),
+                    Rc::new(Expr::Var(Var::Free(x))),
+                ))),
+                Rc::new(Expr::Lit(Ignore(Literal::Int(1)))),
+            ),
+            Rc::new(KExpr::Var(Var::Free(halt))),
+        );
+
+        assert_eq!(term.check_cps_wellformed(), Ok(()));
+    }
+
+    #[test]
+    fn a_leaked_continuation_parameter_is_rejected() {
+        let p = FreeVar::fresh_named("p");
+        let k = FreeVar::fresh_named("k");
+        let halt = FreeVar::fresh_named("halt");
+
+        // λ(p k). k k — the continuation parameter escapes into user
+        // (argument) position
+        let bad_body = CCall::KCall(
+            Rc::new(KExpr::Var(Var::Free(k.clone()))),
+            Rc::new(UExpr::Var(Var::Free(k.clone()))),
+        );
+        let term = CCall::KCall(
+            Rc::new(KExpr::Var(Var::Free(halt))),
+            Rc::new(UExpr::lam(p, k, bad_body)),
+        );
+
+        assert!(matches!(
+            term.check_cps_wellformed(),
+            Err(CpsError::ContVarInUserPosition(_))
+        ));
+    }
+
+    #[test]
+    fn shallow_clones_share_and_deep_clones_do_not() {
+        let x = FreeVar::fresh_named("x");
+        let halt = FreeVar::fresh_named("halt");
+        let term = t_k(
+            Expr::App(
+                Rc::new(Expr::Lam(Scope::new(
+                    Binder(x.clone()),
+                    Rc::new(Expr::Var(Var::Free(x))),
+                ))),
+                Rc::new(Expr::Lit(Ignore(Literal::Int(1)))),
+            ),
+            Rc::new(KExpr::Var(Var::Free(halt))),
+        );
+
+        let shallow = term.clone();
+        assert!(term.shares_with(&shallow));
+
+        // a deep clone is a different tree with the same shape
+        let deep = term.deep_clone();
+        assert!(!term.shares_with(&deep));
+        assert!(CCall::term_eq(&term, &deep));
+    }
+
+    #[test]
+    fn flatten_deep_term() {
+        let x = FreeVar::fresh_named("x");
+        let mut term = CCall::KCall(
+            Rc::new(KExpr::Var(Var::Free(FreeVar::fresh_named("halt")))),
+            Rc::new(UExpr::Lit(Ignore(Literal::Void))),
+        );
+
+        // the spine is built with raw scopes for the same reason as in
+        // `pretty_print_deep_term` below
+        for _ in 0..100_000 {
+            term = CCall::KCall(
+                Rc::new(KExpr::Lam(Scope {
+                    unsafe_pattern: Binder(x.clone()),
+                    unsafe_body: Rc::new(term),
+                })),
+                Rc::new(UExpr::Lit(Ignore(Literal::Void))),
+            );
+        }
+
+        let flat = term.into_fexpr();
+
+        // dropping either tree would itself recurse once per level
+        std::mem::forget(flat);
+    }
+
+    #[test]
+    fn pretty_print_deep_term() {
+        let x = FreeVar::fresh_named("x");
+        let mut term = CCall::KCall(
+            Rc::new(KExpr::Var(Var::Free(FreeVar::fresh_named("halt")))),
+            Rc::new(UExpr::Lit(Ignore(Literal::Void))),
+        );
+
+        // build the spine with raw scopes: `Scope::new` re-walks the whole
+        // body each time, which would make this quadratic
+        for _ in 0..100_000 {
+            term = CCall::KCall(
+                Rc::new(KExpr::Lam(Scope {
+                    unsafe_pattern: Binder(x.clone()),
+                    unsafe_body: Rc::new(term),
+                })),
+                Rc::new(UExpr::Lit(Ignore(Literal::Void))),
+            );
+        }
+
+        term.pretty_print(Buffer::no_color()).unwrap();
+
+        // dropping the term would itself recurse once per level
+        std::mem::forget(term);
+    }
+
+    #[cfg(feature = "eval")]
+    #[test]
+    fn a_supplied_halt_post_processes_the_result() {
+        use crate::eval::{run_ccall, Env, Value};
+        use crate::prelude::{app, identity, lit};
+
+        let halt = FreeVar::fresh_named("halt");
+        let r_v = FreeVar::fresh_named("r");
+
+        // a continuation that adds a recognizable marker to the result
+        // before handing it to the real halt
+        let wrap = KExpr::Lam(Scope::new(
+            Binder(r_v.clone()),
+            Rc::new(CCall::UCall(
+                Rc::new(UExpr::Prim(Ignore(PrimOp::BinaryWith(
+                    BinOp::Add,
+                    Literal::Int(100),
+                )))),
+                Rc::new(UExpr::Var(Var::Free(r_v))),
+                Rc::new(KExpr::Var(Var::Free(halt.clone()))),
+            )),
+        ));
+
+        let call = t_with_halt(app(identity(), lit(Literal::Int(1))), Rc::new(wrap));
+
+        let value = run_ccall(call, Env::new().insert(halt, Value::Halt)).unwrap();
+        assert!(matches!(value, Value::Lit(Literal::Int(101))));
+    }
+
+    #[cfg(feature = "eval")]
+    #[test]
+    fn call_by_name_ignores_a_divergent_argument() {
+        use crate::eval::{run_budgeted, run_ccall, Budgeted, Env, Value};
+        use crate::prelude::{app, constant, lam, lit, var};
+
+        // Ω = (λx. x x) (λx. x x)
+        let omega = {
+            let x = FreeVar::fresh_named("x");
+            let half = lam(x.clone(), app(var(&x), var(&x)));
+            app(half.clone(), half)
+        };
+
+        // (λx. λy. x) 42 Ω: only `y` names the divergent argument
+        let program =
+            || app(app(constant(), lit(Literal::Int(42))), omega.clone());
+
+        // call-by-value runs Ω before the call and never finishes
+        assert!(matches!(
+            run_budgeted(program(), None, 10_000).unwrap(),
+            Budgeted::Paused(_)
+        ));
+
+        // call-by-name never forces the unused thunk
+        let halt = FreeVar::fresh_named("halt");
+        let call = t_k_by_name(program(), Rc::new(KExpr::Var(Var::Free(halt.clone()))));
+        let value = run_ccall(call, Env::new().insert(halt, Value::Halt)).unwrap();
+        assert!(matches!(value, Value::Lit(Literal::Int(42))));
+    }
+
+    #[test]
+    fn a_malformed_scope_is_an_error_not_a_panic() {
+        use moniker::{BinderIndex, BoundVar, ScopeOffset};
+
+        let halt = || Rc::new(KExpr::Var(Var::Free(FreeVar::fresh_named("halt"))));
+
+        // a bound variable with no enclosing scope at all
+        let stray = Var::Bound(BoundVar {
+            scope: ScopeOffset(0),
+            binder: BinderIndex(0),
+            pretty_name: Some("x".to_owned()),
+        });
+        let call = CCall::KCall(halt(), Rc::new(UExpr::Var(stray)));
+        assert!(matches!(
+            call.try_into_fexpr(),
+            Err(CpsError::MalformedScope(_))
+        ));
+
+        // a binder index beyond the single binder its scope carries
+        let deep = Var::Bound(BoundVar {
+            scope: ScopeOffset(0),
+            binder: BinderIndex(1),
+            pretty_name: Some("x".to_owned()),
+        });
+        let lam = KExpr::Lam(Scope::new(
+            Binder(FreeVar::fresh_named("x")),
+            Rc::new(CCall::KCall(halt(), Rc::new(UExpr::Var(deep)))),
+        ));
+        let call = CCall::KCall(
+            Rc::new(lam),
+            Rc::new(UExpr::Lit(Ignore(Literal::Int(1)))),
+        );
+        assert!(matches!(
+            call.try_into_fexpr(),
+            Err(CpsError::MalformedScope(_))
+        ));
+    }
+
+    #[test]
+    fn single_steps_run_an_identity_application_to_a_stuck_value() {
+        use crate::prelude::{app, lam, lit, var};
+
+        let x = FreeVar::fresh_named("x");
+        let halt = FreeVar::fresh_named("halt");
+        let expr = app(lam(x.clone(), var(&x)), lit(Literal::Int(5)));
+
+        let mut term = t_k(expr, Rc::new(KExpr::Var(Var::Free(halt.clone()))));
+        let mut steps = 0;
+        while let Some(next) = term.clone().reduce_once() {
+            term = next;
+            steps += 1;
+            assert!(steps < 32, "the step sequence should be finite");
+        }
+
+        // the binding of the lambda, the call itself, and the return
+        assert!(steps >= 2, "took only {} steps", steps);
+        let expected = CCall::KCall(
+            Rc::new(KExpr::Var(Var::Free(halt))),
+            Rc::new(UExpr::Lit(Ignore(Literal::Int(5)))),
+        );
+        assert!(CCall::term_eq(&term, &expected), "stuck at {:?}", term);
+    }
+
+    #[test]
+    fn a_lowered_application_gives_up_its_parts() {
+        use crate::prelude::{app, var};
+
+        let f = FreeVar::fresh_named("f");
+        let x = FreeVar::fresh_named("x");
+        let halt = FreeVar::fresh_named("halt");
+
+        let call = t_k(
+            app(var(&f), var(&x)),
+            Rc::new(KExpr::Var(Var::Free(halt.clone()))),
+        );
+
+        let parts = match_app_cps(&call).expect("the lowering shape should match");
+        assert!(UExpr::term_eq(&parts.func, &UExpr::Var(Var::Free(f))));
+        assert!(UExpr::term_eq(&parts.arg, &UExpr::Var(Var::Free(x))));
+        assert!(KExpr::term_eq(&parts.cont, &KExpr::Var(Var::Free(halt))));
+
+        // anything that isn't the application chain stays unmatched
+        let plain = CCall::KCall(
+            Rc::new(KExpr::Var(Var::Free(FreeVar::fresh_named("k")))),
+            Rc::new(UExpr::Lit(Ignore(Literal::Int(1)))),
+        );
+        assert!(match_app_cps(&plain).is_none());
+    }
+
+    #[test]
+    fn the_transform_report_matches_independent_counts() {
+        use crate::prelude::{app, lam, lit, var};
+
+        struct Survey {
+            nodes: usize,
+            binders: Vec<FreeVar<String>>,
+        }
+
+        impl Cata for Survey {
+            type U = usize;
+            type K = usize;
+            type C = usize;
+
+            fn c_ucall(&mut self, f: usize, v: usize, k: usize) -> usize {
+                1 + f + v + k
+            }
+            fn c_kcall(&mut self, k: usize, v: usize) -> usize {
+                1 + k + v
+            }
+            fn c_if(&mut self, c: usize, t: usize, e: usize) -> usize {
+                1 + c + t + e
+            }
+            fn u_lam(&mut self, p: &Binder<String>, c: &Binder<String>, body: usize) -> usize {
+                self.binders.push(p.0.clone());
+                self.binders.push(c.0.clone());
+                1 + body
+            }
+            fn u_fix(&mut self, b: &Binder<String>, body: usize) -> usize {
+                self.binders.push(b.0.clone());
+                1 + body
+            }
+            fn u_var(&mut self, _: &Var<String>) -> usize {
+                1
+            }
+            fn u_lit(&mut self, _: &Literal) -> usize {
+                1
+            }
+            fn u_prim(&mut self, _: &PrimOp) -> usize {
+                1
+            }
+            fn k_lam(&mut self, p: &Binder<String>, body: usize) -> usize {
+                self.binders.push(p.0.clone());
+                1 + body
+            }
+            fn k_var(&mut self, _: &Var<String>) -> usize {
+                1
+            }
+            fn k_lit(&mut self, _: &Literal) -> usize {
+                1
+            }
+        }
+
+        let x = FreeVar::fresh_named("x");
+        let halt = FreeVar::fresh_named("halt");
+        let expr = app(
+            lam(
+                x.clone(),
+                Expr::Bin(
+                    Ignore(BinOp::Add),
+                    Rc::new(var(&x)),
+                    Rc::new(var(&x)),
+                ),
+            ),
+            lit(Literal::Int(5)),
+        );
+        let input_nodes = expr.size_hint();
+
+        let (call, report) =
+            transform_with_stats(expr, Rc::new(KExpr::Var(Var::Free(halt))));
+
+        let mut survey = Survey {
+            nodes: 0,
+            binders: Vec::new(),
+        };
+        survey.nodes = call.fold(&mut survey);
+
+        assert_eq!(report.input_nodes, input_nodes);
+        assert_eq!(report.output_nodes, survey.nodes);
+        let fresh = survey.binders.iter().filter(|b| **b != x).count();
+        assert_eq!(report.fresh_vars_created, fresh);
+        // lowering an application invents at least one administrative
+        // continuation
+        assert!(report.fresh_vars_created >= 1);
+    }
+}
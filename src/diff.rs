@@ -0,0 +1,88 @@
+use similar::{ChangeTag, TextDiff};
+
+use crate::cont_expr::CCall;
+
+// Developer tooling for inspecting what an optimization pass did to a
+// term: render both sides monochrome and diff them line by line.
+
+pub fn render_plain(call: &CCall) -> String {
+    let mut buf = termcolor::Buffer::no_color();
+    call.pretty_print(&mut buf)
+        .expect("rendering to a buffer can't fail");
+    String::from_utf8(buf.into_inner()).expect("rendered output is utf-8")
+}
+
+// A line-level diff between two terms, with `-`/`+` markers on removed
+// and added lines. Returns an empty string when the renderings agree.
+pub fn diff_ccalls(old: &CCall, new: &CCall) -> String {
+    let old = render_plain(old);
+    let new = render_plain(new);
+
+    if old == new {
+        return String::new();
+    }
+
+    let diff = TextDiff::from_lines(&old, &new);
+    let mut out = String::new();
+
+    for change in diff.iter_all_changes() {
+        let marker = match change.tag() {
+            ChangeTag::Delete => '-',
+            ChangeTag::Insert => '+',
+            ChangeTag::Equal => ' ',
+        };
+
+        out.push(marker);
+        out.push(' ');
+        out.push_str(change.value());
+        if !change.value().ends_with('\n') {
+            out.push('\n');
+        }
+    }
+
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cont_expr::{KExpr, UExpr};
+    use crate::literals::Literal;
+    use crate::opt::specialize;
+    use moniker::{FreeVar, Ignore, Var};
+
+    #[test]
+    fn beta_reduction_produces_a_nonempty_diff() {
+        let x = FreeVar::fresh_named("x");
+        let k = FreeVar::fresh_named("k");
+        let halt = FreeVar::fresh_named("halt");
+
+        let call = CCall::ucall(
+            UExpr::lam(
+                x.clone(),
+                k.clone(),
+                CCall::kcall(KExpr::Var(Var::Free(k)), UExpr::Var(Var::Free(x))),
+            ),
+            UExpr::Lit(Ignore(Literal::Int(5))),
+            KExpr::Var(Var::Free(halt)),
+        );
+
+        let reduced = specialize(call.clone());
+        let diff = diff_ccalls(&call, &reduced);
+
+        assert!(!diff.is_empty());
+        assert!(diff.lines().any(|l| l.starts_with('-')));
+        assert!(diff.lines().any(|l| l.starts_with('+')));
+    }
+
+    #[test]
+    fn identical_terms_diff_to_nothing() {
+        let halt = FreeVar::fresh_named("halt");
+        let call = CCall::kcall(
+            KExpr::Var(Var::Free(halt)),
+            UExpr::Lit(Ignore(Literal::Int(1))),
+        );
+
+        assert_eq!(diff_ccalls(&call, &call.clone()), "");
+    }
+}
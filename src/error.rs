@@ -0,0 +1,132 @@
+// A top-level error enum over everything the crate can fail with, so
+// embedders can thread the whole pipeline through `?` into a single
+// error type (or a `Box<dyn std::error::Error>`). Each leaf type also
+// implements `std::error::Error` on its own; this enum just adds the
+// `From` conversions and `source()` chaining on top.
+
+use crate::cont_expr;
+use crate::flat_expr;
+use crate::text;
+
+#[cfg(feature = "eval")]
+use crate::eval;
+#[cfg(feature = "parser")]
+use crate::parser;
+
+#[derive(Debug)]
+pub enum Error {
+    // printing an `FExpr` to the textual format
+    Print(text::PrintError),
+    // parsing the textual `FExpr` format
+    Parse(text::ParseError),
+    // a malformed CPS term, from a custom lowering or rewrite pass
+    Cps(cont_expr::CpsError),
+    // reading an `FExpr` back as a direct-style `Expr`
+    DirectStyle(flat_expr::DirectStyleError),
+    // parsing infix source through the configurable front-end
+    #[cfg(feature = "parser")]
+    InfixParse(parser::ParseError),
+    #[cfg(feature = "eval")]
+    Runtime(eval::RuntimeError),
+}
+
+impl std::fmt::Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Error::Print(e) => write!(f, "print error: {}", e),
+            Error::Parse(e) => write!(f, "parse error: {}", e),
+            Error::Cps(e) => write!(f, "malformed cps term: {}", e),
+            Error::DirectStyle(e) => write!(f, "not direct style: {}", e),
+            #[cfg(feature = "parser")]
+            Error::InfixParse(e) => write!(f, "parse error: {}", e),
+            #[cfg(feature = "eval")]
+            Error::Runtime(e) => write!(f, "runtime error: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Print(e) => Some(e),
+            Error::Parse(e) => Some(e),
+            Error::Cps(e) => Some(e),
+            Error::DirectStyle(e) => Some(e),
+            #[cfg(feature = "parser")]
+            Error::InfixParse(e) => Some(e),
+            #[cfg(feature = "eval")]
+            Error::Runtime(e) => Some(e),
+        }
+    }
+}
+
+impl From<text::PrintError> for Error {
+    fn from(e: text::PrintError) -> Error {
+        Error::Print(e)
+    }
+}
+
+impl From<text::ParseError> for Error {
+    fn from(e: text::ParseError) -> Error {
+        Error::Parse(e)
+    }
+}
+
+impl From<cont_expr::CpsError> for Error {
+    fn from(e: cont_expr::CpsError) -> Error {
+        Error::Cps(e)
+    }
+}
+
+impl From<flat_expr::DirectStyleError> for Error {
+    fn from(e: flat_expr::DirectStyleError) -> Error {
+        Error::DirectStyle(e)
+    }
+}
+
+#[cfg(feature = "parser")]
+impl From<parser::ParseError> for Error {
+    fn from(e: parser::ParseError) -> Error {
+        Error::InfixParse(e)
+    }
+}
+
+#[cfg(feature = "eval")]
+impl From<eval::RuntimeError> for Error {
+    fn from(e: eval::RuntimeError) -> Error {
+        Error::Runtime(e)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_errors_convert_and_chain() {
+        // `?` converts the leaf into the top-level enum
+        let run = || -> Result<(), Error> {
+            crate::text::parse("(lam1")?;
+            Ok(())
+        };
+        let err = run().unwrap_err();
+
+        assert!(matches!(err, Error::Parse(_)));
+        let shown = err.to_string();
+        assert!(shown.starts_with("parse error:"), "got {:?}", shown);
+        assert!(std::error::Error::source(&err).is_some());
+    }
+
+    #[cfg(feature = "eval")]
+    #[test]
+    fn runtime_errors_display_their_kind() {
+        use crate::eval::{ErrorKind, RuntimeError};
+
+        let err: Error = RuntimeError::from(ErrorKind::DivideByZero).into();
+        assert_eq!(err.to_string(), "runtime error: divide by zero");
+
+        // a boxed dyn chain reaches the leaf
+        let boxed: Box<dyn std::error::Error> = Box::new(err);
+        assert_eq!(boxed.source().unwrap().to_string(), "divide by zero");
+    }
+}
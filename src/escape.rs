@@ -0,0 +1,210 @@
+// Escape analysis for the planned bytecode VM: a closure whose value
+// never outlives the frame that creates it can live on the stack instead
+// of the heap. In CPS form "outliving the frame" has a syntactic shape —
+// a lambda escapes when it is handed to something we can't see inside
+// (passed to an unknown callee, or to a continuation variable, which is
+// how CPS spells "returned"). A lambda stays local when it is applied on
+// the spot, or bound by a syntactically-known callee whose parameter is
+// only ever used in callee position.
+//
+// The analysis is conservative: anything it can't prove local is
+// classified as escaping.
+
+use std::collections::HashMap;
+
+use moniker::{Binder, FreeVar, Var};
+
+use crate::flat_expr::FExpr;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Escape {
+    Local,
+    Escapes,
+}
+
+// Classifies every lambda node in `expr`, keyed by node address, so
+// callers holding an `Rc` to a lambda can look its verdict up with
+// `Rc::as_ptr`.
+pub fn classify(expr: &FExpr) -> HashMap<*const FExpr, Escape> {
+    let mut out = HashMap::new();
+    visit(expr, &mut out);
+    out
+}
+
+fn visit(expr: &FExpr, out: &mut HashMap<*const FExpr, Escape>) {
+    match expr {
+        FExpr::LamOne(s) => visit(&s.unsafe_body, out),
+        FExpr::LamTwo(s) => visit(&s.unsafe_body.unsafe_body, out),
+        FExpr::Fix(s) => visit(&s.unsafe_body, out),
+        FExpr::Var(_) | FExpr::Lit(_) | FExpr::Prim(_) => {}
+        FExpr::CallOne(f, v) => {
+            mark(f, Escape::Local, out);
+            mark(v, arg_verdict(f, 0), out);
+            visit(f, out);
+            visit(v, out);
+        }
+        FExpr::CallTwo(f, v, c) => {
+            mark(f, Escape::Local, out);
+            mark(v, arg_verdict(f, 0), out);
+            mark(c, arg_verdict(f, 1), out);
+            visit(f, out);
+            visit(v, out);
+            visit(c, out);
+        }
+        FExpr::If(c, t, e) => {
+            // a bare lambda in condition position flows nowhere we track
+            mark(c, Escape::Escapes, out);
+            visit(c, out);
+            visit(t, out);
+            visit(e, out);
+        }
+    }
+}
+
+fn mark(expr: &FExpr, verdict: Escape, out: &mut HashMap<*const FExpr, Escape>) {
+    if matches!(expr, FExpr::LamOne(_) | FExpr::LamTwo(_)) {
+        // a shared subterm keeps the verdict of its first occurrence,
+        // which is at least as conservative as re-deriving it would be
+        // only if occurrences agree; prefer the escaping one
+        let entry = out.entry(expr as *const FExpr).or_insert(verdict);
+        if verdict == Escape::Escapes {
+            *entry = Escape::Escapes;
+        }
+    }
+}
+
+// What happens to the `idx`-th argument of a call with callee `f`: bound
+// locally only when `f` is a syntactically-known lambda whose matching
+// parameter is exclusively applied, never stored or passed on.
+fn arg_verdict(f: &FExpr, idx: usize) -> Escape {
+    let applied_only = match (f, idx) {
+        (FExpr::LamOne(s), 0) => {
+            let (Binder(param), body) = s.clone().unbind();
+            only_called(&body, &param)
+        }
+        (FExpr::LamTwo(s), 0) => {
+            let (Binder(param), rest) = s.clone().unbind();
+            let (_, body) = rest.unbind();
+            only_called(&body, &param)
+        }
+        (FExpr::LamTwo(s), 1) => {
+            let (_, rest) = s.clone().unbind();
+            let (Binder(cont), body) = rest.unbind();
+            only_called(&body, &cont)
+        }
+        _ => false,
+    };
+
+    if applied_only {
+        Escape::Local
+    } else {
+        Escape::Escapes
+    }
+}
+
+// Every free occurrence of `var` in `expr` is the callee of a call.
+fn only_called(expr: &FExpr, var: &FreeVar<String>) -> bool {
+    let head_ok = |f: &FExpr| {
+        matches!(f, FExpr::Var(Var::Free(v)) if v == var) || only_called(f, var)
+    };
+
+    match expr {
+        FExpr::Var(Var::Free(v)) => v != var,
+        FExpr::Var(Var::Bound(_)) | FExpr::Lit(_) | FExpr::Prim(_) => true,
+        FExpr::LamOne(s) => only_called(&s.unsafe_body, var),
+        FExpr::LamTwo(s) => only_called(&s.unsafe_body.unsafe_body, var),
+        FExpr::Fix(s) => only_called(&s.unsafe_body, var),
+        FExpr::CallOne(f, v) => head_ok(f) && only_called(v, var),
+        FExpr::CallTwo(f, v, c) => head_ok(f) && only_called(v, var) && only_called(c, var),
+        FExpr::If(c, t, e) => {
+            only_called(c, var) && only_called(t, var) && only_called(e, var)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use moniker::{Ignore, Scope};
+    use std::rc::Rc;
+
+    use crate::literals::Literal;
+
+    fn fvar(v: &FreeVar<String>) -> Rc<FExpr> {
+        Rc::new(FExpr::Var(Var::Free(v.clone())))
+    }
+
+    #[test]
+    fn locally_applied_helper_does_not_escape() {
+        let h = FreeVar::fresh_named("h");
+        let k = FreeVar::fresh_named("k");
+        let x = FreeVar::fresh_named("x");
+        let k2 = FreeVar::fresh_named("k2");
+        let halt = FreeVar::fresh_named("halt");
+
+        // ((lambda (h k) (h 1 k)) (lambda (x k2) (k2 x)) halt): the
+        // helper is bound to h, which is only ever applied
+        let helper = Rc::new(FExpr::LamTwo(Scope::new(
+            Binder(x.clone()),
+            Scope::new(Binder(k2.clone()), Rc::new(FExpr::CallOne(fvar(&k2), fvar(&x)))),
+        )));
+        let consumer = Rc::new(FExpr::LamTwo(Scope::new(
+            Binder(h.clone()),
+            Scope::new(
+                Binder(k.clone()),
+                Rc::new(FExpr::CallTwo(
+                    fvar(&h),
+                    Rc::new(FExpr::Lit(Ignore(Literal::Int(1)))),
+                    fvar(&k),
+                )),
+            ),
+        )));
+        let term = FExpr::CallTwo(consumer.clone(), helper.clone(), fvar(&halt));
+
+        let verdicts = classify(&term);
+        assert_eq!(verdicts[&Rc::as_ptr(&helper)], Escape::Local);
+        assert_eq!(verdicts[&Rc::as_ptr(&consumer)], Escape::Local);
+    }
+
+    #[test]
+    fn returned_closure_escapes() {
+        let k = FreeVar::fresh_named("k");
+        let x = FreeVar::fresh_named("x");
+        let k2 = FreeVar::fresh_named("k2");
+
+        // (k (lambda (x k2) (k2 x))): handing the lambda to the
+        // continuation is how CPS returns it
+        let returned = Rc::new(FExpr::LamTwo(Scope::new(
+            Binder(x.clone()),
+            Scope::new(Binder(k2.clone()), Rc::new(FExpr::CallOne(fvar(&k2), fvar(&x)))),
+        )));
+        let term = FExpr::CallOne(fvar(&k), returned.clone());
+
+        let verdicts = classify(&term);
+        assert_eq!(verdicts[&Rc::as_ptr(&returned)], Escape::Escapes);
+    }
+
+    #[test]
+    fn stored_parameter_escapes() {
+        let h = FreeVar::fresh_named("h");
+        let k = FreeVar::fresh_named("k");
+        let x = FreeVar::fresh_named("x");
+        let k2 = FreeVar::fresh_named("k2");
+        let halt = FreeVar::fresh_named("halt");
+
+        // like the helper test, but the consumer passes h onward as a
+        // value instead of applying it
+        let helper = Rc::new(FExpr::LamTwo(Scope::new(
+            Binder(x.clone()),
+            Scope::new(Binder(k2.clone()), Rc::new(FExpr::CallOne(fvar(&k2), fvar(&x)))),
+        )));
+        let consumer = Rc::new(FExpr::LamTwo(Scope::new(
+            Binder(h.clone()),
+            Scope::new(Binder(k.clone()), Rc::new(FExpr::CallOne(fvar(&k), fvar(&h)))),
+        )));
+        let term = FExpr::CallTwo(consumer, helper.clone(), fvar(&halt));
+
+        let verdicts = classify(&term);
+        assert_eq!(verdicts[&Rc::as_ptr(&helper)], Escape::Escapes);
+    }
+}
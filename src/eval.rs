@@ -0,0 +1,3119 @@
+use moniker::{Binder, FreeVar, Ignore, Scope, Var};
+
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::Rc;
+
+use crate::cont_expr::{t_k, BinOp, CCall, CastKind, KExpr, PrimOp, UExpr};
+use crate::expr::Expr;
+use crate::literals::Literal;
+use crate::utils::clone_rc;
+
+#[derive(Debug, Clone)]
+// Small values — integers, floats, booleans — live directly in the
+// enum payload as immediate tagged values; nothing about arithmetic
+// touches the heap. The fat closure variants are boxed so they don't
+// inflate the union: without the boxes every integer move copies the
+// inline `Closure`'s worth of padding, with them a `Value` is a few
+// words and arithmetic-heavy loops shuffle immediates.
+pub enum Value {
+    Lit(Literal),
+    Closure(Box<Closure>),
+    // a closure tagged variadic by `PrimOp::Rest`: `apply` binds its
+    // parameter to the list of every remaining argument
+    RestClosure(Box<Closure>),
+    // a closure wrapped by `Prim::Memo`: results for literal arguments
+    // are cached, keyed by the argument
+    Memoized(Rc<Memoized>),
+    // the continuation a memoized miss runs under: writes the cache
+    // entry, then forwards the value to the continuation it wraps
+    MemoCont(Box<MemoCont>),
+    // a delayed computation built by `delay`; `force` runs its body at
+    // most once
+    Thunk(Rc<Thunk>),
+    // the continuation a first force runs under: fills the thunk's
+    // cache, then forwards the value to the continuation it wraps
+    ThunkCont(Box<ThunkCont>),
+    // the continuation a `finally` body runs under: marks that a
+    // cleanup is waiting on the unwind stack, to run before the body's
+    // value moves on
+    FinallyCont(Box<FinallyCont>),
+    // the continuation a cleanup runs under on the normal path: drops
+    // the cleanup's own result and forwards the body's saved one
+    FinallyDone(Box<FinallyDone>),
+    // the continuation a cleanup runs under while an error unwinds:
+    // re-raises the error once the cleanup finishes
+    UnwindCont(Box<RuntimeError>),
+    Cont(Box<ContClosure>),
+    // an n-ary tuple of already-evaluated components
+    Tuple(Vec<Value>),
+    // a partially applied tuple collector, holding the components seen
+    // so far and awaiting the rest
+    TupleBuild(Box<TupleBuild>),
+    Prim(Prim),
+    PrimOp(PrimOp),
+    Halt,
+}
+
+impl Value {
+    // The literal inside a `Lit`, or `None` for values — closures,
+    // continuations, primitives — with no literal representation.
+    pub fn into_literal(self) -> Option<Literal> {
+        match self {
+            Value::Lit(l) => Some(l),
+            _ => None,
+        }
+    }
+}
+
+// Why a host-side extraction from a `Value` failed: the value wasn't a
+// literal of the requested kind. The debug rendering of the offending
+// value rides along so the message says what was actually there.
+#[derive(Debug, Clone)]
+pub struct WrongKind {
+    expected: &'static str,
+    got: String,
+}
+
+impl WrongKind {
+    fn new(expected: &'static str, got: &Value) -> WrongKind {
+        WrongKind {
+            expected,
+            got: format!("{:?}", got),
+        }
+    }
+}
+
+impl std::fmt::Display for WrongKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "expected {}, got {}", self.expected, self.got)
+    }
+}
+
+impl std::error::Error for WrongKind {}
+
+impl std::convert::TryFrom<Value> for u64 {
+    type Error = WrongKind;
+
+    fn try_from(v: Value) -> Result<u64, WrongKind> {
+        match v {
+            Value::Lit(Literal::Int(i)) => Ok(i),
+            v => Err(WrongKind::new("an integer", &v)),
+        }
+    }
+}
+
+impl std::convert::TryFrom<Value> for f64 {
+    type Error = WrongKind;
+
+    fn try_from(v: Value) -> Result<f64, WrongKind> {
+        match v {
+            Value::Lit(Literal::Float(f)) => Ok(f),
+            v => Err(WrongKind::new("a float", &v)),
+        }
+    }
+}
+
+impl std::convert::TryFrom<Value> for bool {
+    type Error = WrongKind;
+
+    fn try_from(v: Value) -> Result<bool, WrongKind> {
+        match v {
+            Value::Lit(Literal::Bool(b)) => Ok(b),
+            v => Err(WrongKind::new("a boolean", &v)),
+        }
+    }
+}
+
+impl std::convert::TryFrom<Value> for String {
+    type Error = WrongKind;
+
+    fn try_from(v: Value) -> Result<String, WrongKind> {
+        match v {
+            Value::Lit(Literal::String(s)) => Ok(s),
+            v => Err(WrongKind::new("a string", &v)),
+        }
+    }
+}
+
+impl std::convert::TryFrom<Value> for char {
+    type Error = WrongKind;
+
+    fn try_from(v: Value) -> Result<char, WrongKind> {
+        match v {
+            Value::Lit(Literal::Char(c)) => Ok(c),
+            v => Err(WrongKind::new("a char", &v)),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct TupleBuild {
+    remaining: usize,
+    elems: Vec<Value>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Closure {
+    pub param: FreeVar<String>,
+    pub cont: FreeVar<String>,
+    pub body: Rc<CCall>,
+    pub env: Env,
+    // for closures built by `fix`: the variable that names the closure
+    // itself, re-bound on every application so recursion needs no cyclic
+    // environment
+    pub fix: Option<FreeVar<String>>,
+}
+
+// A memoized closure and its cache. The cache is the closure's identity:
+// every clone of the wrapper shares it, and nothing else does, so hits
+// can never cross between distinct instantiations.
+#[derive(Debug)]
+pub struct Memoized {
+    pub closure: Closure,
+    cache: Rc<RefCell<HashMap<Literal, Value>>>,
+}
+
+#[derive(Debug, Clone)]
+pub struct MemoCont {
+    cache: Rc<RefCell<HashMap<Literal, Value>>>,
+    key: Literal,
+    next: Value,
+}
+
+// A delayed computation and its at-most-once cache. Like `Memoized`,
+// the cache is the thunk's identity: every clone shares it, so the body
+// runs on the first force and never again.
+#[derive(Debug)]
+pub struct Thunk {
+    pub closure: Closure,
+    cache: Rc<RefCell<Option<Value>>>,
+}
+
+#[derive(Debug, Clone)]
+pub struct ThunkCont {
+    cache: Rc<RefCell<Option<Value>>>,
+    next: Value,
+}
+
+#[derive(Debug, Clone)]
+pub struct FinallyCont {
+    next: Value,
+}
+
+#[derive(Debug, Clone)]
+pub struct FinallyDone {
+    result: Value,
+    next: Value,
+}
+
+#[derive(Debug, Clone)]
+pub struct ContClosure {
+    pub param: FreeVar<String>,
+    pub body: Rc<CCall>,
+    pub env: Env,
+}
+
+// Host-provided primitives, applied like closures but implemented in Rust.
+#[derive(Debug, Clone)]
+pub enum Prim {
+    // runs a `Literal::Quoted` expression, passing its result on to the
+    // continuation of the call site
+    Eval,
+    // suspends evaluation, handing the argument and the captured
+    // continuation back to the host as a `Step::Yielded`
+    Yield,
+    // wraps a closure in a `Value::Memoized`; only worthwhile for pure
+    // functions — a cache hit skips the body, effects and all
+    Memo,
+}
+
+impl Prim {
+    // the name a capability error reports
+    pub fn name(&self) -> &'static str {
+        match self {
+            Prim::Eval => "eval",
+            Prim::Yield => "yield",
+            Prim::Memo => "memo",
+        }
+    }
+}
+
+// Which host primitives a program may call. The built-in `PrimOp`s
+// (arithmetic, tuples, casts) are part of the language and always
+// available; capabilities gate the `Prim` values a host installs, which
+// is where effects enter, so an untrusted script can be handed the full
+// environment but only the grants it deserves. Entry points without a
+// capability parameter behave as `Capabilities::all`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Capabilities {
+    grants: u8,
+}
+
+impl Capabilities {
+    pub fn all() -> Capabilities {
+        Capabilities { grants: !0 }
+    }
+
+    pub fn none() -> Capabilities {
+        Capabilities { grants: 0 }
+    }
+
+    // adds a grant for `prim`; grants chain, starting from `none`
+    pub fn with(self, prim: &Prim) -> Capabilities {
+        Capabilities {
+            grants: self.grants | Capabilities::bit(prim),
+        }
+    }
+
+    pub fn allows(&self, prim: &Prim) -> bool {
+        self.grants & Capabilities::bit(prim) != 0
+    }
+
+    fn bit(prim: &Prim) -> u8 {
+        match prim {
+            Prim::Eval => 1,
+            Prim::Yield => 1 << 1,
+            Prim::Memo => 1 << 2,
+        }
+    }
+}
+
+// How the arithmetic primitives treat operands of mixed numeric kind.
+// `Strict` keeps the default behaviour — `(+ 1 2.0)` is an unsupported
+// operand error — while `PromoteNumeric` promotes the integer side to a
+// float and proceeds in floats.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum CoercionPolicy {
+    #[default]
+    Strict,
+    PromoteNumeric,
+}
+
+// The host-visible result of driving an evaluation: either it ran to
+// completion, or a `yield` suspended it and the host holds the resumption.
+#[derive(Debug)]
+pub enum Step {
+    Done(Value),
+    Yielded(Value, Resume),
+}
+
+// The captured continuation of a suspended `yield` call site; the
+// coercion policy of the run it came from rides along so resumption
+// keeps behaving the same way.
+#[derive(Debug)]
+pub struct Resume {
+    cont: Value,
+    policy: CoercionPolicy,
+    caps: Capabilities,
+    // the cleanups that were pending at the suspension, restored so a
+    // resumed run still unwinds through them
+    finals: Vec<Closure>,
+    // the backtrack alternatives pending at the suspension, restored so
+    // a resumed run can still `fail` into them
+    choices: Vec<(Closure, Value)>,
+}
+
+impl Resume {
+    pub fn resume(self, val: Value) -> Result<Step, RuntimeError> {
+        let Resume {
+            cont,
+            policy,
+            caps,
+            mut finals,
+            choices,
+        } = self;
+
+        match cont {
+            Value::Halt => Ok(Step::Done(val)),
+            Value::Cont(c) => run_ccall_traced_policy(
+                clone_rc(c.body),
+                c.env.insert(c.param, val),
+                &mut NoTrace,
+                policy,
+                caps,
+                finals,
+                choices,
+            ),
+            Value::ThunkCont(t) => {
+                let ThunkCont { cache, next } = *t;
+                *cache.borrow_mut() = Some(val.clone());
+                Resume {
+                    cont: next,
+                    policy,
+                    caps,
+                    finals,
+                    choices,
+                }
+                .resume(val)
+            }
+            Value::MemoCont(m) => {
+                let MemoCont { cache, key, next } = *m;
+                cache.borrow_mut().insert(key, val.clone());
+                Resume {
+                    cont: next,
+                    policy,
+                    caps,
+                    finals,
+                    choices,
+                }
+                .resume(val)
+            }
+            Value::FinallyCont(f) => {
+                let cleanup = finals.pop().ok_or_else(finals_out_of_sync)?;
+                let done = Value::FinallyDone(Box::new(FinallyDone {
+                    result: val,
+                    next: f.next,
+                }));
+                let (call, env) = enter_thunk(cleanup, done, &mut NoTrace);
+                run_ccall_traced_policy(call, env, &mut NoTrace, policy, caps, finals, choices)
+            }
+            Value::FinallyDone(d) => {
+                let FinallyDone { result, next } = *d;
+                Resume {
+                    cont: next,
+                    policy,
+                    caps,
+                    finals,
+                    choices,
+                }
+                .resume(result)
+            }
+            Value::UnwindCont(e) => match finals.pop() {
+                None => Err(*e),
+                Some(cleanup) => {
+                    let (call, env) =
+                        enter_thunk(cleanup, Value::UnwindCont(e), &mut NoTrace);
+                    run_ccall_traced_policy(call, env, &mut NoTrace, policy, caps, finals, choices)
+                }
+            },
+            kv => Err(ErrorKind::NotAContinuation(Box::new(kv)).into()),
+        }
+    }
+}
+
+// A failure during evaluation: what went wrong, plus the pretty-printed
+// continuation frames that were active when it did, innermost first.
+#[derive(Debug, Clone)]
+pub struct RuntimeError {
+    pub kind: ErrorKind,
+    pub trace: Vec<String>,
+}
+
+#[derive(Debug, Clone)]
+pub enum ErrorKind {
+    UnboundVar(Var<String>),
+    NotAFunction(Box<Value>),
+    NotAContinuation(Box<Value>),
+    AssertionFailed(String),
+    IndexOutOfBounds(usize),
+    DivideByZero,
+    PrimError(String),
+    // the program called a host primitive it was not granted the
+    // capability for
+    CapabilityDenied(String),
+}
+
+impl RuntimeError {
+    fn with_frame(mut self, frame: String) -> RuntimeError {
+        self.trace.push(frame);
+        self
+    }
+}
+
+impl From<ErrorKind> for RuntimeError {
+    fn from(kind: ErrorKind) -> RuntimeError {
+        RuntimeError {
+            kind,
+            trace: Vec::new(),
+        }
+    }
+}
+
+impl std::fmt::Display for ErrorKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            ErrorKind::UnboundVar(v) => write!(f, "unbound variable {}", v),
+            ErrorKind::NotAFunction(v) => write!(f, "called a non-function: {:?}", v),
+            ErrorKind::NotAContinuation(v) => write!(f, "continued a non-continuation: {:?}", v),
+            ErrorKind::AssertionFailed(msg) => write!(f, "assertion failed: {}", msg),
+            ErrorKind::IndexOutOfBounds(i) => write!(f, "index {} out of bounds", i),
+            ErrorKind::DivideByZero => write!(f, "divide by zero"),
+            ErrorKind::PrimError(msg) => write!(f, "{}", msg),
+            ErrorKind::CapabilityDenied(name) => {
+                write!(f, "capability denied for primitive {}", name)
+            }
+        }
+    }
+}
+
+impl std::fmt::Display for RuntimeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}", self.kind)?;
+        if !self.trace.is_empty() {
+            write!(f, " ({} frames active)", self.trace.len())?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for RuntimeError {}
+
+// Renders one frame of a trace; errors never carry colour codes.
+fn trace_frame(call: &CCall) -> String {
+    let mut buf = termcolor::Buffer::no_color();
+    call.pretty_print(&mut buf)
+        .expect("rendering to a buffer can't fail");
+    String::from_utf8(buf.into_inner()).expect("rendered output is utf-8")
+}
+
+// The map lives behind an `Rc` so that cloning an `Env` — which happens
+// every time a closure or continuation value is copied around — is a
+// pointer bump rather than a deep copy of every captured environment in
+// the chain (which made recursive programs exponential).
+#[derive(Debug, Clone, Default)]
+pub struct Env {
+    map: Rc<HashMap<FreeVar<String>, Value>>,
+    // when set, discarded tables are recycled through this pool instead
+    // of going back to the allocator
+    pool: Option<EnvPool>,
+}
+
+impl Env {
+    pub fn new() -> Env {
+        Env::default()
+    }
+
+    // An empty environment whose tables are drawn from (and returned
+    // to) `pool`; every environment derived from it via `insert` shares
+    // the pool.
+    pub fn pooled(pool: &EnvPool) -> Env {
+        Env {
+            map: Rc::new(HashMap::new()),
+            pool: Some(pool.clone()),
+        }
+    }
+
+    pub fn insert(&self, var: FreeVar<String>, val: Value) -> Env {
+        let mut map = match &self.pool {
+            // `clone_from` reuses the recycled table's buckets, so a
+            // warmed-up pool extends environments without allocating
+            Some(pool) => {
+                let mut map = pool.0.borrow_mut().pop().unwrap_or_default();
+                map.clone_from(&self.map);
+                map
+            }
+            None => (*self.map).clone(),
+        };
+        map.insert(var, val);
+        Env {
+            map: Rc::new(map),
+            pool: self.pool.clone(),
+        }
+    }
+
+    pub fn get(&self, var: &FreeVar<String>) -> Option<&Value> {
+        self.map.get(var)
+    }
+}
+
+// Uniquely-owned tables go back to the pool when their `Env` dies, so a
+// pooled evaluation stops paying for table allocation once the pool has
+// warmed up. Entries are cleared first — before the pool is borrowed,
+// since dropping them can drop further pooled environments — so the
+// pool never keeps program values alive between uses.
+impl Drop for Env {
+    fn drop(&mut self) {
+        if let Some(pool) = &self.pool {
+            if let Some(map) = Rc::get_mut(&mut self.map) {
+                let mut map = std::mem::take(map);
+                map.clear();
+                pool.0.borrow_mut().push(map);
+            }
+        }
+    }
+}
+
+// A free list of environment tables shared by every `Env` created
+// through `Env::pooled`. Purely an allocation strategy: results are
+// identical to the default evaluator's, only the allocator traffic in
+// tight loops changes. `reset` between top-level evaluations hands the
+// retained capacity back.
+type EnvTable = HashMap<FreeVar<String>, Value>;
+
+#[derive(Debug, Clone, Default)]
+pub struct EnvPool(Rc<RefCell<Vec<EnvTable>>>);
+
+impl EnvPool {
+    pub fn new() -> EnvPool {
+        EnvPool::default()
+    }
+
+    // Drops every recycled table, returning their memory to the
+    // allocator.
+    pub fn reset(&self) {
+        self.0.borrow_mut().clear();
+    }
+}
+
+// Observes the evaluator as it runs: `step` fires once per executed
+// `CCall`, `bind` once per variable the step writes into the environment.
+// The methods default to no-ops and the evaluator is generic over the
+// tracer, so the untraced paths monomorphize to exactly the old code.
+pub trait Tracer {
+    fn step(&mut self, _call: &CCall, _env: &Env) {}
+    fn bind(&mut self, _var: &FreeVar<String>, _val: &Value) {}
+    // a continuation closure was built for the current step
+    fn cont_created(&mut self) {}
+    // a continuation closure was entered, ending its life
+    fn cont_consumed(&mut self) {}
+}
+
+pub struct NoTrace;
+
+impl Tracer for NoTrace {}
+
+// A sink that keeps everything: executed calls rendered monochrome, and
+// the bindings each step introduced.
+#[derive(Default)]
+pub struct RecordingTracer {
+    pub steps: Vec<String>,
+    pub binds: Vec<(FreeVar<String>, Value)>,
+}
+
+impl Tracer for RecordingTracer {
+    fn step(&mut self, call: &CCall, _env: &Env) {
+        self.steps.push(trace_frame(call));
+    }
+
+    fn bind(&mut self, var: &FreeVar<String>, val: &Value) {
+        self.binds.push((var.clone(), val.clone()));
+    }
+}
+
+// Measures continuation pressure: how many continuation closures are
+// live at once, maximised over the run. Source-level tail calls pass
+// the enclosing continuation through unchanged, so tail loops hold this
+// constant; non-tail recursion stacks one continuation per level.
+#[derive(Debug, Default)]
+pub struct ContProfiler {
+    live: usize,
+    pub max_live: usize,
+}
+
+impl Tracer for ContProfiler {
+    fn cont_created(&mut self) {
+        self.live += 1;
+        self.max_live = self.max_live.max(self.live);
+    }
+
+    fn cont_consumed(&mut self) {
+        self.live = self.live.saturating_sub(1);
+    }
+}
+
+// Lowers `expr` against a halt continuation and runs it to completion,
+// with `bindings` seeding the global environment (primitives etc).
+pub fn run_with_env(
+    expr: Expr,
+    bindings: impl IntoIterator<Item = (FreeVar<String>, Value)>,
+) -> Result<Value, RuntimeError> {
+    let halt = FreeVar::fresh_named("halt");
+    let call = t_k(expr, Rc::new(KExpr::Var(Var::Free(halt.clone()))));
+
+    let mut env = Env::new().insert(halt, Value::Halt);
+    for (var, val) in bindings {
+        env = env.insert(var, val);
+    }
+
+    run_ccall(call, env)
+}
+
+pub fn run(expr: Expr) -> Result<Value, RuntimeError> {
+    run_with_env(expr, None)
+}
+
+// As `run_with_env`, with `policy` controlling how the arithmetic
+// primitives treat mixed numeric operands.
+pub fn run_with_policy(
+    expr: Expr,
+    bindings: impl IntoIterator<Item = (FreeVar<String>, Value)>,
+    policy: CoercionPolicy,
+) -> Result<Value, RuntimeError> {
+    let halt = FreeVar::fresh_named("halt");
+    let call = t_k(expr, Rc::new(KExpr::Var(Var::Free(halt.clone()))));
+
+    let mut env = Env::new().insert(halt, Value::Halt);
+    for (var, val) in bindings {
+        env = env.insert(var, val);
+    }
+
+    match run_ccall_stepped(call, env, policy, Capabilities::all())? {
+        Step::Done(v) => Ok(v),
+        Step::Yielded(v, _) => Err(ErrorKind::PrimError(format!(
+            "yielded outside of a generator: {:?}",
+            v
+        ))
+        .into()),
+    }
+}
+
+// As `run_with_env`, with environment tables drawn from `pool`. The
+// pool survives the call, so repeated evaluations reuse each other's
+// capacity; callers decide when `reset` gives it back.
+pub fn run_with_pool(
+    expr: Expr,
+    bindings: impl IntoIterator<Item = (FreeVar<String>, Value)>,
+    pool: &EnvPool,
+) -> Result<Value, RuntimeError> {
+    let halt = FreeVar::fresh_named("halt");
+    let call = t_k(expr, Rc::new(KExpr::Var(Var::Free(halt.clone()))));
+
+    let mut env = Env::pooled(pool).insert(halt, Value::Halt);
+    for (var, val) in bindings {
+        env = env.insert(var, val);
+    }
+
+    run_ccall(call, env)
+}
+
+// A REPL-style evaluation session. Each expression is lowered exactly
+// once, against a fresh top-level continuation, and runs with every
+// earlier binding in scope; results bound through `eval_bind` persist
+// in the session environment, so prior expressions are never re-lowered
+// or re-run.
+#[derive(Default)]
+pub struct Session {
+    env: Env,
+}
+
+impl Session {
+    pub fn new() -> Session {
+        Session::default()
+    }
+
+    // Evaluates `expr` with all earlier bindings in scope.
+    pub fn eval(&mut self, expr: Expr) -> Result<Value, RuntimeError> {
+        let halt = FreeVar::fresh_named("halt");
+        let call = t_k(expr, Rc::new(KExpr::Var(Var::Free(halt.clone()))));
+
+        run_ccall(call, self.env.insert(halt, Value::Halt))
+    }
+
+    // As `eval`, then binds the result to `name` for later expressions.
+    pub fn eval_bind(
+        &mut self,
+        name: FreeVar<String>,
+        expr: Expr,
+    ) -> Result<Value, RuntimeError> {
+        let value = self.eval(expr)?;
+        self.env = self.env.insert(name, value.clone());
+        Ok(value)
+    }
+}
+
+// As `run_with_env`, reporting each reduction step to `tracer`.
+pub fn run_traced(
+    expr: Expr,
+    bindings: impl IntoIterator<Item = (FreeVar<String>, Value)>,
+    tracer: &mut impl Tracer,
+) -> Result<Value, RuntimeError> {
+    let halt = FreeVar::fresh_named("halt");
+    let call = t_k(expr, Rc::new(KExpr::Var(Var::Free(halt.clone()))));
+
+    let mut env = Env::new().insert(halt, Value::Halt);
+    for (var, val) in bindings {
+        env = env.insert(var, val);
+    }
+
+    match run_ccall_traced(call, env, tracer)? {
+        Step::Done(v) => Ok(v),
+        Step::Yielded(v, _) => Err(ErrorKind::PrimError(format!(
+            "yielded outside of a generator: {:?}",
+            v
+        ))
+        .into()),
+    }
+}
+
+// As `run_with_env`, but the value reaching the halt continuation is
+// handed to a host callback rather than returned, so embedders decide
+// what happens to it (print it, store it, stream it somewhere).
+pub fn run_with_halt(
+    expr: Expr,
+    bindings: impl IntoIterator<Item = (FreeVar<String>, Value)>,
+    on_halt: impl FnOnce(Value),
+) -> Result<(), RuntimeError> {
+    let val = run_with_env(expr, bindings)?;
+    on_halt(val);
+    Ok(())
+}
+
+// As `run_with_env`, but the program may suspend itself with `Prim::Yield`.
+pub fn run_generator(
+    expr: Expr,
+    bindings: impl IntoIterator<Item = (FreeVar<String>, Value)>,
+) -> Result<Step, RuntimeError> {
+    let halt = FreeVar::fresh_named("halt");
+    let call = t_k(expr, Rc::new(KExpr::Var(Var::Free(halt.clone()))));
+
+    let mut env = Env::new().insert(halt, Value::Halt);
+    for (var, val) in bindings {
+        env = env.insert(var, val);
+    }
+
+    run_ccall_stepped(call, env, CoercionPolicy::Strict, Capabilities::all())
+}
+
+// As `run_generator`, with `caps` controlling which host primitives the
+// program may call; anything else trips a `CapabilityDenied` error.
+// Combine with `run_budgeted`-style fuel by driving the result's
+// `Resume` under the same grants — a suspension carries them along.
+pub fn run_sandboxed(
+    expr: Expr,
+    bindings: impl IntoIterator<Item = (FreeVar<String>, Value)>,
+    caps: Capabilities,
+) -> Result<Step, RuntimeError> {
+    let halt = FreeVar::fresh_named("halt");
+    let call = t_k(expr, Rc::new(KExpr::Var(Var::Free(halt.clone()))));
+
+    let mut env = Env::new().insert(halt, Value::Halt);
+    for (var, val) in bindings {
+        env = env.insert(var, val);
+    }
+
+    run_ccall_stepped(call, env, CoercionPolicy::Strict, caps)
+}
+
+// The outcome of running under a step budget: either the program
+// finished, or the budget ran out first and `Paused` holds everything
+// needed to pick up where it stopped.
+#[derive(Debug)]
+pub enum Budgeted {
+    Done(Value),
+    Paused(Paused),
+}
+
+// The trampoline's intermediate state — the next call and the
+// environment to run it in — packaged so a host can resume it later
+// with a fresh budget.
+#[derive(Debug)]
+pub struct Paused {
+    call: CCall,
+    env: Env,
+    finals: Vec<Closure>,
+    choices: Vec<(Closure, Value)>,
+}
+
+impl Paused {
+    pub fn resume(self, budget: usize) -> Result<Budgeted, RuntimeError> {
+        run_ccall_budgeted(self.call, self.env, budget, self.finals, self.choices)
+    }
+}
+
+// As `run_with_env`, but runs at most `budget` trampoline steps before
+// handing control back, so interactive hosts can interleave long
+// computations with other work.
+pub fn run_budgeted(
+    expr: Expr,
+    bindings: impl IntoIterator<Item = (FreeVar<String>, Value)>,
+    budget: usize,
+) -> Result<Budgeted, RuntimeError> {
+    let halt = FreeVar::fresh_named("halt");
+    let call = t_k(expr, Rc::new(KExpr::Var(Var::Free(halt.clone()))));
+
+    let mut env = Env::new().insert(halt, Value::Halt);
+    for (var, val) in bindings {
+        env = env.insert(var, val);
+    }
+
+    run_ccall_budgeted(call, env, budget, Vec::new(), Vec::new())
+}
+
+// A practical (not proof-grade) equivalence check for refactoring:
+// applies both programs to every argument tuple in `inputs` and reports
+// whether their observable results agree. A run observes a literal
+// result; errors and non-literal results (closures can't be compared)
+// both observe as nothing, so two programs that fail on the same input
+// still agree there.
+pub fn observationally_agree(a: &Expr, b: &Expr, inputs: &[Vec<Literal>]) -> bool {
+    let observe = |f: &Expr, args: &[Literal]| -> Option<Literal> {
+        let applied = args.iter().fold(f.clone(), |acc, l| {
+            Expr::App(Rc::new(acc), Rc::new(Expr::Lit(Ignore(l.clone()))))
+        });
+
+        match run(applied) {
+            Ok(Value::Lit(l)) => Some(l),
+            _ => None,
+        }
+    };
+
+    inputs
+        .iter()
+        .all(|args| observe(a, args) == observe(b, args))
+}
+
+// The evaluator as a pull-based iterator, for step-debugger UIs: each
+// item is the `CCall` the machine is about to execute, so every state
+// can be pretty-printed and shown before the next step runs. A failing
+// step yields its error as the final item; after the iterator is
+// exhausted, `value()` holds the program's result.
+#[derive(Debug)]
+pub struct Steps {
+    state: Option<(CCall, Env)>,
+    value: Option<Value>,
+    finals: Vec<Closure>,
+    choices: Vec<(Closure, Value)>,
+}
+
+// Lowers `expr` the way `run_with_env` does and packages it for
+// single-stepping.
+pub fn steps(
+    expr: Expr,
+    bindings: impl IntoIterator<Item = (FreeVar<String>, Value)>,
+) -> Steps {
+    let halt = FreeVar::fresh_named("halt");
+    let call = t_k(expr, Rc::new(KExpr::Var(Var::Free(halt.clone()))));
+
+    let mut env = Env::new().insert(halt, Value::Halt);
+    for (var, val) in bindings {
+        env = env.insert(var, val);
+    }
+
+    Steps {
+        state: Some((call, env)),
+        value: None,
+        finals: Vec::new(),
+        choices: Vec::new(),
+    }
+}
+
+impl Steps {
+    // The final value, once the iterator has run dry without erroring.
+    pub fn value(&self) -> Option<&Value> {
+        self.value.as_ref()
+    }
+}
+
+impl Iterator for Steps {
+    type Item = Result<CCall, RuntimeError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let (call, env) = self.state.take()?;
+        // cloning the state is cheap — a `CCall`'s children sit behind Rcs
+        let here = call.clone();
+
+        match transition(
+            call,
+            env,
+            &mut NoTrace,
+            CoercionPolicy::Strict,
+            Capabilities::all(),
+            &mut self.finals,
+            &mut self.choices,
+        ) {
+            Ok(Transition::Continue(next_call, next_env)) => {
+                self.state = Some((next_call, next_env));
+                Some(Ok(here))
+            }
+            Ok(Transition::Finished(step)) => match *step {
+                Step::Done(v) => {
+                    self.value = Some(v);
+                    Some(Ok(here))
+                }
+                Step::Yielded(v, _) => Some(Err(ErrorKind::PrimError(format!(
+                    "yielded outside of a generator: {:?}",
+                    v
+                ))
+                .into())),
+            },
+            // the failing step did execute; a pending cleanup means the
+            // error is still unwinding, so stepping continues through it
+            Err(e) => match self.finals.pop() {
+                None => Some(Err(e)),
+                Some(cleanup) => {
+                    self.state = Some(enter_thunk(
+                        cleanup,
+                        Value::UnwindCont(Box::new(e)),
+                        &mut NoTrace,
+                    ));
+                    Some(Ok(here))
+                }
+            },
+        }
+    }
+}
+
+fn run_ccall_budgeted(
+    call: CCall,
+    env: Env,
+    budget: usize,
+    mut finals: Vec<Closure>,
+    mut choices: Vec<(Closure, Value)>,
+) -> Result<Budgeted, RuntimeError> {
+    let mut call = call;
+    let mut env = env;
+
+    for _ in 0..budget {
+        match transition(
+            call,
+            env,
+            &mut NoTrace,
+            CoercionPolicy::Strict,
+            Capabilities::all(),
+            &mut finals,
+            &mut choices,
+        ) {
+            Ok(Transition::Continue(next_call, next_env)) => {
+                call = next_call;
+                env = next_env;
+            }
+            Ok(Transition::Finished(step)) => match *step {
+                Step::Done(v) => return Ok(Budgeted::Done(v)),
+                Step::Yielded(v, _) => {
+                    return Err(ErrorKind::PrimError(format!(
+                        "yielded outside of a generator: {:?}",
+                        v
+                    ))
+                    .into())
+                }
+            },
+            Err(e) => match finals.pop() {
+                None => return Err(e),
+                Some(cleanup) => {
+                    let (next_call, next_env) =
+                        enter_thunk(cleanup, Value::UnwindCont(Box::new(e)), &mut NoTrace);
+                    call = next_call;
+                    env = next_env;
+                }
+            },
+        }
+    }
+
+    Ok(Budgeted::Paused(Paused {
+        call,
+        env,
+        finals,
+        choices,
+    }))
+}
+
+pub fn run_ccall(call: CCall, env: Env) -> Result<Value, RuntimeError> {
+    match run_ccall_stepped(call, env, CoercionPolicy::Strict, Capabilities::all())? {
+        Step::Done(v) => Ok(v),
+        Step::Yielded(v, _) => Err(ErrorKind::PrimError(format!(
+            "yielded outside of a generator: {:?}",
+            v
+        ))
+        .into()),
+    }
+}
+
+fn run_ccall_stepped(
+    call: CCall,
+    env: Env,
+    policy: CoercionPolicy,
+    caps: Capabilities,
+) -> Result<Step, RuntimeError> {
+    run_ccall_traced_policy(call, env, &mut NoTrace, policy, caps, Vec::new(), Vec::new())
+}
+
+fn run_ccall_traced(call: CCall, env: Env, tracer: &mut impl Tracer) -> Result<Step, RuntimeError> {
+    run_ccall_traced_policy(
+        call,
+        env,
+        tracer,
+        CoercionPolicy::Strict,
+        Capabilities::all(),
+        Vec::new(),
+        Vec::new(),
+    )
+}
+
+fn run_ccall_traced_policy(
+    call: CCall,
+    env: Env,
+    tracer: &mut impl Tracer,
+    policy: CoercionPolicy,
+    caps: Capabilities,
+    mut finals: Vec<Closure>,
+    mut choices: Vec<(Closure, Value)>,
+) -> Result<Step, RuntimeError> {
+    #[cfg(feature = "tracing")]
+    let _span = tracing::debug_span!("eval").entered();
+
+    let mut call = call;
+    let mut env = env;
+
+    loop {
+        match transition(call, env, tracer, policy, caps, &mut finals, &mut choices) {
+            Ok(Transition::Continue(next_call, next_env)) => {
+                call = next_call;
+                env = next_env;
+            }
+            Ok(Transition::Finished(step)) => return Ok(*step),
+            // an error unwinds through every pending cleanup before it
+            // escapes; the `UnwindCont` re-raises it after each one, so
+            // nested cleanups run innermost-first
+            Err(e) => match finals.pop() {
+                None => return Err(e),
+                Some(cleanup) => {
+                    let (next_call, next_env) =
+                        enter_thunk(cleanup, Value::UnwindCont(Box::new(e)), tracer);
+                    call = next_call;
+                    env = next_env;
+                }
+            },
+        }
+    }
+}
+
+// The outcome of one trampoline step: either the machine moved to a new
+// state, or it produced a final `Step` for the host.
+enum Transition {
+    Continue(CCall, Env),
+    // boxed: a `Step` carries a whole `Value`, which would otherwise
+    // dominate the size of every trampoline bounce
+    Finished(Box<Step>),
+}
+
+fn transition(
+    call: CCall,
+    env: Env,
+    tracer: &mut impl Tracer,
+    policy: CoercionPolicy,
+    caps: Capabilities,
+    finals: &mut Vec<Closure>,
+    choices: &mut Vec<(Closure, Value)>,
+) -> Result<Transition, RuntimeError> {
+    // held so a failing step can record which call it happened in
+    let here = call.clone();
+
+    tracer.step(&here, &env);
+
+    match call {
+        CCall::UCall(f, v, k) => {
+            let fv = eval_u(clone_rc(f), &env).map_err(|e| e.with_frame(trace_frame(&here)))?;
+            let vv = eval_u(clone_rc(v), &env).map_err(|e| e.with_frame(trace_frame(&here)))?;
+            // a lambda here allocates a continuation closure; a variable
+            // merely passes an existing one along
+            if matches!(&*k, KExpr::Lam(_)) {
+                tracer.cont_created();
+            }
+            let kv = eval_k(clone_rc(k), &env).map_err(|e| e.with_frame(trace_frame(&here)))?;
+
+            match fv {
+                Value::Closure(c) => {
+                    tracer.bind(&c.param, &vv);
+                    tracer.bind(&c.cont, &kv);
+                    let mut env = c.env.insert(c.param.clone(), vv).insert(c.cont.clone(), kv);
+                    if let Some(fix) = &c.fix {
+                        env = env.insert(fix.clone(), Value::Closure(c.clone()));
+                    }
+                    Ok(Transition::Continue(clone_rc(c.body), env))
+                }
+                // `apply` needs the continuation in hand to build its
+                // unrolled chain, so it can't go through the
+                // value-to-value `apply_prim_op` path
+                Value::PrimOp(PrimOp::ApplyWith(l)) => {
+                    let elems = match l {
+                        Literal::List(elems) => elems,
+                        l => {
+                            return Err(RuntimeError::from(ErrorKind::PrimError(format!(
+                                "apply applied to a non-list: {:?}",
+                                l
+                            )))
+                            .with_frame(trace_frame(&here)))
+                        }
+                    };
+
+                    let (next_call, next_env) = unroll_apply(elems, vv, kv, &env);
+                    Ok(Transition::Continue(next_call, next_env))
+                }
+                // forcing needs the continuation in hand — a hit
+                // answers from the cache, a miss runs the body under a
+                // cache-filling wrapper — so it can't go through the
+                // value-to-value `apply_prim_op` path
+                Value::PrimOp(PrimOp::Force) => match vv {
+                    Value::Thunk(t) => {
+                        if let Some(hit) = t.cache.borrow().clone() {
+                            return continue_with(kv, hit, tracer, finals);
+                        }
+
+                        let kv = Value::ThunkCont(Box::new(ThunkCont {
+                            cache: t.cache.clone(),
+                            next: kv,
+                        }));
+                        let c = &t.closure;
+                        let vv = Value::Lit(Literal::Void);
+                        tracer.bind(&c.param, &vv);
+                        tracer.bind(&c.cont, &kv);
+                        let env = c.env.insert(c.param.clone(), vv).insert(c.cont.clone(), kv);
+                        Ok(Transition::Continue(clone_rc(c.body.clone()), env))
+                    }
+                    vv => Err(RuntimeError::from(ErrorKind::PrimError(format!(
+                        "force applied to a non-thunk: {:?}",
+                        vv
+                    )))
+                    .with_frame(trace_frame(&here))),
+                },
+                // `try/finally` needs the continuation in hand: the
+                // cleanup must run whether the body's value arrives
+                // normally or an error unwinds past it, so the body
+                // runs under a marker continuation while the cleanup
+                // waits on the unwind stack
+                Value::PrimOp(PrimOp::Finally) => {
+                    let (body, cleanup) = match vv {
+                        Value::Tuple(parts) if parts.len() == 2 => {
+                            let mut parts = parts.into_iter();
+                            (parts.next().unwrap(), parts.next().unwrap())
+                        }
+                        vv => {
+                            return Err(RuntimeError::from(ErrorKind::PrimError(format!(
+                                "finally applied to a non-pair: {:?}",
+                                vv
+                            )))
+                            .with_frame(trace_frame(&here)))
+                        }
+                    };
+                    let body = match body {
+                        Value::Closure(c) => c,
+                        body => {
+                            return Err(RuntimeError::from(ErrorKind::PrimError(format!(
+                                "finally body is not a thunk: {:?}",
+                                body
+                            )))
+                            .with_frame(trace_frame(&here)))
+                        }
+                    };
+                    let cleanup = match cleanup {
+                        Value::Closure(c) => *c,
+                        cleanup => {
+                            return Err(RuntimeError::from(ErrorKind::PrimError(format!(
+                                "finally cleanup is not a thunk: {:?}",
+                                cleanup
+                            )))
+                            .with_frame(trace_frame(&here)))
+                        }
+                    };
+
+                    finals.push(cleanup);
+                    let kv = Value::FinallyCont(Box::new(FinallyCont { next: kv }));
+                    let vv = Value::Lit(Literal::Void);
+                    tracer.bind(&body.param, &vv);
+                    tracer.bind(&body.cont, &kv);
+                    let env = body
+                        .env
+                        .insert(body.param.clone(), vv)
+                        .insert(body.cont.clone(), kv);
+                    Ok(Transition::Continue(clone_rc(body.body), env))
+                }
+                // `amb` needs the continuation in hand: the second
+                // alternative is stashed with it on the backtrack stack,
+                // so a later `fail` can abandon its own continuation and
+                // re-enter this one
+                Value::PrimOp(PrimOp::Amb) => {
+                    let (first, second) = match vv {
+                        Value::Tuple(parts) if parts.len() == 2 => {
+                            let mut parts = parts.into_iter();
+                            (parts.next().unwrap(), parts.next().unwrap())
+                        }
+                        vv => {
+                            return Err(RuntimeError::from(ErrorKind::PrimError(format!(
+                                "amb applied to a non-pair: {:?}",
+                                vv
+                            )))
+                            .with_frame(trace_frame(&here)))
+                        }
+                    };
+                    let first = match first {
+                        Value::Closure(c) => *c,
+                        first => {
+                            return Err(RuntimeError::from(ErrorKind::PrimError(format!(
+                                "an amb alternative is not a thunk: {:?}",
+                                first
+                            )))
+                            .with_frame(trace_frame(&here)))
+                        }
+                    };
+                    let second = match second {
+                        Value::Closure(c) => *c,
+                        second => {
+                            return Err(RuntimeError::from(ErrorKind::PrimError(format!(
+                                "an amb alternative is not a thunk: {:?}",
+                                second
+                            )))
+                            .with_frame(trace_frame(&here)))
+                        }
+                    };
+
+                    choices.push((second, kv.clone()));
+                    let (call, env) = enter_thunk(first, kv, tracer);
+                    Ok(Transition::Continue(call, env))
+                }
+                // `fail` discards both its argument and its own
+                // continuation: the machine re-enters the most recent
+                // pending alternative with the continuation captured at
+                // its choice point
+                Value::PrimOp(PrimOp::Fail) => match choices.pop() {
+                    Some((alternative, kv)) => {
+                        let (call, env) = enter_thunk(alternative, kv, tracer);
+                        Ok(Transition::Continue(call, env))
+                    }
+                    None => Err(RuntimeError::from(ErrorKind::PrimError(
+                        "fail with no pending amb alternative".to_owned(),
+                    ))
+                    .with_frame(trace_frame(&here))),
+                },
+                Value::PrimOp(op) => {
+                    let vv = apply_prim_op(op, vv, policy)
+                        .map_err(|e| e.with_frame(trace_frame(&here)))?;
+
+                    continue_with(kv, vv, tracer, finals)
+                }
+                // a memoized closure: literal arguments hit the cache,
+                // and the result of a miss is written on its way out
+                Value::Memoized(m) => {
+                    if let Value::Lit(l) = &vv {
+                        if let Some(hit) = m.cache.borrow().get(l).cloned() {
+                            return continue_with(kv, hit, tracer, finals);
+                        }
+                    }
+
+                    let kv = match &vv {
+                        Value::Lit(l) => Value::MemoCont(Box::new(MemoCont {
+                            cache: m.cache.clone(),
+                            key: l.clone(),
+                            next: kv,
+                        })),
+                        // non-literal arguments can't key the cache
+                        _ => kv,
+                    };
+
+                    let c = &m.closure;
+                    tracer.bind(&c.param, &vv);
+                    tracer.bind(&c.cont, &kv);
+                    let mut env = c.env.insert(c.param.clone(), vv).insert(c.cont.clone(), kv);
+                    if let Some(fix) = &c.fix {
+                        // recursion re-enters through the wrapper, so
+                        // nested calls share the cache
+                        env = env.insert(fix.clone(), Value::Memoized(m.clone()));
+                    }
+                    Ok(Transition::Continue(clone_rc(c.body.clone()), env))
+                }
+                Value::Prim(p) => {
+                    if !caps.allows(&p) {
+                        return Err(RuntimeError::from(ErrorKind::CapabilityDenied(
+                            p.name().to_owned(),
+                        ))
+                        .with_frame(trace_frame(&here)));
+                    }
+                    match apply_prim(p, vv, kv, &env)
+                        .map_err(|e| e.with_frame(trace_frame(&here)))?
+                    {
+                        PrimResult::Continue(next_call, next_env) => {
+                            Ok(Transition::Continue(next_call, next_env))
+                        }
+                        PrimResult::Suspend(val, cont) => Ok(Transition::Finished(Box::new(
+                            Step::Yielded(
+                                *val,
+                                Resume {
+                                    cont: *cont,
+                                    policy,
+                                    caps,
+                                    finals: std::mem::take(finals),
+                                    choices: std::mem::take(choices),
+                                },
+                            ),
+                        ))),
+                    }
+                }
+                // outside `apply` there is no argument list to hand over
+                Value::TupleBuild(mut b) => {
+                    b.elems.push(vv);
+                    let result = if b.remaining == 1 {
+                        Value::Tuple(b.elems)
+                    } else {
+                        b.remaining -= 1;
+                        Value::TupleBuild(b)
+                    };
+
+                    continue_with(kv, result, tracer, finals)
+                }
+                fv @ Value::RestClosure(_) => Err(RuntimeError::from(ErrorKind::PrimError(
+                    format!("a variadic lambda must be called through apply: {:?}", fv),
+                ))
+                .with_frame(trace_frame(&here))),
+                fv => Err(ErrorKind::NotAFunction(Box::new(fv)).into()),
+            }
+        }
+        CCall::If(c, t, e) => {
+            let cv = eval_u(clone_rc(c), &env).map_err(|e| e.with_frame(trace_frame(&here)))?;
+
+            match cv {
+                Value::Lit(Literal::Bool(true)) => Ok(Transition::Continue(clone_rc(t), env)),
+                Value::Lit(Literal::Bool(false)) => Ok(Transition::Continue(clone_rc(e), env)),
+                cv => Err(RuntimeError::from(ErrorKind::PrimError(format!(
+                    "if applied to a non-boolean: {:?}",
+                    cv
+                )))
+                .with_frame(trace_frame(&here))),
+            }
+        }
+        CCall::KCall(k, v) => {
+            if matches!(&*k, KExpr::Lam(_)) {
+                tracer.cont_created();
+            }
+            let kv = eval_k(clone_rc(k), &env).map_err(|e| e.with_frame(trace_frame(&here)))?;
+            let vv = eval_u(clone_rc(v), &env).map_err(|e| e.with_frame(trace_frame(&here)))?;
+
+            continue_with(kv, vv, tracer, finals)
+        }
+    }
+}
+
+// Hands `vv` to the continuation value `kv` — the shared tail of every
+// reduction that produces a value. A `MemoCont` records its cache entry
+// and forwards to the continuation it wraps.
+fn continue_with(
+    kv: Value,
+    vv: Value,
+    tracer: &mut impl Tracer,
+    finals: &mut Vec<Closure>,
+) -> Result<Transition, RuntimeError> {
+    match kv {
+        Value::Halt => Ok(Transition::Finished(Box::new(Step::Done(vv)))),
+        Value::Cont(c) => {
+            tracer.bind(&c.param, &vv);
+            tracer.cont_consumed();
+            Ok(Transition::Continue(
+                clone_rc(c.body),
+                c.env.insert(c.param, vv),
+            ))
+        }
+        Value::MemoCont(m) => {
+            let MemoCont { cache, key, next } = *m;
+            cache.borrow_mut().insert(key, vv.clone());
+            continue_with(next, vv, tracer, finals)
+        }
+        Value::ThunkCont(t) => {
+            let ThunkCont { cache, next } = *t;
+            *cache.borrow_mut() = Some(vv.clone());
+            continue_with(next, vv, tracer, finals)
+        }
+        // the body delivered its value: run the cleanup, holding the
+        // value until it finishes
+        Value::FinallyCont(f) => {
+            let cleanup = finals.pop().ok_or_else(finals_out_of_sync)?;
+            let done = Value::FinallyDone(Box::new(FinallyDone {
+                result: vv,
+                next: f.next,
+            }));
+            let (call, env) = enter_thunk(cleanup, done, tracer);
+            Ok(Transition::Continue(call, env))
+        }
+        // the cleanup finished: its own result is discarded, the body's
+        // resumes
+        Value::FinallyDone(d) => {
+            let FinallyDone { result, next } = *d;
+            continue_with(next, result, tracer, finals)
+        }
+        // the cleanup finished while an error was unwinding: re-raise
+        // it, so the run loop pops the next pending cleanup (or lets it
+        // escape)
+        Value::UnwindCont(e) => Err(*e),
+        kv => Err(ErrorKind::NotAContinuation(Box::new(kv)).into()),
+    }
+}
+
+// Builds the machine state that runs a nullary thunk with `kv` as its
+// continuation. `finally` cleanups enter here with a `FinallyDone` (or
+// an `UnwindCont` while an error unwinds), `amb` alternatives with the
+// continuation captured at their choice point.
+fn enter_thunk(thunk: Closure, kv: Value, tracer: &mut impl Tracer) -> (CCall, Env) {
+    let vv = Value::Lit(Literal::Void);
+    tracer.bind(&thunk.param, &vv);
+    tracer.bind(&thunk.cont, &kv);
+    let env = thunk
+        .env
+        .insert(thunk.param.clone(), vv)
+        .insert(thunk.cont.clone(), kv);
+    (clone_rc(thunk.body), env)
+}
+
+// A `FinallyCont` with no matching entry on the unwind stack: only
+// reachable by smuggling the wrapper continuation out of its run, which
+// nothing in the language can express.
+fn finals_out_of_sync() -> RuntimeError {
+    ErrorKind::PrimError("a finally continuation outlived its unwind entry".to_owned()).into()
+}
+
+fn eval_u(expr: UExpr, env: &Env) -> Result<Value, RuntimeError> {
+    match expr {
+        UExpr::Var(v) => lookup(&v, env),
+        UExpr::Lit(l) => Ok(Value::Lit(l.0)),
+        UExpr::Prim(p) => Ok(Value::PrimOp(p.0)),
+        UExpr::Lam(s) => {
+            let (Binder(param), body) = s.unbind();
+            let (Binder(cont), body) = body.unbind();
+
+            Ok(Value::Closure(Box::new(Closure {
+                param,
+                cont,
+                body,
+                env: env.clone(),
+                fix: None,
+            })))
+        }
+        UExpr::Fix(s) => {
+            let (Binder(fix), inner) = s.unbind();
+
+            match eval_u(clone_rc(inner), env)? {
+                Value::Closure(c) => Ok(Value::Closure(Box::new(Closure {
+                    fix: Some(fix),
+                    ..*c
+                }))),
+                v => Err(ErrorKind::PrimError(format!(
+                    "fix applied to a non-lambda: {:?}",
+                    v
+                ))
+                .into()),
+            }
+        }
+    }
+}
+
+fn eval_k(expr: KExpr, env: &Env) -> Result<Value, RuntimeError> {
+    match expr {
+        KExpr::Var(v) => lookup(&v, env),
+        KExpr::Lit(l) => Ok(Value::Lit(l.0)),
+        KExpr::Lam(s) => {
+            let (Binder(param), body) = s.unbind();
+
+            Ok(Value::Cont(Box::new(ContClosure {
+                param,
+                body,
+                env: env.clone(),
+            })))
+        }
+    }
+}
+
+fn lookup(var: &Var<String>, env: &Env) -> Result<Value, RuntimeError> {
+    match var {
+        Var::Free(fv) => env
+            .get(fv)
+            .cloned()
+            .ok_or_else(|| ErrorKind::UnboundVar(var.clone()).into()),
+        v @ Var::Bound(_) => Err(ErrorKind::UnboundVar(v.clone()).into()),
+    }
+}
+
+// Lowering-introduced operations: applied to an already-evaluated
+// argument, producing the value handed to the call's continuation.
+fn apply_prim_op(op: PrimOp, arg: Value, policy: CoercionPolicy) -> Result<Value, RuntimeError> {
+    match op {
+        PrimOp::Assert(msg) => match arg {
+            Value::Lit(Literal::Bool(true)) => Ok(Value::Lit(Literal::Void)),
+            Value::Lit(Literal::Bool(false)) => Err(ErrorKind::AssertionFailed(msg).into()),
+            arg => Err(ErrorKind::PrimError(format!(
+                "assert applied to a non-boolean: {:?}",
+                arg
+            ))
+            .into()),
+        },
+        PrimOp::Rest => match arg {
+            Value::Closure(c) => Ok(Value::RestClosure(c)),
+            arg => Err(ErrorKind::PrimError(format!(
+                "rest applied to a non-closure: {:?}",
+                arg
+            ))
+            .into()),
+        },
+        PrimOp::Not => match arg {
+            Value::Lit(Literal::Bool(b)) => Ok(Value::Lit(Literal::Bool(!b))),
+            arg => Err(ErrorKind::PrimError(format!(
+                "not applied to a non-boolean: {:?}",
+                arg
+            ))
+            .into()),
+        },
+        PrimOp::Delay => match arg {
+            Value::Closure(c) => Ok(Value::Thunk(Rc::new(Thunk {
+                closure: *c,
+                cache: Rc::new(RefCell::new(None)),
+            }))),
+            arg => Err(ErrorKind::PrimError(format!(
+                "delay applied to a non-closure: {:?}",
+                arg
+            ))
+            .into()),
+        },
+        // force is dispatched with its continuation in hand, before the
+        // generic primitive path; a bare force value reaching here was
+        // passed somewhere it cannot run
+        PrimOp::Force => Err(ErrorKind::PrimError(format!(
+            "force applied outside a call: {:?}",
+            arg
+        ))
+        .into()),
+        // like force, dispatched with its continuation in hand before
+        // the generic primitive path
+        PrimOp::Finally => Err(ErrorKind::PrimError(format!(
+            "finally applied outside a call: {:?}",
+            arg
+        ))
+        .into()),
+        PrimOp::Amb => Err(ErrorKind::PrimError(format!(
+            "amb applied outside a call: {:?}",
+            arg
+        ))
+        .into()),
+        PrimOp::Fail => Err(ErrorKind::PrimError(format!(
+            "fail applied outside a call: {:?}",
+            arg
+        ))
+        .into()),
+        PrimOp::Cast(kind) => match (kind, arg) {
+            (CastKind::IntToFloat, Value::Lit(Literal::Int(i))) => {
+                Ok(Value::Lit(Literal::Float(i as f64)))
+            }
+            (CastKind::IntToString, Value::Lit(Literal::Int(i))) => {
+                Ok(Value::Lit(Literal::String(i.to_string())))
+            }
+            (CastKind::BoolToInt, Value::Lit(Literal::Bool(b))) => {
+                Ok(Value::Lit(Literal::Int(b as u64)))
+            }
+            (kind, arg) => Err(ErrorKind::PrimError(format!(
+                "cast {} applied to the wrong kind of value: {:?}",
+                kind, arg
+            ))
+            .into()),
+        },
+        PrimOp::Binary(op) => match arg {
+            Value::Lit(l) => Ok(Value::PrimOp(PrimOp::BinaryWith(op, l))),
+            arg => Err(ErrorKind::PrimError(format!(
+                "{} applied to a non-literal: {:?}",
+                op, arg
+            ))
+            .into()),
+        },
+        PrimOp::BinaryWith(op, a) => match arg {
+            Value::Lit(b) => apply_bin_op(op, a, b, policy),
+            arg => Err(ErrorKind::PrimError(format!(
+                "{} applied to a non-literal: {:?}",
+                op, arg
+            ))
+            .into()),
+        },
+        PrimOp::Apply => match arg {
+            Value::Lit(l @ Literal::List(_)) => Ok(Value::PrimOp(PrimOp::ApplyWith(l))),
+            arg => Err(ErrorKind::PrimError(format!(
+                "apply applied to a non-list: {:?}",
+                arg
+            ))
+            .into()),
+        },
+        // intercepted in the main loop, which has the continuation; an
+        // `ApplyWith` can only reach here through a hand-built term
+        PrimOp::ApplyWith(l) => Err(ErrorKind::PrimError(format!(
+            "apply[{:?}] outside of call position",
+            l
+        ))
+        .into()),
+        PrimOp::Tuple(n) => match n {
+            // the lowering feeds a nullary tuple void just to have an
+            // application to ride on; the argument carries nothing
+            0 => Ok(Value::Tuple(Vec::new())),
+            1 => Ok(Value::Tuple(vec![arg])),
+            n => Ok(Value::TupleBuild(Box::new(TupleBuild {
+                remaining: n - 1,
+                elems: vec![arg],
+            }))),
+        },
+        PrimOp::Proj(i) => match arg {
+            Value::Tuple(vs) => match vs.get(i) {
+                Some(v) => Ok(v.clone()),
+                None => Err(ErrorKind::IndexOutOfBounds(i).into()),
+            },
+            arg => Err(ErrorKind::PrimError(format!(
+                "proj applied to a non-tuple: {:?}",
+                arg
+            ))
+            .into()),
+        },
+    }
+}
+
+// Synthesizes the chain of curried applications for `apply`: the
+// function and the saved continuation are bound to fresh variables so
+// the chain is ordinary CPS syntax, driven (and traced) by the main
+// loop like any other calls.
+fn unroll_apply(elems: Vec<Literal>, f: Value, k: Value, env: &Env) -> (CCall, Env) {
+    // a rest closure takes everything that remains, as one list
+    if let Value::RestClosure(c) = f {
+        let mut env = c
+            .env
+            .insert(c.param.clone(), Value::Lit(Literal::List(elems)))
+            .insert(c.cont.clone(), k);
+        if let Some(fix) = &c.fix {
+            env = env.insert(fix.clone(), Value::RestClosure(c.clone()));
+        }
+        return (clone_rc(c.body), env);
+    }
+
+    let f_v = FreeVar::fresh_named("apf");
+    let k_v = FreeVar::fresh_named("apk");
+    let env = env.insert(f_v.clone(), f).insert(k_v.clone(), k);
+
+    let mut elems = elems.into_iter();
+    let call = match elems.next() {
+        // out of arguments: hand whatever the chain produced to the
+        // continuation of the `apply` call site; an empty list returns
+        // the function untouched
+        None => CCall::KCall(
+            Rc::new(KExpr::Var(Var::Free(k_v))),
+            Rc::new(UExpr::Var(Var::Free(f_v))),
+        ),
+        // apply one argument, then re-enter `apply` with the remainder,
+        // so every round sees the current function value — a nested rest
+        // closure captures whatever is left at that point
+        Some(e) => {
+            let r_v = FreeVar::fresh_named("apr");
+            CCall::UCall(
+                Rc::new(UExpr::Var(Var::Free(f_v))),
+                Rc::new(UExpr::Lit(Ignore(e))),
+                Rc::new(KExpr::Lam(Scope::new(
+                    Binder(r_v.clone()),
+                    Rc::new(CCall::UCall(
+                        Rc::new(UExpr::Prim(Ignore(PrimOp::ApplyWith(Literal::List(
+                            elems.collect(),
+                        ))))),
+                        Rc::new(UExpr::Var(Var::Free(r_v))),
+                        Rc::new(KExpr::Var(Var::Free(k_v))),
+                    )),
+                ))),
+            )
+        }
+    };
+
+    (call, env)
+}
+
+fn apply_bin_op(
+    op: BinOp,
+    a: Literal,
+    b: Literal,
+    policy: CoercionPolicy,
+) -> Result<Value, RuntimeError> {
+    // arithmetic touching a rational promotes the other operand too and
+    // stays exact; checked before the bignum block so a rational/bigint
+    // mix lands here
+    #[cfg(feature = "rational")]
+    if matches!(op, BinOp::Div | BinOp::Add | BinOp::Sub | BinOp::Mul)
+        && matches!(
+            (&a, &b),
+            (Literal::Rational(_), _) | (_, Literal::Rational(_))
+        )
+    {
+        if let (Some(a), Some(b)) = (as_rational(&a), as_rational(&b)) {
+            use num_rational::BigRational;
+
+            return match op {
+                BinOp::Div if b == BigRational::from_integer(0.into()) => {
+                    Err(ErrorKind::DivideByZero.into())
+                }
+                BinOp::Div => Ok(Value::Lit(Literal::Rational(Box::new(a / b)))),
+                BinOp::Add => Ok(Value::Lit(Literal::Rational(Box::new(a + b)))),
+                BinOp::Sub => Ok(Value::Lit(Literal::Rational(Box::new(a - b)))),
+                BinOp::Mul => Ok(Value::Lit(Literal::Rational(Box::new(a * b)))),
+                _ => unreachable!(),
+            };
+        }
+    }
+
+    // with bignums available, arithmetic touching one promotes the other
+    // operand too and computes exactly
+    #[cfg(feature = "bignum")]
+    if matches!(op, BinOp::Div | BinOp::Add | BinOp::Sub | BinOp::Mul)
+        && matches!(
+            (&a, &b),
+            (Literal::BigInt(_), _) | (_, Literal::BigInt(_))
+        )
+    {
+        if let (Some(a), Some(b)) = (as_bignum(&a), as_bignum(&b)) {
+            use num_bigint::BigInt;
+
+            return match op {
+                BinOp::Div if b == BigInt::from(0u64) => Err(ErrorKind::DivideByZero.into()),
+                BinOp::Div => Ok(Value::Lit(Literal::BigInt(a / b))),
+                BinOp::Add => Ok(Value::Lit(Literal::BigInt(a + b))),
+                BinOp::Sub => Ok(Value::Lit(Literal::BigInt(a - b))),
+                BinOp::Mul => Ok(Value::Lit(Literal::BigInt(a * b))),
+                _ => unreachable!(),
+            };
+        }
+    }
+
+    // mixed int/float arithmetic: promote under `PromoteNumeric`, fall
+    // through to the unsupported-operands error under `Strict`
+    if policy == CoercionPolicy::PromoteNumeric
+        && matches!(op, BinOp::Div | BinOp::Add | BinOp::Sub | BinOp::Mul)
+    {
+        match (&a, &b) {
+            (Literal::Int(a), Literal::Float(b)) => {
+                return apply_bin_op(op, Literal::Float(*a as f64), Literal::Float(*b), policy)
+            }
+            (Literal::Float(a), Literal::Int(b)) => {
+                return apply_bin_op(op, Literal::Float(*a), Literal::Float(*b as f64), policy)
+            }
+            _ => {}
+        }
+    }
+
+    match (op, a, b) {
+        (BinOp::CharAt, Literal::String(s), Literal::Int(i)) => s
+            .chars()
+            .nth(i as usize)
+            .map(|c| Value::Lit(Literal::Char(c)))
+            .ok_or_else(|| ErrorKind::IndexOutOfBounds(i as usize).into()),
+        (BinOp::Div, Literal::Int(_), Literal::Int(0)) => Err(ErrorKind::DivideByZero.into()),
+        (BinOp::Div, Literal::Int(a), Literal::Int(b)) => Ok(Value::Lit(Literal::Int(a / b))),
+        (BinOp::Add, Literal::Int(a), Literal::Int(b)) => match a.checked_add(b) {
+            Some(n) => Ok(Value::Lit(Literal::Int(n))),
+            None => overflowed("overflow in add", |a, b| a + b, a, b),
+        },
+        (BinOp::Sub, Literal::Int(a), Literal::Int(b)) => match a.checked_sub(b) {
+            Some(n) => Ok(Value::Lit(Literal::Int(n))),
+            None => overflowed("underflow in sub", |a, b| a - b, a, b),
+        },
+        (BinOp::Mul, Literal::Int(a), Literal::Int(b)) => match a.checked_mul(b) {
+            Some(n) => Ok(Value::Lit(Literal::Int(n))),
+            None => overflowed("overflow in mul", |a, b| a * b, a, b),
+        },
+        // float arithmetic is plain IEEE 754 binary64 in the default
+        // round-to-nearest-even mode, applied one operation at a time:
+        // no fused-multiply-add or other contracted forms, so the same
+        // script produces bit-identical results on every platform.
+        // Division by zero follows IEEE too (infinities and NaNs)
+        // rather than erroring like the integer case.
+        (BinOp::Div, Literal::Float(a), Literal::Float(b)) => {
+            Ok(Value::Lit(Literal::Float(a / b)))
+        }
+        (BinOp::Add, Literal::Float(a), Literal::Float(b)) => {
+            Ok(Value::Lit(Literal::Float(a + b)))
+        }
+        (BinOp::Sub, Literal::Float(a), Literal::Float(b)) => {
+            Ok(Value::Lit(Literal::Float(a - b)))
+        }
+        (BinOp::Mul, Literal::Float(a), Literal::Float(b)) => {
+            Ok(Value::Lit(Literal::Float(a * b)))
+        }
+        (BinOp::Eq, a, b) => Ok(Value::Lit(Literal::Bool(a == b))),
+        (op, a, b) => Err(ErrorKind::PrimError(format!(
+            "{} applied to unsupported operands: {:?}, {:?}",
+            op, a, b
+        ))
+        .into()),
+    }
+}
+
+#[cfg(feature = "rational")]
+fn as_rational(lit: &Literal) -> Option<num_rational::BigRational> {
+    use num_rational::BigRational;
+
+    match lit {
+        Literal::Rational(r) => Some(r.as_ref().clone()),
+        Literal::BigInt(i) => Some(BigRational::from_integer(i.clone())),
+        Literal::Int(i) => Some(BigRational::from_integer((*i).into())),
+        _ => None,
+    }
+}
+
+#[cfg(feature = "bignum")]
+fn as_bignum(lit: &Literal) -> Option<num_bigint::BigInt> {
+    match lit {
+        Literal::BigInt(i) => Some(i.clone()),
+        Literal::Int(i) => Some(num_bigint::BigInt::from(*i)),
+        _ => None,
+    }
+}
+
+// A fixed-width result overflowed: promote and redo the operation
+// exactly when bignums are enabled, keep the old error otherwise.
+#[cfg(feature = "bignum")]
+fn overflowed(
+    _what: &str,
+    op: impl Fn(num_bigint::BigInt, num_bigint::BigInt) -> num_bigint::BigInt,
+    a: u64,
+    b: u64,
+) -> Result<Value, RuntimeError> {
+    use num_bigint::BigInt;
+
+    Ok(Value::Lit(Literal::BigInt(op(
+        BigInt::from(a),
+        BigInt::from(b),
+    ))))
+}
+
+#[cfg(not(feature = "bignum"))]
+fn overflowed(
+    what: &str,
+    _op: impl Fn(u64, u64) -> u64,
+    _a: u64,
+    _b: u64,
+) -> Result<Value, RuntimeError> {
+    Err(ErrorKind::PrimError(format!("integer {}", what)).into())
+}
+
+enum PrimResult {
+    Continue(CCall, Env),
+    Suspend(Box<Value>, Box<Value>),
+}
+
+fn apply_prim(
+    prim: Prim,
+    arg: Value,
+    cont: Value,
+    env: &Env,
+) -> Result<PrimResult, RuntimeError> {
+    match prim {
+        Prim::Yield => Ok(PrimResult::Suspend(Box::new(arg), Box::new(cont))),
+        Prim::Eval => {
+            let quoted = match arg {
+                Value::Lit(Literal::Quoted(e)) => clone_rc(e),
+                arg => {
+                    return Err(ErrorKind::PrimError(format!(
+                        "eval applied to non-quoted value: {:?}",
+                        arg
+                    ))
+                    .into())
+                }
+            };
+
+            // lower the quoted expression against a fresh continuation
+            // variable bound to the call site's continuation
+            let kv = FreeVar::fresh_named("k");
+            let call = t_k(quoted, Rc::new(KExpr::Var(Var::Free(kv.clone()))));
+
+            Ok(PrimResult::Continue(call, env.insert(kv, cont)))
+        }
+        Prim::Memo => match arg {
+            Value::Closure(c) => {
+                let m_v = FreeVar::fresh_named("mv");
+                let k_v = FreeVar::fresh_named("mk");
+                let memoized = Value::Memoized(Rc::new(Memoized {
+                    closure: *c,
+                    cache: Rc::new(RefCell::new(HashMap::new())),
+                }));
+
+                // hand the wrapper straight to the continuation, through
+                // fresh bindings so the step is ordinary CPS syntax
+                let env = env.insert(m_v.clone(), memoized).insert(k_v.clone(), cont);
+                Ok(PrimResult::Continue(
+                    CCall::KCall(
+                        Rc::new(KExpr::Var(Var::Free(k_v))),
+                        Rc::new(UExpr::Var(Var::Free(m_v))),
+                    ),
+                    env,
+                ))
+            }
+            arg => Err(ErrorKind::PrimError(format!(
+                "memo applied to a non-closure: {:?}",
+                arg
+            ))
+            .into()),
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use moniker::{BoundTerm, Ignore, Scope};
+
+    #[test]
+    fn tracer_sees_an_application_step_by_step() {
+        use crate::prelude::{app, lam, lit, var};
+
+        let x = FreeVar::fresh_named("x");
+        let expr = app(lam(x.clone(), var(&x)), lit(Literal::Int(5)));
+
+        let mut tracer = RecordingTracer::default();
+        let result = run_traced(expr, None, &mut tracer).unwrap();
+
+        assert!(matches!(result, Value::Lit(Literal::Int(5))));
+        // lowering produces at least the binding step and the call itself
+        assert!(tracer.steps.len() >= 2);
+        assert!(tracer.steps[0].contains("lambda"));
+        assert!(tracer
+            .binds
+            .iter()
+            .any(|(_, v)| matches!(v, Value::Lit(Literal::Int(5)))));
+    }
+
+    // fix f. lambda n. if n == 0 then 1 else n * (f (n - 1))
+    fn factorial() -> Expr {
+        use crate::prelude::{app, lam, lit, var};
+
+        let f = FreeVar::fresh_named("f");
+        let n = FreeVar::fresh_named("n");
+
+        let body = Expr::If(
+            Rc::new(Expr::Bin(
+                Ignore(BinOp::Eq),
+                Rc::new(var(&n)),
+                Rc::new(lit(Literal::Int(0))),
+            )),
+            Rc::new(lit(Literal::Int(1))),
+            Rc::new(Expr::Bin(
+                Ignore(BinOp::Mul),
+                Rc::new(var(&n)),
+                Rc::new(app(
+                    var(&f),
+                    Expr::Bin(
+                        Ignore(BinOp::Sub),
+                        Rc::new(var(&n)),
+                        Rc::new(lit(Literal::Int(1))),
+                    ),
+                )),
+            )),
+        );
+
+        Expr::Fix(Scope::new(Binder(f), Rc::new(lam(n, body))))
+    }
+
+    // fix f. lambda n. if n == 0 then 0 else f (n - 1): the recursive
+    // call is a source-level tail call
+    fn countdown() -> Expr {
+        use crate::prelude::{app, lam, lit, var};
+
+        let f = FreeVar::fresh_named("f");
+        let n = FreeVar::fresh_named("n");
+
+        let body = Expr::If(
+            Rc::new(Expr::Bin(
+                Ignore(BinOp::Eq),
+                Rc::new(var(&n)),
+                Rc::new(lit(Literal::Int(0))),
+            )),
+            Rc::new(lit(Literal::Int(0))),
+            Rc::new(app(
+                var(&f),
+                Expr::Bin(
+                    Ignore(BinOp::Sub),
+                    Rc::new(var(&n)),
+                    Rc::new(lit(Literal::Int(1))),
+                ),
+            )),
+        );
+
+        Expr::Fix(Scope::new(Binder(f), Rc::new(lam(n, body))))
+    }
+
+    #[test]
+    fn tail_loops_hold_live_continuations_constant() {
+        use crate::prelude::{app, lit};
+
+        let max_live = |expr: Expr| {
+            let mut profiler = ContProfiler::default();
+            run_traced(expr, None, &mut profiler).unwrap();
+            profiler.max_live
+        };
+
+        // a deeper tail loop needs no more continuations than a shallow
+        // one; factorial parks one multiply continuation per level
+        let shallow_tail = max_live(app(countdown(), lit(Literal::Int(4))));
+        let deep_tail = max_live(app(countdown(), lit(Literal::Int(12))));
+        assert_eq!(shallow_tail, deep_tail);
+
+        let shallow = max_live(app(factorial(), lit(Literal::Int(4))));
+        let deep = max_live(app(factorial(), lit(Literal::Int(12))));
+        assert!(deep > shallow);
+    }
+
+    #[test]
+    fn a_paused_evaluation_resumes_to_completion() {
+        use crate::prelude::{app, lit};
+
+        let expr = app(factorial(), lit(Literal::Int(5)));
+
+        let mut state = run_budgeted(expr, None, 10).unwrap();
+        assert!(matches!(state, Budgeted::Paused(_)));
+
+        let mut rounds = 0;
+        let value = loop {
+            state = match state {
+                Budgeted::Done(v) => break v,
+                Budgeted::Paused(p) => {
+                    rounds += 1;
+                    p.resume(10).unwrap()
+                }
+            };
+        };
+
+        assert!(matches!(value, Value::Lit(Literal::Int(120))));
+        assert!(rounds > 1);
+    }
+
+    #[test]
+    fn apply_unrolls_a_list_of_arguments() {
+        use crate::prelude::{lam, lit, var};
+
+        let x = FreeVar::fresh_named("x");
+        let y = FreeVar::fresh_named("y");
+
+        // (apply (lambda (x) (lambda (y) (sub x y))) [10 4])
+        let curried = lam(
+            x.clone(),
+            lam(
+                y.clone(),
+                Expr::Bin(Ignore(BinOp::Sub), Rc::new(var(&x)), Rc::new(var(&y))),
+            ),
+        );
+        let expr = Expr::Apply(
+            Rc::new(curried),
+            Rc::new(lit(Literal::List(vec![Literal::Int(10), Literal::Int(4)]))),
+        );
+
+        match run(expr).unwrap() {
+            Value::Lit(Literal::Int(6)) => {}
+            v => panic!("expected 6, got {:?}", v),
+        }
+    }
+
+    #[test]
+    fn stepping_yields_every_machine_state() {
+        use crate::prelude::{app, identity, lit};
+
+        let mut stepper = steps(app(identity(), lit(Literal::Int(7))), None);
+        assert!(stepper.value().is_none());
+
+        let states: Vec<CCall> = stepper.by_ref().map(|s| s.unwrap()).collect();
+
+        // the lowering evaluates the lambda, the argument, then the call
+        // and its continuation, so several distinct states go by
+        assert!(states.len() >= 2);
+        assert!(matches!(
+            stepper.value(),
+            Some(Value::Lit(Literal::Int(7)))
+        ));
+    }
+
+    #[test]
+    fn not_negates_a_boolean_and_rejects_the_rest() {
+        use crate::prelude::lit;
+
+        match run(Expr::Not(Rc::new(lit(Literal::Bool(true))))).unwrap() {
+            Value::Lit(Literal::Bool(false)) => {}
+            v => panic!("expected false, got {:?}", v),
+        }
+
+        let err = run(Expr::Not(Rc::new(lit(Literal::Int(3))))).unwrap_err();
+        assert!(matches!(err.kind, ErrorKind::PrimError(_)));
+    }
+
+    // fix f. lambda n. if n == 0 then 0 else if n == 1 then 1
+    //                   else (f (n - 1)) + (f (n - 2))
+    fn fibonacci() -> Expr {
+        use crate::prelude::{app, lam, lit, var};
+
+        let f = FreeVar::fresh_named("f");
+        let n = FreeVar::fresh_named("n");
+
+        let eq_n = |i| {
+            Rc::new(Expr::Bin(
+                Ignore(BinOp::Eq),
+                Rc::new(var(&n)),
+                Rc::new(lit(Literal::Int(i))),
+            ))
+        };
+        let call_less = |i| {
+            Rc::new(app(
+                var(&f),
+                Expr::Bin(
+                    Ignore(BinOp::Sub),
+                    Rc::new(var(&n)),
+                    Rc::new(lit(Literal::Int(i))),
+                ),
+            ))
+        };
+
+        let body = Expr::If(
+            eq_n(0),
+            Rc::new(lit(Literal::Int(0))),
+            Rc::new(Expr::If(
+                eq_n(1),
+                Rc::new(lit(Literal::Int(1))),
+                Rc::new(Expr::Bin(Ignore(BinOp::Add), call_less(1), call_less(2))),
+            )),
+        );
+
+        Expr::Fix(Scope::new(Binder(f), Rc::new(lam(n, body))))
+    }
+
+    #[test]
+    fn memoized_fibonacci_takes_far_fewer_steps() {
+        use crate::prelude::{app, lit, var};
+
+        struct StepCount(usize);
+        impl Tracer for StepCount {
+            fn step(&mut self, _call: &CCall, _env: &Env) {
+                self.0 += 1;
+            }
+        }
+
+        let mut plain = StepCount(0);
+        let result = run_traced(app(fibonacci(), lit(Literal::Int(15))), None, &mut plain).unwrap();
+        assert!(matches!(result, Value::Lit(Literal::Int(610))));
+
+        // (memo fib) 15 with the memo primitive bound by the host
+        let memo = FreeVar::fresh_named("memo");
+        let expr = app(app(var(&memo), fibonacci()), lit(Literal::Int(15)));
+
+        let mut memoized = StepCount(0);
+        let result = run_traced(
+            expr,
+            Some((memo, Value::Prim(Prim::Memo))),
+            &mut memoized,
+        )
+        .unwrap();
+        assert!(matches!(result, Value::Lit(Literal::Int(610))));
+
+        // the naive recursion is exponential, the memoized one linear
+        assert!(memoized.0 * 10 < plain.0);
+    }
+
+    #[test]
+    fn a_refactoring_agrees_on_a_battery_where_a_bug_does_not() {
+        use crate::prelude::{lam, lit, var};
+
+        let add = |a: Expr, b: Expr| Expr::Bin(Ignore(BinOp::Add), Rc::new(a), Rc::new(b));
+        let x = FreeVar::fresh_named("x");
+        let inc = lam(x.clone(), add(var(&x), lit(Literal::Int(1))));
+        let x = FreeVar::fresh_named("x");
+        let inc_flipped = lam(x.clone(), add(lit(Literal::Int(1)), var(&x)));
+        let x = FreeVar::fresh_named("x");
+        let inc_two = lam(x.clone(), add(var(&x), lit(Literal::Int(2))));
+
+        let inputs: Vec<Vec<Literal>> = (0..5).map(|i| vec![Literal::Int(i)]).collect();
+
+        assert!(observationally_agree(&inc, &inc_flipped, &inputs));
+        assert!(!observationally_agree(&inc, &inc_two, &inputs));
+    }
+
+    #[test]
+    fn a_rest_parameter_binds_the_remaining_arguments() {
+        use crate::prelude::{lam, lam_rest, lit, var};
+
+        let x = FreeVar::fresh_named("x");
+        let r = FreeVar::fresh_named("rest");
+
+        // (apply (lambda (x) (lambda (&rest) rest)) [1 2 3]): x takes 1,
+        // rest takes the remaining [2 3]
+        let f = lam(x, lam_rest(r.clone(), var(&r)));
+        let expr = Expr::Apply(
+            Rc::new(f),
+            Rc::new(lit(Literal::List(vec![
+                Literal::Int(1),
+                Literal::Int(2),
+                Literal::Int(3),
+            ]))),
+        );
+
+        match run(expr).unwrap() {
+            Value::Lit(Literal::List(elems)) => {
+                assert_eq!(elems, vec![Literal::Int(2), Literal::Int(3)])
+            }
+            v => panic!("expected a list, got {:?}", v),
+        }
+    }
+
+    #[test]
+    fn fix_computes_a_factorial() {
+        use crate::prelude::{app, lit};
+
+        match run(app(factorial(), lit(Literal::Int(5)))).unwrap() {
+            Value::Lit(Literal::Int(120)) => {}
+            v => panic!("expected 120, got {:?}", v),
+        }
+    }
+
+    #[cfg(feature = "bignum")]
+    #[test]
+    fn bignum_factorials_are_exact() {
+        use crate::prelude::{app, lit};
+
+        // 20! still fits the fixed-width type exactly
+        match run(app(factorial(), lit(Literal::Int(20)))).unwrap() {
+            Value::Lit(Literal::Int(2432902008176640000)) => {}
+            v => panic!("expected 20!, got {:?}", v),
+        }
+
+        // 25! doesn't: the multiplication chain promotes and stays exact
+        let expected: num_bigint::BigInt = "15511210043330985984000000".parse().unwrap();
+        match run(app(factorial(), lit(Literal::Int(25)))).unwrap() {
+            Value::Lit(Literal::BigInt(n)) => assert_eq!(n, expected),
+            v => panic!("expected 25! as a bignum, got {:?}", v),
+        }
+    }
+
+    #[cfg(feature = "rational")]
+    #[test]
+    fn rational_arithmetic_is_exact() {
+        use crate::prelude::lit;
+        use num_rational::BigRational;
+
+        let rat = |n: i64, d: i64| {
+            lit(Literal::Rational(Box::new(BigRational::new(
+                n.into(),
+                d.into(),
+            ))))
+        };
+
+        // 1/3 + 1/6 is exactly 1/2 — no float would get this right
+        let expr = Expr::Bin(Ignore(BinOp::Add), Rc::new(rat(1, 3)), Rc::new(rat(1, 6)));
+
+        match run(expr).unwrap() {
+            Value::Lit(Literal::Rational(r)) => {
+                assert_eq!(*r, BigRational::new(1.into(), 2.into()))
+            }
+            v => panic!("expected 1/2, got {:?}", v),
+        }
+
+        // a plain int promotes to the rational side
+        let expr = Expr::Bin(
+            Ignore(BinOp::Mul),
+            Rc::new(rat(1, 3)),
+            Rc::new(lit(Literal::Int(6))),
+        );
+
+        match run(expr).unwrap() {
+            Value::Lit(Literal::Rational(r)) => {
+                assert_eq!(*r, BigRational::from_integer(2.into()))
+            }
+            v => panic!("expected 2/1, got {:?}", v),
+        }
+    }
+
+    #[test]
+    fn if_selects_the_right_branch() {
+        let expr = Expr::If(
+            Rc::new(Expr::Lit(Ignore(Literal::Bool(false)))),
+            Rc::new(Expr::Lit(Ignore(Literal::Int(1)))),
+            Rc::new(Expr::Lit(Ignore(Literal::Int(2)))),
+        );
+
+        match run(expr).unwrap() {
+            Value::Lit(Literal::Int(2)) => {}
+            v => panic!("expected 2, got {:?}", v),
+        }
+    }
+
+    #[test]
+    fn cond_selects_the_first_true_clause() {
+        let clause = |test, body| {
+            (
+                Rc::new(Expr::Lit(Ignore(Literal::Bool(test)))),
+                Rc::new(Expr::Lit(Ignore(Literal::Int(body)))),
+            )
+        };
+
+        let expr = Expr::Cond(
+            vec![clause(false, 1), clause(true, 2), clause(true, 3)],
+            Rc::new(Expr::Lit(Ignore(Literal::Int(4)))),
+        );
+
+        match run(expr).unwrap() {
+            Value::Lit(Literal::Int(2)) => {}
+            v => panic!("expected 2, got {:?}", v),
+        }
+    }
+
+    #[test]
+    fn quoted_expr_round_trips() {
+        let inner = Expr::Lit(Ignore(Literal::Int(5)));
+        let expr = Expr::Lit(Ignore(Literal::Quoted(Rc::new(inner.clone()))));
+
+        match run(expr).unwrap() {
+            Value::Lit(Literal::Quoted(e)) => assert!(Expr::term_eq(&e, &inner)),
+            v => panic!("expected a quoted literal back, got {:?}", v),
+        }
+    }
+
+    #[test]
+    fn char_at_indexes_a_string() {
+        let expr = Expr::Bin(
+            Ignore(BinOp::CharAt),
+            Rc::new(Expr::Lit(Ignore(Literal::String("abc".to_owned())))),
+            Rc::new(Expr::Lit(Ignore(Literal::Int(1)))),
+        );
+
+        match run(expr).unwrap() {
+            Value::Lit(Literal::Char('b')) => {}
+            v => panic!("expected 'b', got {:?}", v),
+        }
+    }
+
+    #[test]
+    fn char_at_out_of_bounds_errors() {
+        let expr = Expr::Bin(
+            Ignore(BinOp::CharAt),
+            Rc::new(Expr::Lit(Ignore(Literal::String("abc".to_owned())))),
+            Rc::new(Expr::Lit(Ignore(Literal::Int(5)))),
+        );
+
+        match run(expr) {
+            Err(RuntimeError {
+                kind: ErrorKind::IndexOutOfBounds(5),
+                ..
+            }) => {}
+            r => panic!("expected an out-of-bounds error, got {:?}", r),
+        }
+    }
+
+    #[test]
+    fn division_by_zero_carries_a_trace() {
+        let expr = Expr::Bin(
+            Ignore(BinOp::Div),
+            Rc::new(Expr::Lit(Ignore(Literal::Int(1)))),
+            Rc::new(Expr::Lit(Ignore(Literal::Int(0)))),
+        );
+
+        let err = run(expr).unwrap_err();
+
+        assert!(matches!(err.kind, ErrorKind::DivideByZero));
+        // the innermost frame is the curried primitive applied to the
+        // divisor operand (the `b` variable the Bin lowering introduces)
+        assert!(!err.trace.is_empty());
+        assert!(err.trace[0].contains('b'));
+    }
+
+    #[test]
+    fn division_produces_a_quotient() {
+        let expr = Expr::Bin(
+            Ignore(BinOp::Div),
+            Rc::new(Expr::Lit(Ignore(Literal::Int(7)))),
+            Rc::new(Expr::Lit(Ignore(Literal::Int(2)))),
+        );
+
+        match run(expr).unwrap() {
+            Value::Lit(Literal::Int(3)) => {}
+            v => panic!("expected 3, got {:?}", v),
+        }
+    }
+
+    #[test]
+    fn passing_assert_yields_void() {
+        let expr = Expr::Assert(
+            Rc::new(Expr::Lit(Ignore(Literal::Bool(true)))),
+            Ignore("should not fire".to_owned()),
+        );
+
+        match run(expr).unwrap() {
+            Value::Lit(Literal::Void) => {}
+            v => panic!("expected void, got {:?}", v),
+        }
+    }
+
+    #[test]
+    fn failing_assert_reports_its_message() {
+        let expr = Expr::Assert(
+            Rc::new(Expr::Lit(Ignore(Literal::Bool(false)))),
+            Ignore("boom".to_owned()),
+        );
+
+        match run(expr) {
+            Err(RuntimeError {
+                kind: ErrorKind::AssertionFailed(msg),
+                ..
+            }) => assert_eq!(msg, "boom"),
+            r => panic!("expected an assertion failure, got {:?}", r),
+        }
+    }
+
+    #[test]
+    fn halt_callback_receives_final_value() {
+        use std::cell::RefCell;
+
+        let result = RefCell::new(None);
+
+        run_with_halt(Expr::Lit(Ignore(Literal::Int(42))), None, |v| {
+            *result.borrow_mut() = Some(v)
+        })
+        .unwrap();
+
+        match result.into_inner() {
+            Some(Value::Lit(Literal::Int(42))) => {}
+            v => panic!("expected the callback to see 42, got {:?}", v),
+        }
+    }
+
+    #[test]
+    fn yield_drives_a_counter_generator() {
+        let yield_v = FreeVar::fresh_named("yield");
+
+        // (\_ -> (\_ -> (\_ -> void) (yield 3)) (yield 2)) (yield 1)
+        let mut expr = Expr::Lit(Ignore(Literal::Void));
+        for n in (1..=3).rev() {
+            let ignore = FreeVar::fresh_named("_");
+            expr = Expr::App(
+                Rc::new(Expr::Lam(Scope::new(Binder(ignore), Rc::new(expr)))),
+                Rc::new(Expr::App(
+                    Rc::new(Expr::Var(Var::Free(yield_v.clone()))),
+                    Rc::new(Expr::Lit(Ignore(Literal::Int(n)))),
+                )),
+            );
+        }
+
+        let mut step = run_generator(expr, vec![(yield_v, Value::Prim(Prim::Yield))]).unwrap();
+
+        for n in 1..=3 {
+            match step {
+                Step::Yielded(Value::Lit(Literal::Int(got)), resume) => {
+                    assert_eq!(got, n);
+                    step = resume.resume(Value::Lit(Literal::Void)).unwrap();
+                }
+                s => panic!("expected a yield of {}, got {:?}", n, s),
+            }
+        }
+
+        assert!(matches!(step, Step::Done(Value::Lit(Literal::Void))));
+    }
+
+    #[test]
+    fn eval_prim_runs_quoted_expr() {
+        let eval_v = FreeVar::fresh_named("eval");
+
+        let expr = Expr::App(
+            Rc::new(Expr::Var(Var::Free(eval_v.clone()))),
+            Rc::new(Expr::Lit(Ignore(Literal::Quoted(Rc::new(Expr::Lit(
+                Ignore(Literal::Int(5)),
+            )))))),
+        );
+
+        let result = run_with_env(expr, vec![(eval_v, Value::Prim(Prim::Eval))]).unwrap();
+
+        match result {
+            Value::Lit(Literal::Int(5)) => {}
+            v => panic!("expected 5, got {:?}", v),
+        }
+    }
+
+    #[cfg(feature = "tracing")]
+    #[test]
+    fn pipeline_stages_emit_tracing_spans() {
+        use crate::prelude::{app, identity, lit};
+        use std::sync::{Arc, Mutex};
+
+        // a minimal subscriber that just records span names
+        struct SpanRecorder(Arc<Mutex<Vec<&'static str>>>);
+
+        impl tracing::Subscriber for SpanRecorder {
+            fn enabled(&self, _: &tracing::Metadata) -> bool {
+                true
+            }
+            fn new_span(&self, attrs: &tracing::span::Attributes) -> tracing::span::Id {
+                let mut names = self.0.lock().unwrap();
+                names.push(attrs.metadata().name());
+                tracing::span::Id::from_u64(names.len() as u64)
+            }
+            fn record(&self, _: &tracing::span::Id, _: &tracing::span::Record) {}
+            fn record_follows_from(&self, _: &tracing::span::Id, _: &tracing::span::Id) {}
+            fn event(&self, _: &tracing::Event) {}
+            fn enter(&self, _: &tracing::span::Id) {}
+            fn exit(&self, _: &tracing::span::Id) {}
+        }
+
+        let names = Arc::new(Mutex::new(Vec::new()));
+        tracing::subscriber::with_default(SpanRecorder(names.clone()), || {
+            crate::text::parse("1").unwrap();
+
+            let expr = crate::opt::elide_unused_args(app(identity(), lit(Literal::Int(1))));
+            let halt = FreeVar::fresh_named("halt");
+            let call = t_k(expr, Rc::new(KExpr::Var(Var::Free(halt.clone()))));
+            call.clone().into_fexpr();
+            run_ccall(call, Env::new().insert(halt, Value::Halt)).unwrap();
+        });
+
+        let names = names.lock().unwrap();
+        for expected in ["parse", "elide_unused_args", "cps_transform", "flatten", "eval"] {
+            assert!(
+                names.contains(&expected),
+                "missing span {:?} in {:?}",
+                expected,
+                *names
+            );
+        }
+    }
+
+    #[test]
+    fn a_session_threads_bindings_between_evaluations() {
+        use crate::prelude::{lit, var};
+
+        let mut session = Session::new();
+        let x = FreeVar::fresh_named("x");
+
+        // first entry: x = 20 + 1
+        let first = session
+            .eval_bind(
+                x.clone(),
+                Expr::Bin(
+                    Ignore(BinOp::Add),
+                    Rc::new(lit(Literal::Int(20))),
+                    Rc::new(lit(Literal::Int(1))),
+                ),
+            )
+            .unwrap();
+        assert!(matches!(first, Value::Lit(Literal::Int(21))));
+
+        // second entry sees the earlier binding without re-lowering it
+        let second = session
+            .eval(Expr::Bin(
+                Ignore(BinOp::Add),
+                Rc::new(var(&x)),
+                Rc::new(var(&x)),
+            ))
+            .unwrap();
+        assert!(matches!(second, Value::Lit(Literal::Int(42))));
+
+        // unbound results do not leak into the session
+        let y = FreeVar::fresh_named("y");
+        assert!(session.eval(var(&y)).is_err());
+    }
+
+    #[test]
+    fn tuples_build_and_project_each_element() {
+        use crate::prelude::{lit, proj, tuple};
+
+        for (i, expected) in [(0, 10), (1, 20), (2, 30)] {
+            let expr = proj(
+                i,
+                tuple(vec![
+                    lit(Literal::Int(10)),
+                    // components may compute, not just be literals
+                    Expr::Bin(
+                        Ignore(BinOp::Add),
+                        Rc::new(lit(Literal::Int(15))),
+                        Rc::new(lit(Literal::Int(5))),
+                    ),
+                    lit(Literal::Int(30)),
+                ]),
+            );
+
+            match run(expr).unwrap() {
+                Value::Lit(Literal::Int(n)) => assert_eq!(n, expected),
+                v => panic!("expected {}, got {:?}", expected, v),
+            }
+        }
+    }
+
+    #[test]
+    fn projecting_out_of_range_is_an_error() {
+        use crate::prelude::{lit, proj, tuple};
+
+        let expr = proj(3, tuple(vec![lit(Literal::Int(1)), lit(Literal::Int(2))]));
+
+        let err = run(expr).unwrap_err();
+        assert!(matches!(err.kind, ErrorKind::IndexOutOfBounds(3)));
+    }
+
+    #[test]
+    fn float_arithmetic_is_bit_reproducible() {
+        // (0.1 + 0.2) * 100.0, each step rounded to nearest-even with
+        // no contraction; the expected bit patterns are fixed by IEEE
+        // 754 binary64, not by the platform this test runs on
+        let sum = Expr::Bin(
+            Ignore(BinOp::Add),
+            Rc::new(Expr::Lit(Ignore(Literal::Float(0.1)))),
+            Rc::new(Expr::Lit(Ignore(Literal::Float(0.2)))),
+        );
+        let expr = Expr::Bin(
+            Ignore(BinOp::Mul),
+            Rc::new(sum),
+            Rc::new(Expr::Lit(Ignore(Literal::Float(100.0)))),
+        );
+
+        match run(expr).unwrap() {
+            Value::Lit(Literal::Float(f)) => {
+                assert_eq!(f.to_bits(), 0x403e_0000_0000_0001);
+            }
+            v => panic!("expected a float, got {:?}", v),
+        }
+    }
+
+    #[test]
+    fn float_division_by_zero_follows_ieee() {
+        let expr = Expr::Bin(
+            Ignore(BinOp::Div),
+            Rc::new(Expr::Lit(Ignore(Literal::Float(1.0)))),
+            Rc::new(Expr::Lit(Ignore(Literal::Float(0.0)))),
+        );
+
+        match run(expr).unwrap() {
+            Value::Lit(Literal::Float(f)) => assert!(f.is_infinite() && f > 0.0),
+            v => panic!("expected infinity, got {:?}", v),
+        }
+    }
+
+    #[test]
+    fn pooled_evaluation_matches_the_default_exactly() {
+        use crate::prelude::{church_num, identity};
+
+        // closure-heavy and arithmetic-heavy fixtures, run both ways
+        let fixtures = || {
+            vec![
+                Expr::App(
+                    Rc::new(Expr::App(
+                        Rc::new(church_num(50)),
+                        Rc::new(identity()),
+                    )),
+                    Rc::new(Expr::Lit(Ignore(Literal::Int(7)))),
+                ),
+                Expr::Bin(
+                    Ignore(BinOp::Mul),
+                    Rc::new(Expr::Bin(
+                        Ignore(BinOp::Add),
+                        Rc::new(Expr::Lit(Ignore(Literal::Int(20)))),
+                        Rc::new(Expr::Lit(Ignore(Literal::Int(1)))),
+                    )),
+                    Rc::new(Expr::Lit(Ignore(Literal::Int(2)))),
+                ),
+            ]
+        };
+
+        let pool = EnvPool::new();
+        for (plain, pooled) in fixtures().into_iter().zip(fixtures()) {
+            let expected = run(plain).unwrap();
+            let got = run_with_pool(pooled, None, &pool).unwrap();
+
+            match (expected, got) {
+                (Value::Lit(a), Value::Lit(b)) => assert_eq!(a, b),
+                (a, b) => panic!("expected literals, got {:?} and {:?}", a, b),
+            }
+        }
+
+        // the pool picked up tables from the runs above
+        assert!(!pool.0.borrow().is_empty());
+        pool.reset();
+        assert!(pool.0.borrow().is_empty());
+    }
+
+    #[test]
+    fn a_false_condition_skips_the_loop_entirely() {
+        let expr = Expr::While(
+            Rc::new(Expr::Lit(Ignore(Literal::Bool(false)))),
+            Rc::new(Expr::Lit(Ignore(Literal::Int(1)))),
+        );
+
+        match run(expr).unwrap() {
+            Value::Lit(Literal::Void) => {}
+            v => panic!("expected void, got {:?}", v),
+        }
+    }
+
+    #[test]
+    fn a_counting_loop_runs_its_body_once_per_iteration() {
+        use crate::prelude::{app, lit, var};
+
+        // the host plays the mutable state: the condition yields void
+        // and is answered with a bool, the body yields 1 and is counted
+        let y = FreeVar::fresh_named("yield");
+        let expr = Expr::While(
+            Rc::new(app(var(&y), lit(Literal::Void))),
+            Rc::new(app(var(&y), lit(Literal::Int(1)))),
+        );
+
+        let mut step = run_generator(expr, vec![(y, Value::Prim(Prim::Yield))]).unwrap();
+        let mut conds = 0;
+        let mut bodies = 0;
+
+        loop {
+            match step {
+                Step::Done(Value::Lit(Literal::Void)) => break,
+                Step::Done(v) => panic!("expected void, got {:?}", v),
+                Step::Yielded(Value::Lit(Literal::Void), resume) => {
+                    conds += 1;
+                    step = resume
+                        .resume(Value::Lit(Literal::Bool(conds <= 3)))
+                        .unwrap();
+                }
+                Step::Yielded(Value::Lit(Literal::Int(1)), resume) => {
+                    bodies += 1;
+                    step = resume.resume(Value::Lit(Literal::Void)).unwrap();
+                }
+                Step::Yielded(v, _) => panic!("unexpected yield: {:?}", v),
+            }
+        }
+
+        assert_eq!(conds, 4);
+        assert_eq!(bodies, 3);
+    }
+
+    #[test]
+    fn loops_hold_continuation_pressure_constant() {
+        // the desugared loop re-enters itself by tail call, so however
+        // many times the condition is tested, the continuation count
+        // never grows with it; a false condition makes it observable
+        // without host-driven state
+        let expr = Expr::While(
+            Rc::new(Expr::Lit(Ignore(Literal::Bool(false)))),
+            Rc::new(Expr::Lit(Ignore(Literal::Int(1)))),
+        );
+
+        let mut profiler = ContProfiler::default();
+        run_traced(expr, None, &mut profiler).unwrap();
+
+        assert!(profiler.max_live <= 2);
+    }
+
+    #[test]
+    fn mixed_arithmetic_respects_the_coercion_policy() {
+        let mixed = || {
+            Expr::Bin(
+                Ignore(BinOp::Add),
+                Rc::new(Expr::Lit(Ignore(Literal::Int(1)))),
+                Rc::new(Expr::Lit(Ignore(Literal::Float(2.0)))),
+            )
+        };
+
+        // the default is strict: mixed kinds are an error
+        let err = run(mixed()).unwrap_err();
+        assert!(matches!(err.kind, ErrorKind::PrimError(_)));
+
+        // promotion widens the int side and proceeds in floats
+        match run_with_policy(mixed(), None, CoercionPolicy::PromoteNumeric).unwrap() {
+            Value::Lit(Literal::Float(f)) => assert_eq!(f, 3.0),
+            v => panic!("expected 3.0, got {:?}", v),
+        }
+    }
+
+    #[test]
+    fn host_conversions_extract_literal_results() {
+        use std::convert::TryFrom;
+
+        let expr = Expr::Bin(
+            Ignore(BinOp::Add),
+            Rc::new(Expr::Lit(Ignore(Literal::Int(40)))),
+            Rc::new(Expr::Lit(Ignore(Literal::Int(2)))),
+        );
+
+        let value = run(expr).unwrap();
+        assert_eq!(value.clone().into_literal(), Some(Literal::Int(42)));
+        assert_eq!(u64::try_from(value).unwrap(), 42);
+    }
+
+    #[test]
+    fn a_closure_result_refuses_host_conversion() {
+        use std::convert::TryFrom;
+
+        let value = run(crate::prelude::identity()).unwrap();
+        assert!(value.clone().into_literal().is_none());
+
+        let err = u64::try_from(value).unwrap_err();
+        let shown = err.to_string();
+        assert!(shown.starts_with("expected an integer"), "got {:?}", shown);
+        assert!(shown.contains("Closure"), "got {:?}", shown);
+    }
+
+    #[test]
+    fn mutual_recursion_classifies_parity() {
+        use crate::prelude::{app, fresh, lam, lit, var};
+        use moniker::Binder;
+
+        let even = fresh("even");
+        let odd = fresh("odd");
+        let n = fresh("n");
+        let m = fresh("m");
+
+        let is_zero = |v: &FreeVar<String>| {
+            Expr::Bin(
+                Ignore(BinOp::Eq),
+                Rc::new(var(v)),
+                Rc::new(lit(Literal::Int(0))),
+            )
+        };
+        let minus_one = |v: &FreeVar<String>| {
+            Expr::Bin(
+                Ignore(BinOp::Sub),
+                Rc::new(var(v)),
+                Rc::new(lit(Literal::Int(1))),
+            )
+        };
+
+        // even = λn. if n == 0 then true else odd (n - 1)
+        let even_def = lam(
+            n.clone(),
+            Expr::If(
+                Rc::new(is_zero(&n)),
+                Rc::new(lit(Literal::Bool(true))),
+                Rc::new(app(var(&odd), minus_one(&n))),
+            ),
+        );
+        // odd = λm. if m == 0 then false else even (m - 1)
+        let odd_def = lam(
+            m.clone(),
+            Expr::If(
+                Rc::new(is_zero(&m)),
+                Rc::new(lit(Literal::Bool(false))),
+                Rc::new(app(var(&even), minus_one(&m))),
+            ),
+        );
+
+        let classify = |arg: u64| {
+            Expr::LetRecMany(Scope::new(
+                vec![Binder(even.clone()), Binder(odd.clone())],
+                (
+                    vec![Rc::new(even_def.clone()), Rc::new(odd_def.clone())],
+                    Rc::new(app(var(&even), lit(Literal::Int(arg)))),
+                ),
+            ))
+        };
+
+        assert!(matches!(
+            run(classify(10)).unwrap(),
+            Value::Lit(Literal::Bool(true))
+        ));
+        assert!(matches!(
+            run(classify(7)).unwrap(),
+            Value::Lit(Literal::Bool(false))
+        ));
+    }
+
+    #[test]
+    fn integer_values_are_cheap_immediates() {
+        // the representation contract behind the arithmetic bench: an
+        // integer result lives in the enum payload, and the boxed
+        // closure variants keep the union a few words wide
+        assert!(std::mem::size_of::<Value>() <= 64);
+
+        // x*3 - x*2 + 1 for x = 1: the slimmer representation changes
+        // nothing observable
+        let x = FreeVar::fresh_named("x");
+        let op = |op, a, b| Expr::Bin(Ignore(op), Rc::new(a), Rc::new(b));
+        let body = op(
+            BinOp::Add,
+            op(
+                BinOp::Sub,
+                op(
+                    BinOp::Mul,
+                    Expr::Var(Var::Free(x.clone())),
+                    Expr::Lit(Ignore(Literal::Int(3))),
+                ),
+                op(
+                    BinOp::Mul,
+                    Expr::Var(Var::Free(x.clone())),
+                    Expr::Lit(Ignore(Literal::Int(2))),
+                ),
+            ),
+            Expr::Lit(Ignore(Literal::Int(1))),
+        );
+        let expr = crate::prelude::app(
+            crate::prelude::lam(x, body),
+            Expr::Lit(Ignore(Literal::Int(1))),
+        );
+
+        assert!(matches!(
+            run(expr).unwrap(),
+            Value::Lit(Literal::Int(2))
+        ));
+    }
+
+    #[test]
+    fn an_int_casts_to_a_float_explicitly() {
+        let expr = Expr::Cast(
+            Ignore(CastKind::IntToFloat),
+            Rc::new(Expr::Lit(Ignore(Literal::Int(3)))),
+        );
+
+        match run(expr).unwrap() {
+            Value::Lit(Literal::Float(f)) => assert_eq!(f, 3.0),
+            v => panic!("expected a float, got {:?}", v),
+        }
+    }
+
+    #[test]
+    fn a_mismatched_cast_is_a_runtime_error() {
+        let expr = Expr::Cast(
+            Ignore(CastKind::BoolToInt),
+            Rc::new(Expr::Lit(Ignore(Literal::String("no".to_owned())))),
+        );
+
+        let err = run(expr).unwrap_err();
+        assert!(err.to_string().contains("cast bool->int"), "got {}", err);
+    }
+
+    #[test]
+    fn a_sandbox_denies_ungranted_primitives() {
+        let y = FreeVar::fresh_named("yield");
+        let install = |y: &FreeVar<String>| vec![(y.clone(), Value::Prim(Prim::Yield))];
+
+        // arithmetic is part of the language, not a capability
+        let arith = Expr::Bin(
+            Ignore(BinOp::Add),
+            Rc::new(Expr::Lit(Ignore(Literal::Int(1)))),
+            Rc::new(Expr::Lit(Ignore(Literal::Int(2)))),
+        );
+        let step = run_sandboxed(arith, install(&y), Capabilities::none()).unwrap();
+        assert!(matches!(step, Step::Done(Value::Lit(Literal::Int(3)))));
+
+        // calling the installed primitive without its grant is refused
+        let call = Expr::App(
+            Rc::new(Expr::Var(Var::Free(y.clone()))),
+            Rc::new(Expr::Lit(Ignore(Literal::Int(1)))),
+        );
+        let err = run_sandboxed(call.clone(), install(&y), Capabilities::none()).unwrap_err();
+        assert!(
+            matches!(&err.kind, ErrorKind::CapabilityDenied(name) if name == "yield"),
+            "got {:?}",
+            err.kind
+        );
+
+        // and goes through once granted
+        let granted = Capabilities::none().with(&Prim::Yield);
+        let step = run_sandboxed(call, install(&y), granted).unwrap();
+        assert!(matches!(step, Step::Yielded(Value::Lit(Literal::Int(1)), _)));
+    }
+
+    #[test]
+    fn a_finally_cleanup_runs_once_on_the_normal_path() {
+        use crate::prelude::{app, fresh, lit, var};
+
+        // the host plays the cleanup's effect: it yields, and the
+        // yields are counted — one suspension, then done
+        let y = fresh("yield");
+
+        // try 7 finally (yield void)
+        let expr = Expr::TryFinally(
+            Rc::new(lit(Literal::Int(7))),
+            Rc::new(app(var(&y), lit(Literal::Void))),
+        );
+
+        let step = run_generator(expr, vec![(y, Value::Prim(Prim::Yield))]).unwrap();
+        let resume = match step {
+            Step::Yielded(Value::Lit(Literal::Void), resume) => resume,
+            s => panic!("expected the cleanup to yield, got {:?}", s),
+        };
+
+        // the cleanup's own result is discarded; the body's value
+        // comes through
+        match resume.resume(Value::Lit(Literal::Void)).unwrap() {
+            Step::Done(Value::Lit(Literal::Int(7))) => {}
+            s => panic!("expected the body's 7, got {:?}", s),
+        }
+    }
+
+    #[test]
+    fn a_finally_cleanup_runs_once_while_an_error_unwinds() {
+        use crate::prelude::{app, fresh, lit, var};
+
+        let y = fresh("yield");
+
+        // try (assert false "boom") finally (yield void)
+        let expr = Expr::TryFinally(
+            Rc::new(Expr::Assert(
+                Rc::new(lit(Literal::Bool(false))),
+                Ignore("boom".to_owned()),
+            )),
+            Rc::new(app(var(&y), lit(Literal::Void))),
+        );
+
+        // the failed assertion does not escape before the cleanup has
+        // run: the yield inside it comes first
+        let step = run_generator(expr, vec![(y, Value::Prim(Prim::Yield))]).unwrap();
+        let resume = match step {
+            Step::Yielded(Value::Lit(Literal::Void), resume) => resume,
+            s => panic!("expected the cleanup to yield, got {:?}", s),
+        };
+
+        // once the cleanup finishes, the original error resumes its
+        // unwind — with no second yield, so the cleanup ran exactly once
+        let err = resume.resume(Value::Lit(Literal::Void)).unwrap_err();
+        assert!(
+            matches!(&err.kind, ErrorKind::AssertionFailed(msg) if msg == "boom"),
+            "got {:?}",
+            err.kind
+        );
+    }
+
+    #[test]
+    fn amb_backtracks_until_the_test_passes() {
+        use crate::prelude::{fresh, let_in, lit, var};
+
+        // find x in {1, 2, 3} with x == 3: the first two choices fail
+        // the test, and each fail re-enters the next alternative
+        let x = fresh("x");
+        let choice = Expr::Amb(
+            Rc::new(lit(Literal::Int(1))),
+            Rc::new(Expr::Amb(
+                Rc::new(lit(Literal::Int(2))),
+                Rc::new(lit(Literal::Int(3))),
+            )),
+        );
+        let test = Expr::If(
+            Rc::new(Expr::Bin(
+                Ignore(BinOp::Eq),
+                Rc::new(var(&x)),
+                Rc::new(lit(Literal::Int(3))),
+            )),
+            Rc::new(var(&x)),
+            Rc::new(Expr::Fail),
+        );
+
+        match run(let_in(x, choice, test)).unwrap() {
+            Value::Lit(Literal::Int(3)) => {}
+            v => panic!("expected 3, got {:?}", v),
+        }
+    }
+
+    #[test]
+    fn a_fail_with_no_pending_choice_is_an_error() {
+        let err = run(Expr::Fail).unwrap_err();
+        assert!(
+            matches!(&err.kind, ErrorKind::PrimError(msg) if msg.contains("no pending")),
+            "got {:?}",
+            err.kind
+        );
+    }
+
+    #[test]
+    fn a_thunk_runs_its_body_exactly_once() {
+        use crate::prelude::{app, fresh, let_in, lit, var};
+
+        // the host plays the expensive computation: the delayed body
+        // yields, and each yield is counted
+        let y = fresh("yield");
+        let t = fresh("t");
+        let first = fresh("_");
+
+        // let t = delay (yield void) in (let _ = force t in force t)
+        let expr = let_in(
+            t.clone(),
+            Expr::Delay(Rc::new(app(var(&y), lit(Literal::Void)))),
+            let_in(
+                first,
+                Expr::Force(Rc::new(var(&t))),
+                Expr::Force(Rc::new(var(&t))),
+            ),
+        );
+
+        let mut step = run_generator(expr, vec![(y, Value::Prim(Prim::Yield))]).unwrap();
+        let mut runs = 0;
+
+        let result = loop {
+            match step {
+                Step::Done(v) => break v,
+                Step::Yielded(Value::Lit(Literal::Void), resume) => {
+                    runs += 1;
+                    step = resume.resume(Value::Lit(Literal::Int(42))).unwrap();
+                }
+                Step::Yielded(v, _) => panic!("unexpected yield: {:?}", v),
+            }
+        };
+
+        // the body ran on the first force only; the second answered
+        // from the cache with the same value
+        assert_eq!(runs, 1);
+        assert!(matches!(result, Value::Lit(Literal::Int(42))));
+    }
+}
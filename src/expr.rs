@@ -1,30 +1,447 @@
 use moniker::BoundTerm;
-use moniker::{Binder, Scope, Var, Ignore};
+use moniker::{Binder, FreeVar, Scope, Var, Ignore};
 
-use pretty::{BoxAllocator, DocAllocator, DocBuilder};
+#[cfg(feature = "pretty")]
+use pretty::{Arena, DocAllocator, DocBuilder};
+#[cfg(feature = "pretty")]
 use termcolor::{Color, ColorSpec, WriteColor};
 
-use std::{io::Result, rc::Rc};
+#[cfg(feature = "pretty")]
+use std::io::Result;
+use std::collections::HashMap;
+use std::rc::Rc;
 
+use crate::cont_expr::{BinOp, CastKind};
 use crate::literals::Literal;
+#[cfg(feature = "pretty")]
+use crate::render::PrettyConfig;
+use crate::utils::grow_stack;
+
+// the scope of a `LetRecMany`: the binders are positional with the
+// definitions, and all of them are visible in every definition and in
+// the body
+pub type LetRecScope = Scope<Vec<Binder<String>>, (Vec<Rc<Expr>>, Rc<Expr>)>;
 
 #[derive(Debug, Clone, BoundTerm)]
 pub enum Expr {
     Var(Var<String>),
     Lit(Ignore<Literal>),
     Lam(Scope<Binder<String>, Rc<Expr>>),
+    // a rest lambda: under `apply`, the parameter binds the list of
+    // every argument remaining at that point; calling one directly
+    // (outside `apply`) is a runtime error
+    LamRest(Scope<Binder<String>, Rc<Expr>>),
     App(Rc<Expr>, Rc<Expr>),
+    // applies the function to the elements of a list, one curried
+    // application per element; the second operand must evaluate to a
+    // `Literal::List`, whose length the program may not know statically
+    Apply(Rc<Expr>, Rc<Expr>),
+    // binds the value to the variable within the body; operationally
+    // the same as applying an immediate lambda, but kept as its own node
+    // so lowering and read-back can round-trip it as a `let`
+    Let(Rc<Expr>, Scope<Binder<String>, Rc<Expr>>),
+    // evaluates the condition; false aborts with the message, true
+    // continues with void
+    Assert(Rc<Expr>, Ignore<String>),
+    // a built-in binary operation; operands evaluate left to right
+    Bin(Ignore<BinOp>, Rc<Expr>, Rc<Expr>),
+    // boolean negation; the operand must evaluate to a boolean
+    Not(Rc<Expr>),
+    // an explicit literal conversion; the operand must evaluate to the
+    // kind the cast expects, and anything else is a runtime error
+    Cast(Ignore<CastKind>, Rc<Expr>),
+    // captures the body as a thunk without evaluating it
+    Delay(Rc<Expr>),
+    // runs a thunk's body, at most once; the operand must evaluate to a
+    // thunk built by `delay`
+    Force(Rc<Expr>),
+    // runs the body, then the cleanup, producing the body's value; the
+    // cleanup runs exactly once whether the body completes or an error
+    // unwinds through it
+    TryFinally(Rc<Expr>, Rc<Expr>),
+    // an n-ary tuple; components evaluate left to right
+    Tuple(Vec<Rc<Expr>>),
+    // projects component `i` (zero-based) out of a tuple; an index out
+    // of range is a runtime error, never a panic
+    Proj(Ignore<usize>, Rc<Expr>),
+    // evaluates the condition, then exactly one of the branches
+    If(Rc<Expr>, Rc<Expr>, Rc<Expr>),
+    // multi-branch conditional: tests run in order until one is true, and
+    // its body runs; the final expression runs when none are. Pure sugar
+    // over `If` — the CPS transform never sees this node
+    Cond(Vec<(Rc<Expr>, Rc<Expr>)>, Rc<Expr>),
+    // loops while the condition holds, evaluating the body for effect
+    // and producing void. Pure sugar over `Fix` — the desugared loop
+    // re-enters itself by tail call, so it runs in constant stack
+    While(Rc<Expr>, Rc<Expr>),
+    // nondeterministic choice: runs the first expression; a later `fail`
+    // backtracks here and runs the second with the continuation captured
+    // at this point. Backtracking does not unwind `try/finally`
+    // cleanups, so combining the two is unsupported
+    Amb(Rc<Expr>, Rc<Expr>),
+    // abandons the current path and backtracks to the most recent
+    // pending `amb` alternative; failing with none pending is a runtime
+    // error
+    Fail,
+    // anonymous recursion: the binder refers to the whole fix expression
+    // within its body, which must evaluate to a lambda
+    Fix(Scope<Binder<String>, Rc<Expr>>),
+    // a placeholder for a fragment the recovering parser could not
+    // read, carrying its diagnostic message; evaluating one aborts with
+    // that message, so markers never silently produce a value
+    Error(Ignore<String>),
+    // mutually recursive definitions: every binder is visible in every
+    // definition and in the body. The binder and definition lists are
+    // positional, so they must be the same length. Pure sugar over
+    // `Fix`, `Tuple` and `Proj` — the CPS transform never sees this node
+    LetRecMany(LetRecScope),
 }
 
 impl Expr {
+    // Rebuilds the tree with `f` applied to every literal payload. The
+    // scopes are rebuilt raw rather than reopened, so binding structure
+    // is preserved exactly; quoted expressions are handed to `f` as
+    // literals, not descended into.
+    pub fn map_literals(&self, f: impl Fn(&Literal) -> Literal) -> Expr {
+        self.map_literals_inner(&f)
+    }
+
+    // A cheap node count for pre-sizing buffers before a lowering. The
+    // CPS output is larger by a small constant factor (applications grow
+    // administrative continuations), so callers should scale this rather
+    // than treat it as exact.
+    pub fn size_hint(&self) -> usize {
+        grow_stack(|| match self {
+            Expr::Var(_) | Expr::Lit(_) | Expr::Error(_) | Expr::Fail => 1,
+            Expr::Lam(s) | Expr::LamRest(s) | Expr::Fix(s) => 1 + s.unsafe_body.size_hint(),
+            Expr::App(a, b) | Expr::Apply(a, b) | Expr::Bin(_, a, b) => {
+                1 + a.size_hint() + b.size_hint()
+            }
+            Expr::Assert(e, _) | Expr::Not(e) | Expr::Cast(_, e) | Expr::Proj(_, e) => {
+                1 + e.size_hint()
+            }
+            Expr::Delay(e) | Expr::Force(e) => 1 + e.size_hint(),
+            Expr::Let(v, s) => 1 + v.size_hint() + s.unsafe_body.size_hint(),
+            Expr::While(c, b) | Expr::TryFinally(c, b) | Expr::Amb(c, b) => {
+                1 + c.size_hint() + b.size_hint()
+            }
+            Expr::LetRecMany(s) => {
+                let (defs, body) = &s.unsafe_body;
+                1 + defs.iter().map(|d| d.size_hint()).sum::<usize>() + body.size_hint()
+            }
+            Expr::Tuple(es) => 1 + es.iter().map(|e| e.size_hint()).sum::<usize>(),
+            Expr::If(c, t, e) => 1 + c.size_hint() + t.size_hint() + e.size_hint(),
+            Expr::Cond(arms, default) => {
+                1 + arms
+                    .iter()
+                    .map(|(t, b)| t.size_hint() + b.size_hint())
+                    .sum::<usize>()
+                    + default.size_hint()
+            }
+        })
+    }
+
+    fn map_literals_inner<F: Fn(&Literal) -> Literal>(&self, f: &F) -> Expr {
+        match self {
+            Expr::Var(v) => Expr::Var(v.clone()),
+            Expr::Lit(Ignore(l)) => Expr::Lit(Ignore(f(l))),
+            Expr::Error(msg) => Expr::Error(msg.clone()),
+            Expr::Lam(s) => Expr::Lam(Scope {
+                unsafe_pattern: s.unsafe_pattern.clone(),
+                unsafe_body: Rc::new(s.unsafe_body.map_literals_inner(f)),
+            }),
+            Expr::LamRest(s) => Expr::LamRest(Scope {
+                unsafe_pattern: s.unsafe_pattern.clone(),
+                unsafe_body: Rc::new(s.unsafe_body.map_literals_inner(f)),
+            }),
+            Expr::App(a, b) => Expr::App(
+                Rc::new(a.map_literals_inner(f)),
+                Rc::new(b.map_literals_inner(f)),
+            ),
+            Expr::Apply(a, b) => Expr::Apply(
+                Rc::new(a.map_literals_inner(f)),
+                Rc::new(b.map_literals_inner(f)),
+            ),
+            Expr::Let(v, s) => Expr::Let(
+                Rc::new(v.map_literals_inner(f)),
+                Scope {
+                    unsafe_pattern: s.unsafe_pattern.clone(),
+                    unsafe_body: Rc::new(s.unsafe_body.map_literals_inner(f)),
+                },
+            ),
+            Expr::Assert(c, msg) => {
+                Expr::Assert(Rc::new(c.map_literals_inner(f)), msg.clone())
+            }
+            Expr::Bin(op, a, b) => Expr::Bin(
+                *op,
+                Rc::new(a.map_literals_inner(f)),
+                Rc::new(b.map_literals_inner(f)),
+            ),
+            Expr::Not(e) => Expr::Not(Rc::new(e.map_literals_inner(f))),
+            Expr::Cast(kind, e) => Expr::Cast(*kind, Rc::new(e.map_literals_inner(f))),
+            Expr::Delay(e) => Expr::Delay(Rc::new(e.map_literals_inner(f))),
+            Expr::Force(e) => Expr::Force(Rc::new(e.map_literals_inner(f))),
+            Expr::Tuple(es) => Expr::Tuple(
+                es.iter()
+                    .map(|e| Rc::new(e.map_literals_inner(f)))
+                    .collect(),
+            ),
+            Expr::Proj(i, e) => Expr::Proj(*i, Rc::new(e.map_literals_inner(f))),
+            Expr::If(c, t, e) => Expr::If(
+                Rc::new(c.map_literals_inner(f)),
+                Rc::new(t.map_literals_inner(f)),
+                Rc::new(e.map_literals_inner(f)),
+            ),
+            Expr::Cond(clauses, els) => Expr::Cond(
+                clauses
+                    .iter()
+                    .map(|(test, body)| {
+                        (
+                            Rc::new(test.map_literals_inner(f)),
+                            Rc::new(body.map_literals_inner(f)),
+                        )
+                    })
+                    .collect(),
+                Rc::new(els.map_literals_inner(f)),
+            ),
+            Expr::While(c, b) => Expr::While(
+                Rc::new(c.map_literals_inner(f)),
+                Rc::new(b.map_literals_inner(f)),
+            ),
+            Expr::TryFinally(b, cl) => Expr::TryFinally(
+                Rc::new(b.map_literals_inner(f)),
+                Rc::new(cl.map_literals_inner(f)),
+            ),
+            Expr::Amb(a, b) => Expr::Amb(
+                Rc::new(a.map_literals_inner(f)),
+                Rc::new(b.map_literals_inner(f)),
+            ),
+            Expr::Fail => Expr::Fail,
+            Expr::Fix(s) => Expr::Fix(Scope {
+                unsafe_pattern: s.unsafe_pattern.clone(),
+                unsafe_body: Rc::new(s.unsafe_body.map_literals_inner(f)),
+            }),
+            Expr::LetRecMany(s) => {
+                let (defs, body) = &s.unsafe_body;
+                Expr::LetRecMany(Scope {
+                    unsafe_pattern: s.unsafe_pattern.clone(),
+                    unsafe_body: (
+                        defs.iter()
+                            .map(|d| Rc::new(d.map_literals_inner(f)))
+                            .collect(),
+                        Rc::new(body.map_literals_inner(f)),
+                    ),
+                })
+            }
+        }
+    }
+
+    // Rewrites free-variable identities according to `mapping`, for
+    // hygienic splicing of one fragment into another. The scopes are
+    // rebuilt raw rather than reopened, so bound occurrences — which are
+    // `Var::Bound` in a raw traversal — are never touched, even when a
+    // binder shares its pretty name with a renamed variable.
+    pub fn rename_free(&self, mapping: &HashMap<FreeVar<String>, FreeVar<String>>) -> Expr {
+        match self {
+            Expr::Var(Var::Free(v)) => match mapping.get(v) {
+                Some(new) => Expr::Var(Var::Free(new.clone())),
+                None => Expr::Var(Var::Free(v.clone())),
+            },
+            Expr::Var(v @ Var::Bound(_)) => Expr::Var(v.clone()),
+            Expr::Lit(l) => Expr::Lit(l.clone()),
+            Expr::Error(msg) => Expr::Error(msg.clone()),
+            Expr::Lam(s) => Expr::Lam(Scope {
+                unsafe_pattern: s.unsafe_pattern.clone(),
+                unsafe_body: Rc::new(s.unsafe_body.rename_free(mapping)),
+            }),
+            Expr::LamRest(s) => Expr::LamRest(Scope {
+                unsafe_pattern: s.unsafe_pattern.clone(),
+                unsafe_body: Rc::new(s.unsafe_body.rename_free(mapping)),
+            }),
+            Expr::App(f, e) => Expr::App(
+                Rc::new(f.rename_free(mapping)),
+                Rc::new(e.rename_free(mapping)),
+            ),
+            Expr::Apply(f, l) => Expr::Apply(
+                Rc::new(f.rename_free(mapping)),
+                Rc::new(l.rename_free(mapping)),
+            ),
+            Expr::Let(v, s) => Expr::Let(
+                Rc::new(v.rename_free(mapping)),
+                Scope {
+                    unsafe_pattern: s.unsafe_pattern.clone(),
+                    unsafe_body: Rc::new(s.unsafe_body.rename_free(mapping)),
+                },
+            ),
+            Expr::Assert(c, msg) => Expr::Assert(Rc::new(c.rename_free(mapping)), msg.clone()),
+            Expr::Bin(op, a, b) => Expr::Bin(
+                *op,
+                Rc::new(a.rename_free(mapping)),
+                Rc::new(b.rename_free(mapping)),
+            ),
+            Expr::Not(e) => Expr::Not(Rc::new(e.rename_free(mapping))),
+            Expr::Cast(kind, e) => Expr::Cast(*kind, Rc::new(e.rename_free(mapping))),
+            Expr::Delay(e) => Expr::Delay(Rc::new(e.rename_free(mapping))),
+            Expr::Force(e) => Expr::Force(Rc::new(e.rename_free(mapping))),
+            Expr::Tuple(es) => {
+                Expr::Tuple(es.iter().map(|e| Rc::new(e.rename_free(mapping))).collect())
+            }
+            Expr::Proj(i, e) => Expr::Proj(*i, Rc::new(e.rename_free(mapping))),
+            Expr::If(c, t, e) => Expr::If(
+                Rc::new(c.rename_free(mapping)),
+                Rc::new(t.rename_free(mapping)),
+                Rc::new(e.rename_free(mapping)),
+            ),
+            Expr::Cond(clauses, els) => Expr::Cond(
+                clauses
+                    .iter()
+                    .map(|(test, body)| {
+                        (
+                            Rc::new(test.rename_free(mapping)),
+                            Rc::new(body.rename_free(mapping)),
+                        )
+                    })
+                    .collect(),
+                Rc::new(els.rename_free(mapping)),
+            ),
+            Expr::While(c, b) => Expr::While(
+                Rc::new(c.rename_free(mapping)),
+                Rc::new(b.rename_free(mapping)),
+            ),
+            Expr::TryFinally(b, cl) => Expr::TryFinally(
+                Rc::new(b.rename_free(mapping)),
+                Rc::new(cl.rename_free(mapping)),
+            ),
+            Expr::Amb(a, b) => Expr::Amb(
+                Rc::new(a.rename_free(mapping)),
+                Rc::new(b.rename_free(mapping)),
+            ),
+            Expr::Fail => Expr::Fail,
+            Expr::Fix(s) => Expr::Fix(Scope {
+                unsafe_pattern: s.unsafe_pattern.clone(),
+                unsafe_body: Rc::new(s.unsafe_body.rename_free(mapping)),
+            }),
+            Expr::LetRecMany(s) => {
+                let (defs, body) = &s.unsafe_body;
+                Expr::LetRecMany(Scope {
+                    unsafe_pattern: s.unsafe_pattern.clone(),
+                    unsafe_body: (
+                        defs.iter().map(|d| Rc::new(d.rename_free(mapping))).collect(),
+                        Rc::new(body.rename_free(mapping)),
+                    ),
+                })
+            }
+        }
+    }
+
+    // Rewrites the tree in place, applying `f` to every node, children
+    // first so a rewrite sees its subterms already rewritten. A child
+    // `Rc` that is uniquely owned is mutated where it sits; a shared
+    // one is cloned first (`Rc::make_mut`), so an unshared tree is
+    // rewritten without allocating and sharing degrades to the usual
+    // clone-on-write. The traversal is raw, like `rename_free`: scopes
+    // are never reopened and bound occurrences keep their indices, so
+    // the callback must not add, drop or reorder binders — rewrites
+    // that change binding structure should rebuild with `Scope::new`
+    // instead.
+    pub fn walk_mut(&mut self, f: &mut impl FnMut(&mut Expr)) {
+        match self {
+            Expr::Var(_) | Expr::Lit(_) | Expr::Error(_) | Expr::Fail => {}
+            Expr::Lam(s) | Expr::LamRest(s) | Expr::Fix(s) => {
+                Rc::make_mut(&mut s.unsafe_body).walk_mut(f);
+            }
+            Expr::App(a, b)
+            | Expr::Apply(a, b)
+            | Expr::Bin(_, a, b)
+            | Expr::While(a, b)
+            | Expr::TryFinally(a, b)
+            | Expr::Amb(a, b) => {
+                Rc::make_mut(a).walk_mut(f);
+                Rc::make_mut(b).walk_mut(f);
+            }
+            Expr::Let(v, s) => {
+                Rc::make_mut(v).walk_mut(f);
+                Rc::make_mut(&mut s.unsafe_body).walk_mut(f);
+            }
+            Expr::Assert(e, _)
+            | Expr::Not(e)
+            | Expr::Cast(_, e)
+            | Expr::Delay(e)
+            | Expr::Force(e)
+            | Expr::Proj(_, e) => {
+                Rc::make_mut(e).walk_mut(f);
+            }
+            Expr::Tuple(es) => {
+                for e in es {
+                    Rc::make_mut(e).walk_mut(f);
+                }
+            }
+            Expr::If(c, t, e) => {
+                Rc::make_mut(c).walk_mut(f);
+                Rc::make_mut(t).walk_mut(f);
+                Rc::make_mut(e).walk_mut(f);
+            }
+            Expr::Cond(clauses, els) => {
+                for (test, body) in clauses {
+                    Rc::make_mut(test).walk_mut(f);
+                    Rc::make_mut(body).walk_mut(f);
+                }
+                Rc::make_mut(els).walk_mut(f);
+            }
+            Expr::LetRecMany(s) => {
+                let (defs, body) = &mut s.unsafe_body;
+                for d in defs {
+                    Rc::make_mut(d).walk_mut(f);
+                }
+                Rc::make_mut(body).walk_mut(f);
+            }
+        }
+        f(self);
+    }
+
+    // The binders this node itself introduces, outermost first — a thin
+    // accessor over the scope patterns, complementing `visit_vars` when
+    // debugging why a variable is or isn't captured. Nothing is recursed
+    // into: a non-binding node answers an empty list.
+    pub fn bound_vars(&self) -> Vec<FreeVar<String>> {
+        match self {
+            Expr::Lam(s) | Expr::LamRest(s) | Expr::Fix(s) => vec![s.unsafe_pattern.0.clone()],
+            Expr::Let(_, s) => vec![s.unsafe_pattern.0.clone()],
+            Expr::LetRecMany(s) => s.unsafe_pattern.iter().map(|b| b.0.clone()).collect(),
+            _ => Vec::new(),
+        }
+    }
+
+    #[cfg(feature = "pretty")]
     pub fn pretty<'a, D>(&'a self, allocator: &'a D) -> DocBuilder<'a, D, ColorSpec>
     where
         D: DocAllocator<'a, ColorSpec>,
         D::Doc: Clone,
     {
-        match self {
+        self.pretty_with(allocator, &PrettyConfig::default())
+    }
+
+    // As `pretty`, with the rendering options threaded through; see
+    // `render::PrettyConfig` for what each knob does.
+    #[cfg(feature = "pretty")]
+    pub fn pretty_with<'a, D>(
+        &'a self,
+        allocator: &'a D,
+        config: &PrettyConfig,
+    ) -> DocBuilder<'a, D, ColorSpec>
+    where
+        D: DocAllocator<'a, ColorSpec>,
+        D::Doc: Clone,
+    {
+        grow_stack(|| match self {
             Expr::Var(s) => allocator.as_string(s),
-            Expr::Lit(Ignore(l)) => l.pretty(allocator),
+            Expr::Error(Ignore(msg)) => allocator
+                .text("error")
+                .annotate(ColorSpec::new().set_fg(Some(Color::Red)).clone())
+                .append(allocator.space())
+                .append(allocator.text(format!("{:?}", msg)))
+                .parens(),
+            Expr::Lit(Ignore(l)) => l.pretty_with(allocator, config),
             Expr::Lam(s) => {
                 let Scope {
                     unsafe_pattern: pat,
@@ -37,8 +454,33 @@ impl Expr {
                     .parens();
                 let body_pret = allocator
                     .line_()
-                    .append(body.pretty(allocator))
-                    .nest(1)
+                    .append(body.pretty_with(allocator, config))
+                    .nest(config.nest_offset())
+                    .group();
+
+                allocator
+                    .text("lambda")
+                    .annotate(ColorSpec::new().set_fg(Some(Color::Magenta)).clone())
+                    .append(allocator.space())
+                    .append(pat_pret)
+                    .append(allocator.space())
+                    .append(body_pret)
+                    .parens()
+            }
+            Expr::LamRest(s) => {
+                let Scope {
+                    unsafe_pattern: pat,
+                    unsafe_body: body,
+                } = &s;
+
+                let pat_pret = allocator
+                    .text(format!("&{}", pat))
+                    .annotate(ColorSpec::new().set_fg(Some(Color::Green)).clone())
+                    .parens();
+                let body_pret = allocator
+                    .line_()
+                    .append(body.pretty_with(allocator, config))
+                    .nest(config.nest_offset())
                     .group();
 
                 allocator
@@ -50,9 +492,262 @@ impl Expr {
                     .append(body_pret)
                     .parens()
             }
+            Expr::Let(v, s) => {
+                let Scope {
+                    unsafe_pattern: pat,
+                    unsafe_body: body,
+                } = &s;
+
+                let pat_pret = allocator
+                    .as_string(pat)
+                    .annotate(ColorSpec::new().set_fg(Some(Color::Green)).clone());
+                let v_pret = v.pretty_with(allocator, config);
+                let body_pret = allocator
+                    .line_()
+                    .append(body.pretty_with(allocator, config))
+                    .nest(config.nest_offset())
+                    .group();
+
+                allocator
+                    .text("let")
+                    .annotate(ColorSpec::new().set_fg(Some(Color::Magenta)).clone())
+                    .append(allocator.space())
+                    .append(pat_pret)
+                    .append(allocator.space())
+                    .append(v_pret)
+                    .append(allocator.space())
+                    .append(body_pret)
+                    .parens()
+            }
+            Expr::Assert(c, Ignore(msg)) => {
+                let c_pret = c.pretty_with(allocator, config);
+
+                allocator
+                    .text("assert")
+                    .annotate(ColorSpec::new().set_fg(Some(Color::Magenta)).clone())
+                    .append(allocator.space())
+                    .append(c_pret)
+                    .append(allocator.space())
+                    .append(allocator.text(format!("{:?}", msg)))
+                    .parens()
+            }
+            Expr::Bin(Ignore(op), a, b) => {
+                let a_pret = a.pretty_with(allocator, config);
+                let b_pret = b.pretty_with(allocator, config);
+
+                allocator
+                    .as_string(op)
+                    .annotate(ColorSpec::new().set_fg(Some(Color::Cyan)).clone())
+                    .append(allocator.space())
+                    .append(a_pret)
+                    .append(allocator.space())
+                    .append(b_pret)
+                    .parens()
+            }
+            Expr::Not(e) => {
+                let e_pret = e.pretty_with(allocator, config);
+
+                allocator
+                    .text("not")
+                    .annotate(ColorSpec::new().set_fg(Some(Color::Magenta)).clone())
+                    .append(allocator.space())
+                    .append(e_pret)
+                    .parens()
+            }
+            Expr::Cast(Ignore(kind), e) => {
+                let e_pret = e.pretty_with(allocator, config);
+
+                allocator
+                    .text("cast")
+                    .annotate(ColorSpec::new().set_fg(Some(Color::Magenta)).clone())
+                    .append(allocator.space())
+                    .append(allocator.as_string(kind))
+                    .append(allocator.space())
+                    .append(e_pret)
+                    .parens()
+            }
+            Expr::Delay(e) => {
+                let e_pret = e.pretty_with(allocator, config);
+
+                allocator
+                    .text("delay")
+                    .annotate(ColorSpec::new().set_fg(Some(Color::Magenta)).clone())
+                    .append(allocator.space())
+                    .append(e_pret)
+                    .parens()
+            }
+            Expr::Force(e) => {
+                let e_pret = e.pretty_with(allocator, config);
+
+                allocator
+                    .text("force")
+                    .annotate(ColorSpec::new().set_fg(Some(Color::Magenta)).clone())
+                    .append(allocator.space())
+                    .append(e_pret)
+                    .parens()
+            }
+            Expr::Tuple(es) => {
+                let es_pret = allocator.intersperse(
+                    es.iter().map(|e| e.pretty_with(allocator, config)),
+                    allocator.space(),
+                );
+
+                allocator
+                    .text("tuple")
+                    .annotate(ColorSpec::new().set_fg(Some(Color::Magenta)).clone())
+                    .append(allocator.space())
+                    .append(es_pret)
+                    .parens()
+            }
+            Expr::Proj(Ignore(i), e) => {
+                let e_pret = e.pretty_with(allocator, config);
+
+                allocator
+                    .text("proj")
+                    .annotate(ColorSpec::new().set_fg(Some(Color::Magenta)).clone())
+                    .append(allocator.space())
+                    .append(allocator.as_string(i))
+                    .append(allocator.space())
+                    .append(e_pret)
+                    .parens()
+            }
+            Expr::If(c, t, e) => {
+                let c_pret = c.pretty_with(allocator, config);
+                let t_pret = t.pretty_with(allocator, config);
+                let e_pret = e.pretty_with(allocator, config);
+
+                allocator
+                    .text("if")
+                    .annotate(ColorSpec::new().set_fg(Some(Color::Magenta)).clone())
+                    .append(allocator.space())
+                    .append(c_pret)
+                    .append(allocator.space())
+                    .append(t_pret)
+                    .append(allocator.space())
+                    .append(e_pret)
+                    .parens()
+            }
+            Expr::Cond(clauses, els) => {
+                let clauses_pret = allocator.intersperse(
+                    clauses.iter().map(|(test, body)| {
+                        test.pretty_with(allocator, config)
+                            .append(allocator.space())
+                            .append(body.pretty_with(allocator, config))
+                            .parens()
+                    }),
+                    allocator.space(),
+                );
+
+                allocator
+                    .text("cond")
+                    .annotate(ColorSpec::new().set_fg(Some(Color::Magenta)).clone())
+                    .append(allocator.space())
+                    .append(clauses_pret)
+                    .append(allocator.space())
+                    .append(els.pretty_with(allocator, config))
+                    .parens()
+            }
+            Expr::While(c, b) => {
+                let c_pret = c.pretty_with(allocator, config);
+                let b_pret = b.pretty_with(allocator, config);
+
+                allocator
+                    .text("while")
+                    .annotate(ColorSpec::new().set_fg(Some(Color::Magenta)).clone())
+                    .append(allocator.space())
+                    .append(c_pret)
+                    .append(allocator.space())
+                    .append(b_pret)
+                    .parens()
+            }
+            Expr::TryFinally(b, cl) => {
+                let b_pret = b.pretty_with(allocator, config);
+                let cl_pret = cl.pretty_with(allocator, config);
+
+                allocator
+                    .text("try-finally")
+                    .annotate(ColorSpec::new().set_fg(Some(Color::Magenta)).clone())
+                    .append(allocator.space())
+                    .append(b_pret)
+                    .append(allocator.space())
+                    .append(cl_pret)
+                    .parens()
+            }
+            Expr::Amb(a, b) => {
+                let a_pret = a.pretty_with(allocator, config);
+                let b_pret = b.pretty_with(allocator, config);
+
+                allocator
+                    .text("amb")
+                    .annotate(ColorSpec::new().set_fg(Some(Color::Magenta)).clone())
+                    .append(allocator.space())
+                    .append(a_pret)
+                    .append(allocator.space())
+                    .append(b_pret)
+                    .parens()
+            }
+            Expr::Fail => allocator
+                .text("fail")
+                .annotate(ColorSpec::new().set_fg(Some(Color::Magenta)).clone()),
+            Expr::LetRecMany(s) => {
+                let Scope {
+                    unsafe_pattern: pats,
+                    unsafe_body: (defs, body),
+                } = &s;
+
+                let defs_pret = allocator.intersperse(
+                    pats.iter().zip(defs).map(|(pat, def)| {
+                        allocator
+                            .as_string(pat)
+                            .annotate(ColorSpec::new().set_fg(Some(Color::Green)).clone())
+                            .append(allocator.space())
+                            .append(def.pretty_with(allocator, config))
+                            .parens()
+                    }),
+                    allocator.space(),
+                );
+                let body_pret = allocator
+                    .line_()
+                    .append(body.pretty_with(allocator, config))
+                    .nest(config.nest_offset())
+                    .group();
+
+                allocator
+                    .text("letrec")
+                    .annotate(ColorSpec::new().set_fg(Some(Color::Magenta)).clone())
+                    .append(allocator.space())
+                    .append(defs_pret.parens())
+                    .append(allocator.space())
+                    .append(body_pret)
+                    .parens()
+            }
+            Expr::Fix(s) => {
+                let Scope {
+                    unsafe_pattern: pat,
+                    unsafe_body: body,
+                } = &s;
+
+                let pat_pret = allocator
+                    .as_string(pat)
+                    .annotate(ColorSpec::new().set_fg(Some(Color::Green)).clone());
+                let body_pret = allocator
+                    .line_()
+                    .append(body.pretty_with(allocator, config))
+                    .nest(config.nest_offset())
+                    .group();
+
+                allocator
+                    .text("fix")
+                    .annotate(ColorSpec::new().set_fg(Some(Color::Magenta)).clone())
+                    .append(allocator.space())
+                    .append(pat_pret)
+                    .append(allocator.space())
+                    .append(body_pret)
+                    .parens()
+            }
             Expr::App(f, v) => {
-                let f_pret = f.pretty(allocator);
-                let v_pret = v.pretty(allocator);
+                let f_pret = f.pretty_with(allocator, config);
+                let v_pret = v.pretty_with(allocator, config);
 
                 f_pret
                     .annotate(ColorSpec::new().set_fg(Some(Color::Blue)).clone())
@@ -60,14 +755,216 @@ impl Expr {
                     .append(v_pret)
                     .parens()
             }
-        }
+            Expr::Apply(f, l) => {
+                let f_pret = f.pretty_with(allocator, config);
+                let l_pret = l.pretty_with(allocator, config);
+
+                allocator
+                    .text("apply")
+                    .annotate(ColorSpec::new().set_fg(Some(Color::Magenta)).clone())
+                    .append(allocator.space())
+                    .append(f_pret)
+                    .append(allocator.space())
+                    .append(l_pret)
+                    .parens()
+            }
+        })
     }
 
+    #[cfg(feature = "pretty")]
     pub fn pretty_print(&self, out: impl WriteColor) -> Result<()> {
-        let allocator = BoxAllocator;
+        self.pretty_print_width(70, out)
+    }
+
+    // Renders wrapped to `width` columns; a width of 0 means no wrapping,
+    // producing a single line. Callers wanting terminal-width output can
+    // pass the result of a terminal-size query here directly.
+    #[cfg(feature = "pretty")]
+    pub fn pretty_print_width(&self, width: usize, out: impl WriteColor) -> Result<()> {
+        self.pretty_print_conf(
+            &PrettyConfig {
+                width,
+                ..PrettyConfig::default()
+            },
+            out,
+        )
+    }
+
+    // Renders the whole term on one line, breaks replaced by spaces —
+    // handy when the output is destined for a log and needs to stay
+    // greppable. Shorthand for `pretty_print_width(0, out)`.
+    #[cfg(feature = "pretty")]
+    pub fn pretty_print_oneline(&self, out: impl WriteColor) -> Result<()> {
+        self.pretty_print_width(0, out)
+    }
+
+    // Renders with the full set of options in `config`.
+    #[cfg(feature = "pretty")]
+    pub fn pretty_print_conf(&self, config: &PrettyConfig, out: impl WriteColor) -> Result<()> {
+        let allocator = Arena::new();
+        let doc = self.pretty_with(&allocator, config).1;
 
-        self.pretty(&allocator).1.render_colored(70, out)?;
+        let mut out = crate::render::IndentWriter::new(out, config.indent);
+        if config.color && out.supports_color() {
+            doc.render_colored(config.render_width(), out)?;
+        } else {
+            doc.render(config.render_width(), &mut out)?;
+        }
 
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::prelude::{app, fresh, lam, lit, var};
+
+    // the source language renders with the same scheme as the CPS side:
+    // magenta keywords, green binders, blue call heads
+    #[cfg(feature = "pretty")]
+    #[test]
+    fn source_lambdas_render_like_the_cps_side() {
+        let x = fresh("x");
+        let term = app(lam(x.clone(), var(&x)), lit(Literal::Int(1)));
+
+        let mut buf = termcolor::Buffer::no_color();
+        term.pretty_print(&mut buf).unwrap();
+        let out = String::from_utf8(buf.into_inner()).unwrap();
+
+        // binders carry their unique id (`x$N`), bound occurrences their
+        // de Bruijn position, so only pin the stable parts
+        assert!(out.starts_with("((lambda (x$"), "got {:?}", out);
+        assert!(out.ends_with(") x@0.0) 1)"), "got {:?}", out);
+    }
+
+    #[test]
+    fn renaming_frees_leaves_bound_namesakes_alone() {
+        let x = fresh("x");
+        let y = fresh("y");
+
+        // (λx. x) x: the argument is free, the lambda body's x is bound
+        let term = app(lam(x.clone(), var(&x)), var(&x));
+
+        let mapping = HashMap::from([(x.clone(), y.clone())]);
+        let renamed = term.rename_free(&mapping);
+
+        let expected = app(lam(x.clone(), var(&x)), var(&y));
+        assert!(Expr::term_eq(&renamed, &expected));
+    }
+
+    #[test]
+    fn map_literals_doubles_every_int() {
+        let x = fresh("x");
+        let add_three = |body| Expr::Bin(Ignore(BinOp::Add), Rc::new(body), Rc::new(lit(Literal::Int(3))));
+        let term = app(lam(x.clone(), add_three(var(&x))), lit(Literal::Int(4)));
+
+        let doubled = term.map_literals(|l| match l {
+            Literal::Int(i) => Literal::Int(i * 2),
+            l => l.clone(),
+        });
+
+        let expected = app(
+            lam(
+                x.clone(),
+                Expr::Bin(
+                    Ignore(BinOp::Add),
+                    Rc::new(var(&x)),
+                    Rc::new(lit(Literal::Int(6))),
+                ),
+            ),
+            lit(Literal::Int(8)),
+        );
+        assert!(Expr::term_eq(&doubled, &expected));
+    }
+
+    #[test]
+    fn walk_mut_rewrites_an_unshared_tree_in_place() {
+        let x = fresh("x");
+        let mut term = app(
+            lam(x.clone(), app(var(&x), lit(Literal::Int(1)))),
+            lit(Literal::Int(4)),
+        );
+
+        // the nodes the rewrite should reuse rather than reallocate
+        let (lam_ptr, arg_ptr) = match &term {
+            Expr::App(f, e) => (Rc::as_ptr(f), Rc::as_ptr(e)),
+            term => panic!("expected an application, got {:?}", term),
+        };
+
+        term.walk_mut(&mut |e| {
+            if let Expr::Lit(Ignore(Literal::Int(n))) = e {
+                *n *= 2;
+            }
+        });
+
+        // same allocations, new payloads — including under the binder
+        match &term {
+            Expr::App(f, e) => {
+                assert_eq!(Rc::as_ptr(f), lam_ptr);
+                assert_eq!(Rc::as_ptr(e), arg_ptr);
+            }
+            term => panic!("expected an application, got {:?}", term),
+        }
+        let expected = app(
+            lam(x.clone(), app(var(&x), lit(Literal::Int(2)))),
+            lit(Literal::Int(8)),
+        );
+        assert!(Expr::term_eq(&term, &expected));
+    }
+
+    #[test]
+    fn walk_mut_leaves_a_shared_subtree_alone() {
+        let shared = Rc::new(lit(Literal::Int(1)));
+        let mut term = Expr::App(shared.clone(), shared.clone());
+
+        term.walk_mut(&mut |e| {
+            if let Expr::Lit(Ignore(Literal::Int(n))) = e {
+                *n += 1;
+            }
+        });
+
+        // the shared handle saw nothing; the tree got rewritten copies
+        assert!(Expr::term_eq(&shared, &lit(Literal::Int(1))));
+        let expected = app(lit(Literal::Int(2)), lit(Literal::Int(2)));
+        assert!(Expr::term_eq(&term, &expected));
+    }
+
+    #[test]
+    fn size_hint_tracks_the_lowered_size() {
+        use crate::cont_expr::{t_k, KExpr};
+        use crate::flat_expr::FExpr;
+        use crate::prelude::{church_num, compose, identity};
+        use moniker::Var;
+        use std::rc::Rc;
+
+        fn fexpr_size(f: &FExpr) -> usize {
+            match f {
+                FExpr::LamOne(s) | FExpr::Fix(s) => 1 + fexpr_size(&s.unsafe_body),
+                FExpr::LamTwo(s) => 1 + fexpr_size(&s.unsafe_body.unsafe_body),
+                FExpr::Var(_) | FExpr::Lit(_) | FExpr::Prim(_) => 1,
+                FExpr::CallOne(a, b) => 1 + fexpr_size(a) + fexpr_size(b),
+                FExpr::CallTwo(a, b, c) | FExpr::If(a, b, c) => {
+                    1 + fexpr_size(a) + fexpr_size(b) + fexpr_size(c)
+                }
+            }
+        }
+
+        // exact on a term small enough to count by hand
+        let small = app(identity(), lit(Literal::Int(1)));
+        assert_eq!(small.size_hint(), 4);
+
+        for sample in [small, compose(), church_num(5), app(compose(), church_num(3))] {
+            let hint = sample.size_hint();
+            let halt = FreeVar::fresh_named("halt");
+            let lowered = fexpr_size(
+                &t_k(sample, Rc::new(KExpr::Var(Var::Free(halt)))).into_fexpr(),
+            );
+
+            // the lowering only ever grows the term, and by a bounded
+            // constant factor
+            assert!(hint <= lowered, "hint {} above lowered {}", hint, lowered);
+            assert!(lowered <= hint * 10, "hint {} far below lowered {}", hint, lowered);
+        }
+    }
+}
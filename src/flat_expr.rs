@@ -1,31 +1,79 @@
 use moniker::BoundTerm;
 use moniker::{Binder, Ignore, Scope, Var};
 
-use pretty::{BoxAllocator, DocAllocator, DocBuilder};
+#[cfg(feature = "pretty")]
+use pretty::{Arena, DocAllocator, DocBuilder};
+#[cfg(feature = "pretty")]
 use termcolor::{Color, ColorSpec, WriteColor};
 
-use std::{io::Result, rc::Rc};
+#[cfg(feature = "pretty")]
+use std::io::Result;
+use std::rc::Rc;
 
+use crate::cont_expr::PrimOp;
 use crate::literals::Literal;
+#[cfg(feature = "pretty")]
+use crate::render::PrettyConfig;
 use crate::utils::clone_rc;
+use crate::utils::grow_stack;
+
+use moniker::FreeVar;
+use std::collections::{HashMap, HashSet};
+use std::convert::TryFrom;
+use std::hash::{Hash, Hasher};
+
+use crate::expr::Expr;
 
 #[derive(Debug, Clone, BoundTerm)]
 pub enum FExpr {
     LamOne(Scope<Binder<String>, Rc<FExpr>>),
     LamTwo(Scope<Binder<String>, Scope<Binder<String>, Rc<FExpr>>>),
+    Fix(Scope<Binder<String>, Rc<FExpr>>),
     Var(Var<String>),
     Lit(Ignore<Literal>),
+    Prim(Ignore<PrimOp>),
     CallOne(Rc<FExpr>, Rc<FExpr>),
     CallTwo(Rc<FExpr>, Rc<FExpr>, Rc<FExpr>),
+    If(Rc<FExpr>, Rc<FExpr>, Rc<FExpr>),
 }
 
 impl FExpr {
+    // The binders this node itself introduces, outermost first — user
+    // parameter, then continuation parameter for a `LamTwo`. A thin
+    // accessor over the scope patterns, as `Expr::bound_vars`.
+    pub fn bound_vars(&self) -> Vec<FreeVar<String>> {
+        match self {
+            FExpr::LamOne(s) | FExpr::Fix(s) => vec![s.unsafe_pattern.0.clone()],
+            FExpr::LamTwo(s) => vec![
+                s.unsafe_pattern.0.clone(),
+                s.unsafe_body.unsafe_pattern.0.clone(),
+            ],
+            _ => Vec::new(),
+        }
+    }
+
+    #[cfg(feature = "pretty")]
     pub fn pretty<'a, D>(&'a self, allocator: &'a D) -> DocBuilder<'a, D, ColorSpec>
     where
         D: DocAllocator<'a, ColorSpec>,
         D::Doc: Clone,
     {
-        match self {
+        self.pretty_with(allocator, &PrettyConfig::default())
+    }
+
+    // As `pretty`, with the rendering options threaded through; see
+    // `render::PrettyConfig` for what each knob does.
+    #[cfg(feature = "pretty")]
+    pub fn pretty_with<'a, D>(
+        &'a self,
+        allocator: &'a D,
+        config: &PrettyConfig,
+    ) -> DocBuilder<'a, D, ColorSpec>
+    where
+        D: DocAllocator<'a, ColorSpec>,
+        D::Doc: Clone,
+    {
+        grow_stack(|| match self {
             FExpr::LamOne(s) => {
                 let Scope {
                     unsafe_pattern: pat,
@@ -38,8 +86,8 @@ impl FExpr {
                     .parens();
                 let body_pret = allocator
                     .line_()
-                    .append(body.pretty(allocator))
-                    .nest(1)
+                    .append(body.pretty_with(allocator, config))
+                    .nest(config.nest_offset())
                     .group();
 
                 allocator
@@ -73,8 +121,8 @@ impl FExpr {
                     .parens();
                 let body_pret = allocator
                     .line_()
-                    .append(body.pretty(allocator))
-                    .nest(1)
+                    .append(body.pretty_with(allocator, config))
+                    .nest(config.nest_offset())
                     .group();
 
                 allocator
@@ -86,11 +134,38 @@ impl FExpr {
                     .append(body_pret)
                     .parens()
             }
+            FExpr::Fix(s) => {
+                let Scope {
+                    unsafe_pattern: pat,
+                    unsafe_body: body,
+                } = &s;
+
+                let pat_pret = allocator
+                    .as_string(pat)
+                    .annotate(ColorSpec::new().set_fg(Some(Color::Green)).clone());
+                let body_pret = allocator
+                    .line_()
+                    .append(body.pretty_with(allocator, config))
+                    .nest(config.nest_offset())
+                    .group();
+
+                allocator
+                    .text("fix")
+                    .annotate(ColorSpec::new().set_fg(Some(Color::Magenta)).clone())
+                    .append(allocator.space())
+                    .append(pat_pret)
+                    .append(allocator.space())
+                    .append(body_pret)
+                    .parens()
+            }
             FExpr::Var(s) => allocator.as_string(s),
-            FExpr::Lit(Ignore(l)) => l.pretty(allocator),
+            FExpr::Lit(Ignore(l)) => l.pretty_with(allocator, config),
+            FExpr::Prim(Ignore(p)) => allocator
+                .as_string(p)
+                .annotate(ColorSpec::new().set_fg(Some(Color::Cyan)).clone()),
             FExpr::CallOne(f, c) => {
-                let f_pret = f.pretty(allocator);
-                let c_pret = c.pretty(allocator);
+                let f_pret = f.pretty_with(allocator, config);
+                let c_pret = c.pretty_with(allocator, config);
 
                 f_pret
                     .annotate(ColorSpec::new().set_fg(Some(Color::Blue)).clone())
@@ -99,9 +174,9 @@ impl FExpr {
                     .parens()
             }
             FExpr::CallTwo(f, v, c) => {
-                let f_pret = f.pretty(allocator);
-                let v_pret = v.pretty(allocator);
-                let c_pret = c.pretty(allocator);
+                let f_pret = f.pretty_with(allocator, config);
+                let v_pret = v.pretty_with(allocator, config);
+                let c_pret = c.pretty_with(allocator, config);
 
                 f_pret
                     .annotate(ColorSpec::new().set_fg(Some(Color::Blue)).clone())
@@ -111,13 +186,63 @@ impl FExpr {
                     .append(c_pret)
                     .parens()
             }
-        }
+            FExpr::If(c, t, e) => {
+                let c_pret = c.pretty_with(allocator, config);
+                let t_pret = allocator.line_().append(t.pretty_with(allocator, config)).nest(config.nest_offset());
+                let e_pret = allocator.line_().append(e.pretty_with(allocator, config)).nest(config.nest_offset());
+
+                allocator
+                    .text("if")
+                    .annotate(ColorSpec::new().set_fg(Some(Color::Magenta)).clone())
+                    .append(allocator.space())
+                    .append(c_pret)
+                    .append(t_pret)
+                    .append(e_pret)
+                    .group()
+                    .parens()
+            }
+        })
     }
 
+    #[cfg(feature = "pretty")]
     pub fn pretty_print(&self, out: impl WriteColor) -> Result<()> {
-        let allocator = BoxAllocator;
+        self.pretty_print_width(70, out)
+    }
+
+    // Renders wrapped to `width` columns; a width of 0 means no wrapping,
+    // producing a single line. Callers wanting terminal-width output can
+    // pass the result of a terminal-size query here directly.
+    #[cfg(feature = "pretty")]
+    pub fn pretty_print_width(&self, width: usize, out: impl WriteColor) -> Result<()> {
+        self.pretty_print_conf(
+            &PrettyConfig {
+                width,
+                ..PrettyConfig::default()
+            },
+            out,
+        )
+    }
+
+    // Renders the whole term on one line, breaks replaced by spaces —
+    // handy when the output is destined for a log and needs to stay
+    // greppable. Shorthand for `pretty_print_width(0, out)`.
+    #[cfg(feature = "pretty")]
+    pub fn pretty_print_oneline(&self, out: impl WriteColor) -> Result<()> {
+        self.pretty_print_width(0, out)
+    }
 
-        self.pretty(&allocator).1.render_colored(70, out)?;
+    // Renders with the full set of options in `config`.
+    #[cfg(feature = "pretty")]
+    pub fn pretty_print_conf(&self, config: &PrettyConfig, out: impl WriteColor) -> Result<()> {
+        let allocator = Arena::new();
+        let doc = self.pretty_with(&allocator, config).1;
+
+        let mut out = crate::render::IndentWriter::new(out, config.indent);
+        if config.color && out.supports_color() {
+            doc.render_colored(config.render_width(), out)?;
+        } else {
+            doc.render(config.render_width(), &mut out)?;
+        }
 
         Ok(())
     }
@@ -158,6 +283,17 @@ impl FExpr {
                     },
                 })
             }
+            FExpr::Fix(s) => {
+                let Scope {
+                    unsafe_pattern: pat,
+                    unsafe_body: body,
+                } = s;
+
+                FExpr::Fix(Scope {
+                    unsafe_pattern: pat,
+                    unsafe_body: Rc::new(clone_rc(body).subst(name, rep)),
+                })
+            }
             FExpr::Var(v) => {
                 if name.eq(&v) {
                     rep
@@ -165,7 +301,7 @@ impl FExpr {
                     FExpr::Var(v)
                 }
             }
-            l @ FExpr::Lit(_) => l,
+            l @ (FExpr::Lit(_) | FExpr::Prim(_)) => l,
             FExpr::CallOne(f, v) => FExpr::CallOne(
                 Rc::new(clone_rc(f).subst(name, rep.clone())),
                 Rc::new(clone_rc(v).subst(name, rep)),
@@ -175,6 +311,445 @@ impl FExpr {
                 Rc::new(clone_rc(v).subst(name, rep.clone())),
                 Rc::new(clone_rc(c).subst(name, rep.clone())),
             ),
+            FExpr::If(c, t, e) => FExpr::If(
+                Rc::new(clone_rc(c).subst(name, rep.clone())),
+                Rc::new(clone_rc(t).subst(name, rep.clone())),
+                Rc::new(clone_rc(e).subst(name, rep.clone())),
+            ),
+        }
+    }
+}
+
+// Wraps an `FExpr` for use as a map key, e.g. a content-addressed
+// cache of flattened programs. Equality is alpha-equivalence refined
+// with the literal and primitive payloads (`term_eq` alone ignores
+// everything under `Ignore`); hashing agrees with it by walking the
+// closed term, so binder names and identities never enter the hash.
+#[derive(Debug, Clone)]
+pub struct FExprKey(pub FExpr);
+
+impl PartialEq for FExprKey {
+    fn eq(&self, other: &FExprKey) -> bool {
+        key_eq(&self.0, &other.0)
+    }
+}
+
+impl Eq for FExprKey {}
+
+impl Hash for FExprKey {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        key_hash(&self.0, state)
+    }
+}
+
+// The raw traversal leaves scopes closed: bound occurrences are de
+// Bruijn-style indices, so comparing bodies directly is comparison up
+// to alpha, and free variables compare by identity as `term_eq` would.
+fn key_eq(a: &FExpr, b: &FExpr) -> bool {
+    grow_stack(|| match (a, b) {
+        (FExpr::LamOne(a), FExpr::LamOne(b)) | (FExpr::Fix(a), FExpr::Fix(b)) => {
+            key_eq(&a.unsafe_body, &b.unsafe_body)
+        }
+        (FExpr::LamTwo(a), FExpr::LamTwo(b)) => {
+            key_eq(&a.unsafe_body.unsafe_body, &b.unsafe_body.unsafe_body)
+        }
+        // `BoundVar` equality already ignores pretty names
+        (FExpr::Var(a), FExpr::Var(b)) => a == b,
+        (FExpr::Lit(Ignore(a)), FExpr::Lit(Ignore(b))) => a == b,
+        (FExpr::Prim(Ignore(a)), FExpr::Prim(Ignore(b))) => a == b,
+        (FExpr::CallOne(af, aa), FExpr::CallOne(bf, ba)) => key_eq(af, bf) && key_eq(aa, ba),
+        (FExpr::CallTwo(af, aa, ak), FExpr::CallTwo(bf, ba, bk)) => {
+            key_eq(af, bf) && key_eq(aa, ba) && key_eq(ak, bk)
+        }
+        (FExpr::If(ac, at, af), FExpr::If(bc, bt, bf)) => {
+            key_eq(ac, bc) && key_eq(at, bt) && key_eq(af, bf)
+        }
+        _ => false,
+    })
+}
+
+fn key_hash<H: Hasher>(f: &FExpr, state: &mut H) {
+    grow_stack(|| {
+        std::mem::discriminant(f).hash(state);
+        match f {
+            FExpr::LamOne(s) | FExpr::Fix(s) => key_hash(&s.unsafe_body, state),
+            FExpr::LamTwo(s) => key_hash(&s.unsafe_body.unsafe_body, state),
+            FExpr::Var(Var::Free(v)) => v.hash(state),
+            FExpr::Var(Var::Bound(bv)) => {
+                (bv.scope, bv.binder).hash(state);
+            }
+            FExpr::Lit(Ignore(l)) => l.hash(state),
+            FExpr::Prim(Ignore(p)) => p.hash(state),
+            FExpr::CallOne(f, a) => {
+                key_hash(f, state);
+                key_hash(a, state);
+            }
+            FExpr::CallTwo(f, a, k) => {
+                key_hash(f, state);
+                key_hash(a, state);
+                key_hash(k, state);
+            }
+            FExpr::If(c, t, e) => {
+                key_hash(c, state);
+                key_hash(t, state);
+                key_hash(e, state);
+            }
+        }
+    })
+}
+
+
+// Why an `FExpr` has no direct-style reading.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DirectStyleError {
+    // a continuation escaped into value position, so the control flow
+    // needs first-class continuations to express
+    EssentialContinuation,
+    // a one-argument lambda (a continuation) used as an ordinary value
+    ContinuationValue,
+    // a primitive outside the application shape the lowering produces
+    StrayPrim,
+    // a call or branch where only a value can appear
+    CallInValuePosition,
+    // a value where a call was expected; flattened bodies always end in
+    // a call
+    ValueInCallPosition,
+}
+
+impl std::fmt::Display for DirectStyleError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DirectStyleError::EssentialContinuation => {
+                write!(f, "a continuation is used as a first-class value")
+            }
+            DirectStyleError::ContinuationValue => {
+                write!(f, "a continuation lambda appears in value position")
+            }
+            DirectStyleError::StrayPrim => {
+                write!(f, "a primitive appears outside its application shape")
+            }
+            DirectStyleError::CallInValuePosition => {
+                write!(f, "a call appears in value position")
+            }
+            DirectStyleError::ValueInCallPosition => {
+                write!(f, "a value appears where a call was expected")
+            }
+        }
+    }
+}
+
+impl std::error::Error for DirectStyleError {}
+
+// The fallible inverse of the flattening: reads an `FExpr` back as a
+// direct-style `Expr` when it matches the image of the lowering —
+// lambdas whose continuation parameter is only ever invoked, and calls
+// whose continuation is either the current one (a tail call) or a
+// one-argument lambda naming an intermediate result (a let, read back
+// as an immediately applied lambda). The top of the term answers to a
+// single free continuation, discovered at the first tail. Anything that
+// treats a continuation as a first-class value is rejected.
+impl TryFrom<FExpr> for Expr {
+    type Error = DirectStyleError;
+
+    fn try_from(f: FExpr) -> std::result::Result<Expr, DirectStyleError> {
+        let mut reader = DirectReader {
+            conts: HashSet::new(),
+            top: None,
+            env: HashMap::new(),
+        };
+
+        match &f {
+            FExpr::CallOne(..) | FExpr::CallTwo(..) | FExpr::If(..) => reader.body(&f, None),
+            v => reader.value(v),
+        }
+    }
+}
+
+// Working state for the back-reading: every continuation binder in
+// scope, and the free continuation the whole term answers to (fixed by
+// the first top-level tail we reach).
+struct DirectReader {
+    conts: HashSet<FreeVar<String>>,
+    top: Option<FreeVar<String>>,
+    // administrative let bindings whose right-hand side was a value;
+    // inlined at their uses so the read-back recovers the source shape
+    env: HashMap<FreeVar<String>, Expr>,
+}
+
+impl DirectReader {
+    // Is `kv` the continuation that body position `k` returns through?
+    // With no enclosing lambda (`k` is `None`) any free non-continuation
+    // variable is accepted as the top continuation the first time and
+    // pinned thereafter.
+    fn is_current(&mut self, kv: &FreeVar<String>, k: Option<&FreeVar<String>>) -> bool {
+        match k {
+            Some(k) => kv == k,
+            None => {
+                if self.conts.contains(kv) {
+                    return false;
+                }
+                match &self.top {
+                    Some(t) => kv == t,
+                    None => {
+                        self.top = Some(kv.clone());
+                        true
+                    }
+                }
+            }
+        }
+    }
+
+    // A value position: variables, literals, and lambdas. Meeting a
+    // continuation here means the term captures its continuation.
+    fn value(&mut self, f: &FExpr) -> std::result::Result<Expr, DirectStyleError> {
+        grow_stack(|| match f {
+            FExpr::LamTwo(s) => {
+                let (Binder(x), inner) = s.clone().unbind();
+                let (Binder(k), body) = inner.unbind();
+                self.conts.insert(k.clone());
+                let body = self.body(&body, Some(&k))?;
+                Ok(Expr::Lam(Scope::new(Binder(x), Rc::new(body))))
+            }
+            FExpr::Fix(s) => {
+                let (Binder(fv), body) = s.clone().unbind();
+                let body = self.value(&body)?;
+                Ok(Expr::Fix(Scope::new(Binder(fv), Rc::new(body))))
+            }
+            FExpr::Var(Var::Free(v)) if self.conts.contains(v) => {
+                Err(DirectStyleError::EssentialContinuation)
+            }
+            FExpr::Var(Var::Free(v)) if self.env.contains_key(v) => Ok(self.env[v].clone()),
+            FExpr::Var(v) => Ok(Expr::Var(v.clone())),
+            FExpr::Lit(Ignore(l)) => Ok(Expr::Lit(Ignore(l.clone()))),
+            FExpr::LamOne(_) => Err(DirectStyleError::ContinuationValue),
+            FExpr::Prim(_) => Err(DirectStyleError::StrayPrim),
+            FExpr::CallOne(..) | FExpr::CallTwo(..) | FExpr::If(..) => {
+                Err(DirectStyleError::CallInValuePosition)
+            }
+        })
+    }
+
+    // A body position: a call whose result flows to the continuation
+    // `k` (or the top continuation when `k` is `None`).
+    fn body(
+        &mut self,
+        f: &FExpr,
+        k: Option<&FreeVar<String>>,
+    ) -> std::result::Result<Expr, DirectStyleError> {
+        grow_stack(|| match f {
+            // `k v` returns the value
+            FExpr::CallOne(kf, v) => match &**kf {
+                FExpr::Var(Var::Free(kv)) if self.is_current(kv, k) => self.value(v),
+                // `(λr. rest) v` is a let on a value. t_k's
+                // administrative bindings name each intermediate result
+                // exactly once, so those are inlined to recover the
+                // source shape; a variable used more than once (or not
+                // at all) can only come from a source-level `let`, so
+                // the binding is kept as one
+                FExpr::LamOne(s) => {
+                    let (Binder(r), rest) = s.clone().unbind();
+                    let bound = self.value(v)?;
+
+                    let mut uses = 0;
+                    rest.visit_vars(&mut |var| {
+                        if matches!(var, Var::Free(fv) if *fv == r) {
+                            uses += 1;
+                        }
+                    });
+
+                    if uses == 1 {
+                        self.env.insert(r, bound);
+                        self.body(&rest, k)
+                    } else {
+                        let body = self.body(&rest, k)?;
+                        Ok(Expr::Let(Rc::new(bound), Scope::new(Binder(r), Rc::new(body))))
+                    }
+                }
+                _ => Err(DirectStyleError::EssentialContinuation),
+            },
+            FExpr::CallTwo(ff, v, c) => match &**ff {
+                FExpr::Prim(Ignore(PrimOp::Assert(msg))) => {
+                    let cond = self.value(v)?;
+                    self.finish(Expr::Assert(Rc::new(cond), Ignore(msg.clone())), c, k)
+                }
+                FExpr::Prim(Ignore(PrimOp::Not)) => {
+                    let cond = self.value(v)?;
+                    self.finish(Expr::Not(Rc::new(cond)), c, k)
+                }
+                FExpr::Prim(Ignore(PrimOp::Cast(kind))) => {
+                    let operand = self.value(v)?;
+                    self.finish(Expr::Cast(Ignore(*kind), Rc::new(operand)), c, k)
+                }
+                FExpr::Prim(Ignore(PrimOp::Delay)) => {
+                    // the argument is the lambda lowering packed the
+                    // delayed body into; unwrap it back to the body
+                    match self.value(v)? {
+                        Expr::Lam(s) => {
+                            let (_, body) = s.unbind();
+                            self.finish(Expr::Delay(body), c, k)
+                        }
+                        _ => Err(DirectStyleError::EssentialContinuation),
+                    }
+                }
+                FExpr::Prim(Ignore(PrimOp::Force)) => {
+                    let operand = self.value(v)?;
+                    self.finish(Expr::Force(Rc::new(operand)), c, k)
+                }
+                FExpr::Prim(Ignore(PrimOp::BinaryWith(op, l))) => {
+                    let lhs = self.value(v)?;
+                    self.finish(
+                        Expr::Bin(
+                            Ignore(*op),
+                            Rc::new(lhs),
+                            Rc::new(Expr::Lit(Ignore(l.clone()))),
+                        ),
+                        c,
+                        k,
+                    )
+                }
+                // a curried binary: the partial application comes back
+                // and is immediately fed the second operand
+                FExpr::Prim(Ignore(PrimOp::Binary(op))) => match &**c {
+                    FExpr::LamOne(s) => {
+                        let (Binder(p), rest) = s.clone().unbind();
+                        match &*rest {
+                            FExpr::CallTwo(pf, b, c2)
+                                if matches!(&**pf, FExpr::Var(Var::Free(pv)) if *pv == p) =>
+                            {
+                                let lhs = self.value(v)?;
+                                let rhs = self.value(b)?;
+                                self.finish(
+                                    Expr::Bin(Ignore(*op), Rc::new(lhs), Rc::new(rhs)),
+                                    c2,
+                                    k,
+                                )
+                            }
+                            _ => Err(DirectStyleError::StrayPrim),
+                        }
+                    }
+                    _ => Err(DirectStyleError::StrayPrim),
+                },
+                FExpr::Prim(_) => Err(DirectStyleError::StrayPrim),
+                _ => {
+                    let func = self.value(ff)?;
+                    let arg = self.value(v)?;
+                    self.finish(Expr::App(Rc::new(func), Rc::new(arg)), c, k)
+                }
+            },
+            FExpr::If(c, t, e) => Ok(Expr::If(
+                Rc::new(self.value(c)?),
+                Rc::new(self.body(t, k)?),
+                Rc::new(self.body(e, k)?),
+            )),
+            _ => Err(DirectStyleError::ValueInCallPosition),
+        })
+    }
+
+    // Routes an already-read expression through its continuation:
+    // straight out when the continuation is the current one, through a
+    // let when it names the result first.
+    fn finish(
+        &mut self,
+        e: Expr,
+        c: &FExpr,
+        k: Option<&FreeVar<String>>,
+    ) -> std::result::Result<Expr, DirectStyleError> {
+        match c {
+            FExpr::Var(Var::Free(kv)) if self.is_current(kv, k) => Ok(e),
+            FExpr::LamOne(s) => {
+                let (Binder(r), rest) = s.clone().unbind();
+                let rest = self.body(&rest, k)?;
+                Ok(Expr::App(
+                    Rc::new(Expr::Lam(Scope::new(Binder(r), Rc::new(rest)))),
+                    Rc::new(e),
+                ))
+            }
+            _ => Err(DirectStyleError::EssentialContinuation),
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cont_expr::{t_k, KExpr};
+    use crate::prelude::{app, identity, lit};
+    use moniker::BoundTerm;
+
+    #[test]
+    fn a_flattened_identity_reads_back_directly() {
+        let source = app(identity(), lit(Literal::Int(1)));
+        let halt = FreeVar::fresh_named("halt");
+        let flat = t_k(source.clone(), Rc::new(KExpr::Var(Var::Free(halt)))).into_fexpr();
+
+        let back = Expr::try_from(flat).unwrap();
+        assert!(Expr::term_eq(&back, &source));
+    }
+
+    #[test]
+    fn a_term_returning_its_continuation_is_rejected() {
+        let x = FreeVar::fresh_named("x");
+        let k = FreeVar::fresh_named("k");
+        let halt = FreeVar::fresh_named("halt");
+
+        // λ(x k). k k — the continuation escapes as a value
+        let body = FExpr::CallOne(
+            Rc::new(FExpr::Var(Var::Free(k.clone()))),
+            Rc::new(FExpr::Var(Var::Free(k.clone()))),
+        );
+        let lam = FExpr::LamTwo(Scope::new(
+            Binder(x),
+            Scope::new(Binder(k), Rc::new(body)),
+        ));
+        let flat = FExpr::CallOne(Rc::new(FExpr::Var(Var::Free(halt))), Rc::new(lam));
+
+        assert!(matches!(
+            Expr::try_from(flat),
+            Err(DirectStyleError::EssentialContinuation)
+        ));
+    }
+
+    #[test]
+    fn alpha_equivalent_terms_share_a_cache_slot() {
+        use std::collections::HashMap;
+
+        // fresh builds bind distinct variables under a shared halt, so
+        // the two lowerings are alpha-equal but not identical
+        let halt = FreeVar::fresh_named("halt");
+        let lower = |e| {
+            FExprKey(t_k(e, Rc::new(KExpr::Var(Var::Free(halt.clone())))).into_fexpr())
+        };
+
+        let a = lower(app(identity(), lit(Literal::Int(1))));
+        let b = lower(app(identity(), lit(Literal::Int(1))));
+        let c = lower(app(identity(), lit(Literal::Int(2))));
+
+        let mut cache = HashMap::new();
+        cache.insert(a, "compiled");
+
+        assert_eq!(cache.get(&b), Some(&"compiled"));
+        assert_eq!(cache.get(&c), None);
+    }
+
+    #[test]
+    fn a_let_binding_survives_the_round_trip() {
+        use crate::cont_expr::BinOp;
+        use crate::prelude::{let_in, var};
+
+        // let x = 1 in x + x: the double use marks the binding as
+        // source-level, not administrative
+        let x = FreeVar::fresh_named("x");
+        let source = let_in(
+            x.clone(),
+            lit(Literal::Int(1)),
+            Expr::Bin(Ignore(BinOp::Add), Rc::new(var(&x)), Rc::new(var(&x))),
+        );
+
+        let halt = FreeVar::fresh_named("halt");
+        let flat = t_k(source.clone(), Rc::new(KExpr::Var(Var::Free(halt)))).into_fexpr();
+
+        let back = Expr::try_from(flat).unwrap();
+        assert!(matches!(back, Expr::Let(..)));
+        assert!(Expr::term_eq(&back, &source));
+    }
+}
@@ -1,8 +1,33 @@
-#![feature(or_patterns)]
+// moniker's `BoundTerm` derive (via an old synstructure) expands to
+// impls inside an anonymous const, which trips this lint on new rustc
+#![allow(non_local_definitions)]
 
 pub mod expr;
+pub mod program;
 pub mod cont_expr;
+pub mod closed_expr;
 pub mod flat_expr;
+#[cfg(feature = "pretty")]
+pub mod render;
+pub mod text;
+#[cfg(feature = "wasm")]
+pub mod wasm;
+#[cfg(feature = "parser")]
+pub mod parser;
+#[cfg(feature = "lexpr")]
+pub mod sexpr;
+pub mod check;
+#[cfg(feature = "serde")]
+pub mod wire;
+pub mod error;
+pub mod resolve;
+pub mod escape;
+pub mod opt;
+#[cfg(feature = "eval")]
+pub mod eval;
+pub mod prelude;
+#[cfg(feature = "test-util")]
+pub mod diff;
 pub mod literals;
 mod utils;
 
@@ -1,16 +1,189 @@
+#[cfg(feature = "pretty")]
 use pretty::{DocAllocator, DocBuilder};
+#[cfg(feature = "pretty")]
 use termcolor::{Color, ColorSpec};
 
+use std::cmp::Ordering;
+use std::rc::Rc;
+
+use crate::expr::Expr;
+#[cfg(feature = "pretty")]
+use crate::render::PrettyConfig;
+use moniker::BoundTerm;
+
 #[derive(Debug, Clone)]
 pub enum Literal {
     String(String),
-    Int(u64),   // TODO: bigints
+    Char(char),
+    Int(u64),
+    // arbitrary precision; arithmetic lands here when a fixed-width
+    // result would overflow
+    #[cfg(feature = "bignum")]
+    BigInt(num_bigint::BigInt),
+    // exact fractions, always kept reduced; arithmetic touching one
+    // stays rational instead of rounding through floats. Boxed: a bare
+    // `BigRational` is two `BigInt`s wide and would fatten every
+    // `Literal` (and thus every `Value`) past the immediates contract
+    #[cfg(feature = "rational")]
+    Rational(Box<num_rational::BigRational>),
     Float(f64), // TODO: bigdecimals
+    Bool(bool),
     Void,
+    // an expression held as data, opaque to the CPS transform
+    Quoted(Rc<Expr>),
+    // a sequence of literals; `Expr::Apply` unrolls one into successive
+    // curried arguments at runtime
+    List(Vec<Literal>),
+}
+
+// Literals order by kind first (String < Char < Int < Float < Bool <
+// Void < Quoted < BigInt < List < Rational), then by value within a kind. Floats use `total_cmp`, so NaNs
+// sort after infinities and the order is total. Quoted expressions compare as equal
+// when alpha-equivalent and otherwise fall back to an arbitrary (but
+// total) order on their debug rendering.
+impl Ord for Literal {
+    fn cmp(&self, other: &Literal) -> Ordering {
+        match (self, other) {
+            (Literal::String(a), Literal::String(b)) => a.cmp(b),
+            (Literal::Char(a), Literal::Char(b)) => a.cmp(b),
+            (Literal::Int(a), Literal::Int(b)) => a.cmp(b),
+            #[cfg(feature = "bignum")]
+            (Literal::BigInt(a), Literal::BigInt(b)) => a.cmp(b),
+            #[cfg(feature = "rational")]
+            (Literal::Rational(a), Literal::Rational(b)) => a.cmp(b),
+            (Literal::Float(a), Literal::Float(b)) => a.total_cmp(b),
+            (Literal::Bool(a), Literal::Bool(b)) => a.cmp(b),
+            (Literal::Void, Literal::Void) => Ordering::Equal,
+            (Literal::List(a), Literal::List(b)) => a.cmp(b),
+            (Literal::Quoted(a), Literal::Quoted(b)) => {
+                if Expr::term_eq(a, b) {
+                    Ordering::Equal
+                } else {
+                    format!("{:?}", a).cmp(&format!("{:?}", b))
+                }
+            }
+            _ => self.kind_rank().cmp(&other.kind_rank()),
+        }
+    }
+}
+
+impl PartialOrd for Literal {
+    fn partial_cmp(&self, other: &Literal) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl PartialEq for Literal {
+    fn eq(&self, other: &Literal) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Literal {}
+
+// Hashing matches the equality above: kind first, then value. Floats
+// hash their bit pattern, which is exactly the equality `total_cmp`
+// induces. Quoted expressions are equal when alpha-equivalent, which has
+// no cheap hash, so they hash by kind alone and lean on the equality
+// check to separate them.
+impl std::hash::Hash for Literal {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        self.kind_rank().hash(state);
+        match self {
+            Literal::String(s) => s.hash(state),
+            Literal::Char(c) => c.hash(state),
+            Literal::Int(i) => i.hash(state),
+            #[cfg(feature = "bignum")]
+            Literal::BigInt(b) => b.hash(state),
+            #[cfg(feature = "rational")]
+            Literal::Rational(r) => r.hash(state),
+            Literal::Float(f) => f.to_bits().hash(state),
+            Literal::Bool(b) => b.hash(state),
+            Literal::Void | Literal::Quoted(_) => {}
+            Literal::List(ls) => ls.hash(state),
+        }
+    }
+}
+
+// conversions from the natural Rust representation of each literal kind,
+// mostly for the `expr!` macro and other term builders
+impl From<u64> for Literal {
+    fn from(i: u64) -> Literal {
+        Literal::Int(i)
+    }
+}
+
+impl From<f64> for Literal {
+    fn from(f: f64) -> Literal {
+        Literal::Float(f)
+    }
+}
+
+impl From<bool> for Literal {
+    fn from(b: bool) -> Literal {
+        Literal::Bool(b)
+    }
+}
+
+impl From<char> for Literal {
+    fn from(c: char) -> Literal {
+        Literal::Char(c)
+    }
+}
+
+impl From<&str> for Literal {
+    fn from(s: &str) -> Literal {
+        Literal::String(s.to_owned())
+    }
+}
+
+impl From<()> for Literal {
+    fn from(_: ()) -> Literal {
+        Literal::Void
+    }
+}
+
+impl From<Vec<Literal>> for Literal {
+    fn from(ls: Vec<Literal>) -> Literal {
+        Literal::List(ls)
+    }
 }
 
 impl Literal {
+    fn kind_rank(&self) -> u8 {
+        match self {
+            Literal::String(_) => 0,
+            Literal::Char(_) => 1,
+            Literal::Int(_) => 2,
+            Literal::Float(_) => 3,
+            Literal::Bool(_) => 4,
+            Literal::Void => 5,
+            Literal::Quoted(_) => 6,
+            #[cfg(feature = "bignum")]
+            Literal::BigInt(_) => 7,
+            Literal::List(_) => 8,
+            #[cfg(feature = "rational")]
+            Literal::Rational(_) => 9,
+        }
+    }
+
+    #[cfg(feature = "pretty")]
     pub fn pretty<'a, D>(&'a self, allocator: &'a D) -> DocBuilder<'a, D, ColorSpec>
+    where
+        D: DocAllocator<'a, ColorSpec>,
+        D::Doc: Clone,
+    {
+        self.pretty_with(allocator, &PrettyConfig::default())
+    }
+
+    // As `pretty`, with the rendering options threaded through; see
+    // `render::PrettyConfig` for what each knob does.
+    #[cfg(feature = "pretty")]
+    pub fn pretty_with<'a, D>(
+        &'a self,
+        allocator: &'a D,
+        config: &PrettyConfig,
+    ) -> DocBuilder<'a, D, ColorSpec>
     where
         D: DocAllocator<'a, ColorSpec>,
         D::Doc: Clone,
@@ -19,15 +192,152 @@ impl Literal {
             Literal::String(s) => allocator
                 .text(format!("\"{}\"", s))
                 .annotate(ColorSpec::new().set_fg(Some(Color::Yellow)).clone()),
+            Literal::Char(c) => allocator
+                .text(format!("'{}'", c))
+                .annotate(ColorSpec::new().set_fg(Some(Color::Yellow)).clone()),
             Literal::Int(v) => allocator
-                .as_string(v)
+                .text(int_text(v.to_string(), config.separators))
+                .annotate(ColorSpec::new().set_fg(Some(Color::Yellow)).clone()),
+            #[cfg(feature = "bignum")]
+            Literal::BigInt(v) => allocator
+                .text(int_text(v.to_string(), config.separators))
+                .annotate(ColorSpec::new().set_fg(Some(Color::Yellow)).clone()),
+            // always `num/den`, even for whole values, so the rendering
+            // never collides with an integer's
+            #[cfg(feature = "rational")]
+            Literal::Rational(v) => allocator
+                .text(format!("{}/{}", v.numer(), v.denom()))
                 .annotate(ColorSpec::new().set_fg(Some(Color::Yellow)).clone()),
             Literal::Float(v) => allocator
                 .as_string(v)
                 .annotate(ColorSpec::new().set_fg(Some(Color::Yellow)).clone()),
+            Literal::Bool(b) => allocator
+                .as_string(b)
+                .annotate(ColorSpec::new().set_fg(Some(Color::Yellow)).clone()),
             Literal::Void => allocator
                 .text("void")
                 .annotate(ColorSpec::new().set_fg(Some(Color::Yellow)).clone()),
+            Literal::Quoted(e) => allocator
+                .text("'")
+                .annotate(ColorSpec::new().set_fg(Some(Color::Yellow)).clone())
+                .append(e.pretty_with(allocator, config)),
+            Literal::List(ls) => allocator
+                .intersperse(
+                    ls.iter().map(|l| l.pretty_with(allocator, config)),
+                    allocator.space(),
+                )
+                .enclose("[", "]"),
+        }
+    }
+}
+
+// Groups the digits of a decimal rendering in threes with `_`, leaving
+// any leading sign alone.
+#[cfg(feature = "pretty")]
+fn int_text(digits: String, separators: bool) -> String {
+    if !separators {
+        return digits;
+    }
+
+    let (sign, digits) = match digits.strip_prefix('-') {
+        Some(rest) => ("-", rest),
+        None => ("", digits.as_str()),
+    };
+
+    let mut out = String::from(sign);
+    for (i, c) in digits.chars().enumerate() {
+        if i != 0 && (digits.len() - i) % 3 == 0 {
+            out.push('_');
         }
+        out.push(c);
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::BTreeMap;
+
+    #[test]
+    fn mixed_literals_sort_by_kind_then_value() {
+        let mut lits = vec![
+            Literal::Void,
+            Literal::Int(2),
+            Literal::Float(1.5),
+            Literal::String("b".to_owned()),
+            Literal::Int(1),
+            Literal::String("a".to_owned()),
+        ];
+
+        lits.sort();
+
+        assert_eq!(
+            lits,
+            vec![
+                Literal::String("a".to_owned()),
+                Literal::String("b".to_owned()),
+                Literal::Int(1),
+                Literal::Int(2),
+                Literal::Float(1.5),
+                Literal::Void,
+            ]
+        );
+    }
+
+    #[cfg(feature = "pretty")]
+    #[test]
+    fn integers_render_with_optional_thousands_separators() {
+        fn render(lit: &Literal, separators: bool) -> String {
+            let allocator = pretty::Arena::new();
+            let config = PrettyConfig {
+                separators,
+                ..PrettyConfig::default()
+            };
+            let doc = lit.pretty_with(&allocator, &config).1;
+
+            let mut buf = Vec::new();
+            doc.render(70, &mut buf).unwrap();
+            String::from_utf8(buf).unwrap()
+        }
+
+        let million = Literal::Int(1_000_000);
+        assert_eq!(render(&million, false), "1000000");
+        assert_eq!(render(&million, true), "1_000_000");
+
+        // groups only land between complete triples
+        assert_eq!(render(&Literal::Int(100), true), "100");
+        assert_eq!(render(&Literal::Int(1000), true), "1_000");
+    }
+
+    #[test]
+    fn quoted_expressions_compare_up_to_alpha() {
+        use crate::prelude::identity;
+
+        // two fresh builds bind distinct variables but are alpha-equal
+        let a = Literal::Quoted(Rc::new(identity()));
+        let b = Literal::Quoted(Rc::new(identity()));
+        assert_eq!(a, b);
+
+        let c = Literal::Quoted(Rc::new(crate::prelude::constant()));
+        assert_ne!(a, c);
+
+        // the recursion reaches quoted terms nested inside lists
+        let nested_a = Literal::List(vec![Literal::Int(1), a]);
+        let nested_b = Literal::List(vec![Literal::Int(1), b]);
+        let nested_c = Literal::List(vec![Literal::Int(1), c]);
+        assert_eq!(nested_a, nested_b);
+        assert_ne!(nested_a, nested_c);
+    }
+
+    #[test]
+    fn literals_work_as_map_keys() {
+        let mut map = BTreeMap::new();
+        map.insert(Literal::Int(1), "one");
+        map.insert(Literal::String("one".to_owned()), "uno");
+
+        assert_eq!(map.get(&Literal::Int(1)), Some(&"one"));
+        assert_eq!(map.get(&Literal::String("one".to_owned())), Some(&"uno"));
+        assert_eq!(map.get(&Literal::Int(2)), None);
     }
 }
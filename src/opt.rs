@@ -0,0 +1,853 @@
+use moniker::{Binder, BoundTerm, FreeVar, Ignore, Scope, Var};
+
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+use std::rc::Rc;
+
+use crate::cont_expr::{CCall, KExpr, SubTerm, UExpr};
+use crate::expr::Expr;
+use crate::literals::Literal;
+use crate::utils::{clone_rc, grow_stack};
+
+// Beta-reduces call sites whose head is a syntactically-apparent lambda:
+// `((lambda (x k) body) v c)` becomes `body[x := v][k := c]`. This is a
+// targeted specialization, not full normalization: it works bottom-up and
+// reduces each site once, leaving alone any redexes the substitution
+// itself creates, so it always terminates.
+pub fn specialize(call: CCall) -> CCall {
+    grow_stack(|| match call {
+        CCall::UCall(f, v, c) => {
+            let f = specialize_u(clone_rc(f));
+            let c = specialize_k(clone_rc(c));
+
+            match f {
+                UExpr::Lam(s) => {
+                    let (Binder(param), body) = s.unbind();
+                    let (Binder(cont), body) = body.unbind();
+
+                    clone_rc(body)
+                        .subst_user(&param, clone_rc(v))
+                        .subst_cont(&cont, c)
+                }
+                f => CCall::UCall(Rc::new(f), v, Rc::new(c)),
+            }
+        }
+        CCall::KCall(f, v) => CCall::KCall(Rc::new(specialize_k(clone_rc(f))), v),
+        CCall::If(c, t, e) => CCall::If(
+            Rc::new(specialize_u(clone_rc(c))),
+            Rc::new(specialize(clone_rc(t))),
+            Rc::new(specialize(clone_rc(e))),
+        ),
+    })
+}
+
+fn specialize_u(expr: UExpr) -> UExpr {
+    match expr {
+        UExpr::Lam(s) => {
+            let Scope {
+                unsafe_pattern: pat,
+                unsafe_body:
+                    Scope {
+                        unsafe_pattern: cont,
+                        unsafe_body: body,
+                    },
+            } = s;
+
+            UExpr::Lam(Scope {
+                unsafe_pattern: pat,
+                unsafe_body: Scope {
+                    unsafe_pattern: cont,
+                    unsafe_body: Rc::new(specialize(clone_rc(body))),
+                },
+            })
+        }
+        UExpr::Fix(s) => {
+            let Scope {
+                unsafe_pattern: pat,
+                unsafe_body: body,
+            } = s;
+
+            UExpr::Fix(Scope {
+                unsafe_pattern: pat,
+                unsafe_body: Rc::new(specialize_u(clone_rc(body))),
+            })
+        }
+        v @ (UExpr::Var(_) | UExpr::Lit(_) | UExpr::Prim(_)) => v,
+    }
+}
+
+fn specialize_k(expr: KExpr) -> KExpr {
+    match expr {
+        KExpr::Lam(s) => {
+            let Scope {
+                unsafe_pattern: pat,
+                unsafe_body: body,
+            } = s;
+
+            KExpr::Lam(Scope {
+                unsafe_pattern: pat,
+                unsafe_body: Rc::new(specialize(clone_rc(body))),
+            })
+        }
+        v @ (KExpr::Var(_) | KExpr::Lit(_)) => v,
+    }
+}
+
+// Counts free occurrences of `var` (in either user or continuation
+// position) in `call`. Intended for use on the opened body of an
+// `unbind`, where the binder's uses show up as free variables.
+pub fn occurrences(call: &CCall, var: &FreeVar<String>) -> usize {
+    call.subterms()
+        .filter(|t| match t {
+            SubTerm::U(UExpr::Var(Var::Free(v))) | SubTerm::K(KExpr::Var(Var::Free(v))) => {
+                v == var
+            }
+            _ => false,
+        })
+        .count()
+}
+
+// Removes `let`-style bindings whose result is never used: a
+// `KCall(KExpr::Lam(x. body), v)` where `x` has no occurrences in `body`
+// reduces to `body`. Every `UExpr` is a value, so dropping the bound
+// argument can't discard an effect. Continuations that are still
+// referenced anywhere — including ones only passed along to other calls,
+// which might be invoked indirectly — are kept.
+pub fn dead_code(call: CCall) -> CCall {
+    grow_stack(|| match call {
+        CCall::KCall(k, v) => match clone_rc(k) {
+            KExpr::Lam(s) => {
+                let (Binder(param), body) = s.unbind();
+                let body = dead_code(clone_rc(body));
+
+                if occurrences(&body, &param) == 0 {
+                    body
+                } else {
+                    CCall::KCall(
+                        Rc::new(KExpr::Lam(Scope::new(Binder(param), Rc::new(body)))),
+                        Rc::new(dead_code_u(clone_rc(v))),
+                    )
+                }
+            }
+            k => CCall::KCall(Rc::new(k), Rc::new(dead_code_u(clone_rc(v)))),
+        },
+        CCall::UCall(f, v, c) => CCall::UCall(
+            Rc::new(dead_code_u(clone_rc(f))),
+            Rc::new(dead_code_u(clone_rc(v))),
+            Rc::new(dead_code_k(clone_rc(c))),
+        ),
+        CCall::If(c, t, e) => CCall::If(
+            Rc::new(dead_code_u(clone_rc(c))),
+            Rc::new(dead_code(clone_rc(t))),
+            Rc::new(dead_code(clone_rc(e))),
+        ),
+    })
+}
+
+fn dead_code_u(expr: UExpr) -> UExpr {
+    match expr {
+        UExpr::Lam(s) => {
+            let (param, body) = s.unbind();
+            let (cont, body) = body.unbind();
+
+            UExpr::Lam(Scope::new(
+                param,
+                Scope::new(cont, Rc::new(dead_code(clone_rc(body)))),
+            ))
+        }
+        UExpr::Fix(s) => {
+            let (binder, body) = s.unbind();
+
+            UExpr::Fix(Scope::new(binder, Rc::new(dead_code_u(clone_rc(body)))))
+        }
+        v @ (UExpr::Var(_) | UExpr::Lit(_) | UExpr::Prim(_)) => v,
+    }
+}
+
+fn dead_code_k(expr: KExpr) -> KExpr {
+    match expr {
+        KExpr::Lam(s) => {
+            let (param, body) = s.unbind();
+
+            KExpr::Lam(Scope::new(param, Rc::new(dead_code(clone_rc(body)))))
+        }
+        v @ (KExpr::Var(_) | KExpr::Lit(_)) => v,
+    }
+}
+
+// Inlines administrative continuation-lets: a `KCall(KExpr::Lam(x.
+// body), v)` where `x` occurs exactly once in `body` substitutes `v`
+// straight into that use. The bound term is a value, so moving it to
+// its single use point can't duplicate work or reorder effects; multiple
+// uses are left alone to avoid growing the term.
+pub fn admin_redexes(call: CCall) -> CCall {
+    grow_stack(|| match call {
+        CCall::KCall(k, v) => match clone_rc(k) {
+            KExpr::Lam(s) => {
+                let (Binder(param), body) = s.unbind();
+                let body = admin_redexes(clone_rc(body));
+                let v = admin_redexes_u(clone_rc(v));
+
+                if occurrences(&body, &param) == 1 {
+                    body.subst_user(&param, v)
+                } else {
+                    CCall::KCall(
+                        Rc::new(KExpr::Lam(Scope::new(Binder(param), Rc::new(body)))),
+                        Rc::new(v),
+                    )
+                }
+            }
+            k => CCall::KCall(Rc::new(k), Rc::new(admin_redexes_u(clone_rc(v)))),
+        },
+        CCall::UCall(f, v, c) => CCall::UCall(
+            Rc::new(admin_redexes_u(clone_rc(f))),
+            Rc::new(admin_redexes_u(clone_rc(v))),
+            Rc::new(admin_redexes_k(clone_rc(c))),
+        ),
+        CCall::If(c, t, e) => CCall::If(
+            Rc::new(admin_redexes_u(clone_rc(c))),
+            Rc::new(admin_redexes(clone_rc(t))),
+            Rc::new(admin_redexes(clone_rc(e))),
+        ),
+    })
+}
+
+fn admin_redexes_u(expr: UExpr) -> UExpr {
+    match expr {
+        UExpr::Lam(s) => {
+            let (param, body) = s.unbind();
+            let (cont, body) = body.unbind();
+
+            UExpr::Lam(Scope::new(
+                param,
+                Scope::new(cont, Rc::new(admin_redexes(clone_rc(body)))),
+            ))
+        }
+        UExpr::Fix(s) => {
+            let (binder, body) = s.unbind();
+
+            UExpr::Fix(Scope::new(binder, Rc::new(admin_redexes_u(clone_rc(body)))))
+        }
+        v @ (UExpr::Var(_) | UExpr::Lit(_) | UExpr::Prim(_)) => v,
+    }
+}
+
+fn admin_redexes_k(expr: KExpr) -> KExpr {
+    match expr {
+        KExpr::Lam(s) => {
+            let (param, body) = s.unbind();
+
+            KExpr::Lam(Scope::new(param, Rc::new(admin_redexes(clone_rc(body)))))
+        }
+        v @ (KExpr::Var(_) | KExpr::Lit(_)) => v,
+    }
+}
+
+// One named rewrite over a CPS term, so callers can describe a pipeline
+// as data instead of calling each pass by hand.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Pass {
+    // beta-reduce syntactically-apparent lambda call sites (`specialize`)
+    Beta,
+    // inline single-use continuation-let bindings (`admin_redexes`)
+    AdminRedex,
+    // drop bindings whose result is never used (`dead_code`)
+    DeadCode,
+    // share alpha-equivalent subterms behind one `Rc` (`hash_cons`)
+    HashCons,
+}
+
+// Applies `passes` to `call` in order, once each.
+pub fn optimize(call: CCall, passes: &[Pass]) -> CCall {
+    passes.iter().fold(call, |call, pass| match pass {
+        Pass::Beta => specialize(call),
+        Pass::AdminRedex => admin_redexes(call),
+        Pass::DeadCode => dead_code(call),
+        Pass::HashCons => hash_cons(call),
+    })
+}
+
+// Applies the pass list repeatedly until a round changes nothing (up to
+// alpha) or `max_rounds` is hit. Earlier passes expose work for later
+// ones — a beta reduction can orphan a binding for `dead_code`, say —
+// so one round is rarely the end.
+pub fn optimize_to_fixpoint(call: CCall, passes: &[Pass], max_rounds: usize) -> CCall {
+    let mut call = call;
+
+    for _ in 0..max_rounds {
+        let next = optimize(call.clone(), passes);
+        if CCall::term_eq(&next, &call) {
+            return next;
+        }
+        call = next;
+    }
+
+    call
+}
+
+// Drops the evaluation of arguments that the callee provably ignores:
+// `(λx. body) e` with no occurrences of `x` in `body` rewrites to `body`,
+// but only when `e` is pure — values can be discarded, whereas calls,
+// assertions and primitive applications might diverge, abort or error and
+// must still run. Works on the source language, before CPS lowering turns
+// the argument into an explicit evaluation step.
+pub fn elide_unused_args(expr: Expr) -> Expr {
+    #[cfg(feature = "tracing")]
+    let _span = tracing::debug_span!("elide_unused_args", input_size = expr.size_hint()).entered();
+
+    elide_unused_args_inner(expr)
+}
+
+fn elide_unused_args_inner(expr: Expr) -> Expr {
+    grow_stack(|| match expr {
+        Expr::App(f, e) => {
+            let f = elide_unused_args_inner(clone_rc(f));
+            let e = elide_unused_args_inner(clone_rc(e));
+
+            if let (Expr::Lam(s), true) = (&f, is_pure(&e)) {
+                let (Binder(param), body) = s.clone().unbind();
+
+                if expr_occurrences(&body, &param) == 0 {
+                    return clone_rc(body);
+                }
+            }
+
+            Expr::App(Rc::new(f), Rc::new(e))
+        }
+        Expr::Lam(s) => {
+            let Scope {
+                unsafe_pattern: pat,
+                unsafe_body: body,
+            } = s;
+
+            Expr::Lam(Scope {
+                unsafe_pattern: pat,
+                unsafe_body: Rc::new(elide_unused_args_inner(clone_rc(body))),
+            })
+        }
+        Expr::Apply(f, l) => Expr::Apply(
+            Rc::new(elide_unused_args_inner(clone_rc(f))),
+            Rc::new(elide_unused_args_inner(clone_rc(l))),
+        ),
+        Expr::LamRest(s) => {
+            let Scope {
+                unsafe_pattern: pat,
+                unsafe_body: body,
+            } = s;
+
+            Expr::LamRest(Scope {
+                unsafe_pattern: pat,
+                unsafe_body: Rc::new(elide_unused_args_inner(clone_rc(body))),
+            })
+        }
+        Expr::Let(v, s) => {
+            let Scope {
+                unsafe_pattern: pat,
+                unsafe_body: body,
+            } = s;
+
+            Expr::Let(
+                Rc::new(elide_unused_args_inner(clone_rc(v))),
+                Scope {
+                    unsafe_pattern: pat,
+                    unsafe_body: Rc::new(elide_unused_args_inner(clone_rc(body))),
+                },
+            )
+        }
+        Expr::Assert(cond, msg) => {
+            Expr::Assert(Rc::new(elide_unused_args_inner(clone_rc(cond))), msg)
+        }
+        Expr::Not(e) => Expr::Not(Rc::new(elide_unused_args_inner(clone_rc(e)))),
+        Expr::Cast(kind, e) => Expr::Cast(kind, Rc::new(elide_unused_args_inner(clone_rc(e)))),
+        e @ Expr::Error(_) => e,
+        Expr::Delay(e) => Expr::Delay(Rc::new(elide_unused_args_inner(clone_rc(e)))),
+        Expr::Force(e) => Expr::Force(Rc::new(elide_unused_args_inner(clone_rc(e)))),
+        Expr::Tuple(es) => Expr::Tuple(
+            es.into_iter()
+                .map(|e| Rc::new(elide_unused_args_inner(clone_rc(e))))
+                .collect(),
+        ),
+        Expr::Proj(i, e) => Expr::Proj(i, Rc::new(elide_unused_args_inner(clone_rc(e)))),
+        Expr::Bin(op, a, b) => Expr::Bin(
+            op,
+            Rc::new(elide_unused_args_inner(clone_rc(a))),
+            Rc::new(elide_unused_args_inner(clone_rc(b))),
+        ),
+        Expr::If(c, t, e) => Expr::If(
+            Rc::new(elide_unused_args_inner(clone_rc(c))),
+            Rc::new(elide_unused_args_inner(clone_rc(t))),
+            Rc::new(elide_unused_args_inner(clone_rc(e))),
+        ),
+        Expr::Cond(clauses, els) => Expr::Cond(
+            clauses
+                .into_iter()
+                .map(|(test, body)| {
+                    (
+                        Rc::new(elide_unused_args_inner(clone_rc(test))),
+                        Rc::new(elide_unused_args_inner(clone_rc(body))),
+                    )
+                })
+                .collect(),
+            Rc::new(elide_unused_args_inner(clone_rc(els))),
+        ),
+        Expr::While(c, b) => Expr::While(
+            Rc::new(elide_unused_args_inner(clone_rc(c))),
+            Rc::new(elide_unused_args_inner(clone_rc(b))),
+        ),
+        Expr::TryFinally(b, cl) => Expr::TryFinally(
+            Rc::new(elide_unused_args_inner(clone_rc(b))),
+            Rc::new(elide_unused_args_inner(clone_rc(cl))),
+        ),
+        Expr::Amb(a, b) => Expr::Amb(
+            Rc::new(elide_unused_args_inner(clone_rc(a))),
+            Rc::new(elide_unused_args_inner(clone_rc(b))),
+        ),
+        Expr::LetRecMany(s) => {
+            let Scope {
+                unsafe_pattern: pats,
+                unsafe_body: (defs, body),
+            } = s;
+
+            Expr::LetRecMany(Scope {
+                unsafe_pattern: pats,
+                unsafe_body: (
+                    defs.into_iter()
+                        .map(|d| Rc::new(elide_unused_args_inner(clone_rc(d))))
+                        .collect(),
+                    Rc::new(elide_unused_args_inner(clone_rc(body))),
+                ),
+            })
+        }
+        Expr::Fix(s) => {
+            let Scope {
+                unsafe_pattern: pat,
+                unsafe_body: body,
+            } = s;
+
+            Expr::Fix(Scope {
+                unsafe_pattern: pat,
+                unsafe_body: Rc::new(elide_unused_args_inner(clone_rc(body))),
+            })
+        }
+        v @ (Expr::Var(_) | Expr::Lit(_) | Expr::Fail) => v,
+    })
+}
+
+// Syntactic purity: discarding one of these can't change behaviour.
+// Anything that runs code — calls, assertions, primitives that can
+// error — counts as effectful.
+fn is_pure(expr: &Expr) -> bool {
+    matches!(expr, Expr::Var(_) | Expr::Lit(_) | Expr::Lam(_))
+}
+
+fn expr_occurrences(expr: &Expr, var: &FreeVar<String>) -> usize {
+    let mut count = 0;
+    expr.visit_vars(&mut |v| {
+        if let Var::Free(fv) = v {
+            if fv == var {
+                count += 1;
+            }
+        }
+    });
+    count
+}
+
+// Hash-conses a term: structurally identical (alpha-equivalent) `UExpr`
+// and `KExpr` subterms end up sharing one `Rc`. Terms are fingerprinted
+// with an alpha-invariant hash — binder patterns are skipped, bound
+// occurrences hash by de Bruijn position, free ones by unique id — and
+// buckets are confirmed with `term_eq` before sharing.
+pub fn hash_cons(call: CCall) -> CCall {
+    let mut table = ConsTable::default();
+    cons_c(&call, &mut table)
+}
+
+#[derive(Default)]
+struct ConsTable {
+    u: HashMap<u64, Vec<Rc<UExpr>>>,
+    k: HashMap<u64, Vec<Rc<KExpr>>>,
+}
+
+impl ConsTable {
+    fn share_u(&mut self, expr: UExpr) -> Rc<UExpr> {
+        let mut h = DefaultHasher::new();
+        fingerprint_u(&expr, &mut h);
+        let bucket = self.u.entry(h.finish()).or_default();
+
+        match bucket.iter().find(|e| UExpr::term_eq(e, &expr)) {
+            Some(shared) => shared.clone(),
+            None => {
+                let fresh = Rc::new(expr);
+                bucket.push(fresh.clone());
+                fresh
+            }
+        }
+    }
+
+    fn share_k(&mut self, expr: KExpr) -> Rc<KExpr> {
+        let mut h = DefaultHasher::new();
+        fingerprint_k(&expr, &mut h);
+        let bucket = self.k.entry(h.finish()).or_default();
+
+        match bucket.iter().find(|e| KExpr::term_eq(e, &expr)) {
+            Some(shared) => shared.clone(),
+            None => {
+                let fresh = Rc::new(expr);
+                bucket.push(fresh.clone());
+                fresh
+            }
+        }
+    }
+}
+
+fn cons_c(call: &CCall, table: &mut ConsTable) -> CCall {
+    grow_stack(|| match call {
+        CCall::UCall(f, v, c) => CCall::UCall(
+            cons_u(f, table),
+            cons_u(v, table),
+            cons_k(c, table),
+        ),
+        CCall::KCall(k, v) => CCall::KCall(cons_k(k, table), cons_u(v, table)),
+        CCall::If(c, t, e) => CCall::If(
+            cons_u(c, table),
+            Rc::new(cons_c(t, table)),
+            Rc::new(cons_c(e, table)),
+        ),
+    })
+}
+
+fn cons_u(expr: &UExpr, table: &mut ConsTable) -> Rc<UExpr> {
+    let rebuilt = match expr {
+        UExpr::Lam(s) => UExpr::Lam(Scope {
+            unsafe_pattern: s.unsafe_pattern.clone(),
+            unsafe_body: Scope {
+                unsafe_pattern: s.unsafe_body.unsafe_pattern.clone(),
+                unsafe_body: Rc::new(cons_c(&s.unsafe_body.unsafe_body, table)),
+            },
+        }),
+        UExpr::Fix(s) => UExpr::Fix(Scope {
+            unsafe_pattern: s.unsafe_pattern.clone(),
+            unsafe_body: cons_u(&s.unsafe_body, table),
+        }),
+        v => v.clone(),
+    };
+
+    table.share_u(rebuilt)
+}
+
+fn cons_k(expr: &KExpr, table: &mut ConsTable) -> Rc<KExpr> {
+    let rebuilt = match expr {
+        KExpr::Lam(s) => KExpr::Lam(Scope {
+            unsafe_pattern: s.unsafe_pattern.clone(),
+            unsafe_body: Rc::new(cons_c(&s.unsafe_body, table)),
+        }),
+        v => v.clone(),
+    };
+
+    table.share_k(rebuilt)
+}
+
+fn fingerprint_u(expr: &UExpr, h: &mut impl Hasher) {
+    match expr {
+        UExpr::Lam(s) => {
+            0u8.hash(h);
+            fingerprint_c(&s.unsafe_body.unsafe_body, h);
+        }
+        UExpr::Var(v) => {
+            1u8.hash(h);
+            v.hash(h);
+        }
+        UExpr::Lit(Ignore(l)) => {
+            2u8.hash(h);
+            fingerprint_lit(l, h);
+        }
+        UExpr::Prim(Ignore(p)) => {
+            3u8.hash(h);
+            p.to_string().hash(h);
+        }
+        UExpr::Fix(s) => {
+            4u8.hash(h);
+            fingerprint_u(&s.unsafe_body, h);
+        }
+    }
+}
+
+fn fingerprint_k(expr: &KExpr, h: &mut impl Hasher) {
+    match expr {
+        KExpr::Lam(s) => {
+            0u8.hash(h);
+            fingerprint_c(&s.unsafe_body, h);
+        }
+        KExpr::Var(v) => {
+            1u8.hash(h);
+            v.hash(h);
+        }
+        KExpr::Lit(Ignore(l)) => {
+            2u8.hash(h);
+            fingerprint_lit(l, h);
+        }
+    }
+}
+
+fn fingerprint_c(call: &CCall, h: &mut impl Hasher) {
+    grow_stack(|| match call {
+        CCall::UCall(f, v, c) => {
+            0u8.hash(h);
+            fingerprint_u(f, h);
+            fingerprint_u(v, h);
+            fingerprint_k(c, h);
+        }
+        CCall::KCall(k, v) => {
+            1u8.hash(h);
+            fingerprint_k(k, h);
+            fingerprint_u(v, h);
+        }
+        CCall::If(c, t, e) => {
+            2u8.hash(h);
+            fingerprint_u(c, h);
+            fingerprint_c(t, h);
+            fingerprint_c(e, h);
+        }
+    })
+}
+
+// Bucket literals cheaply: discriminant plus the easily-hashed payloads.
+// Floats and quoted terms fall back to discriminant-only buckets, where
+// the `term_eq` confirmation still keeps sharing correct.
+fn fingerprint_lit(lit: &Literal, h: &mut impl Hasher) {
+    std::mem::discriminant(lit).hash(h);
+    match lit {
+        Literal::String(s) => s.hash(h),
+        Literal::Char(c) => c.hash(h),
+        Literal::Int(i) => i.hash(h),
+        #[cfg(feature = "bignum")]
+        Literal::BigInt(i) => i.hash(h),
+        #[cfg(feature = "rational")]
+        Literal::Rational(r) => r.hash(h),
+        Literal::Bool(b) => b.hash(h),
+        Literal::List(ls) => ls.len().hash(h),
+        Literal::Float(_) | Literal::Void | Literal::Quoted(_) => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::expr;
+
+    #[test]
+    fn unused_pure_argument_is_elided() {
+        let elided = elide_unused_args(expr!(app(lam x -> lit 1, lit 2)));
+
+        assert!(Expr::term_eq(&elided, &expr!(lit 1)));
+    }
+
+    #[test]
+    fn effectful_argument_is_kept() {
+        let f = FreeVar::fresh_named("f");
+
+        // the argument is a call, which might diverge or abort
+        let call = Expr::App(
+            Rc::new(Expr::Var(Var::Free(f))),
+            Rc::new(expr!(lit 2)),
+        );
+        let term = Expr::App(Rc::new(expr!(lam x -> lit 1)), Rc::new(call));
+
+        assert!(Expr::term_eq(&elide_unused_args(term.clone()), &term));
+    }
+
+    #[test]
+    fn equal_continuations_share_one_rc() {
+        let halt = FreeVar::fresh_named("halt");
+        let f = FreeVar::fresh_named("f");
+
+        // the same `(lambda (x) (halt x))` continuation built twice with
+        // distinct binders, used in two different calls
+        let make_k = || {
+            let x = FreeVar::fresh_named("x");
+            KExpr::lam(
+                x.clone(),
+                CCall::kcall(
+                    KExpr::Var(Var::Free(halt.clone())),
+                    UExpr::Var(Var::Free(x)),
+                ),
+            )
+        };
+
+        let call = CCall::UCall(
+            Rc::new(UExpr::lam(
+                FreeVar::fresh_named("y"),
+                FreeVar::fresh_named("k"),
+                CCall::UCall(
+                    Rc::new(UExpr::Var(Var::Free(f.clone()))),
+                    Rc::new(UExpr::Lit(Ignore(Literal::Int(1)))),
+                    Rc::new(make_k()),
+                ),
+            )),
+            Rc::new(UExpr::Var(Var::Free(f))),
+            Rc::new(make_k()),
+        );
+
+        let consed = hash_cons(call.clone());
+        assert!(CCall::term_eq(&consed, &call));
+
+        let lams: Vec<_> = consed
+            .subterms()
+            .filter_map(|t| match t {
+                SubTerm::K(k @ KExpr::Lam(_)) => Some(k as *const KExpr),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(lams.len(), 2);
+        assert_eq!(lams[0], lams[1]);
+    }
+
+    #[test]
+    fn specialize_beta_reduces_known_lambda() {
+        let x = FreeVar::fresh_named("x");
+        let k = FreeVar::fresh_named("k");
+        let halt = FreeVar::fresh_named("halt");
+
+        // ((lambda (x k) (k x)) 5 halt)
+        let lam = UExpr::Lam(Scope::new(
+            Binder(x.clone()),
+            Scope::new(
+                Binder(k.clone()),
+                Rc::new(CCall::KCall(
+                    Rc::new(KExpr::Var(Var::Free(k))),
+                    Rc::new(UExpr::Var(Var::Free(x))),
+                )),
+            ),
+        ));
+
+        let call = CCall::UCall(
+            Rc::new(lam),
+            Rc::new(UExpr::Lit(Ignore(Literal::Int(5)))),
+            Rc::new(KExpr::Var(Var::Free(halt.clone()))),
+        );
+
+        let expected = CCall::KCall(
+            Rc::new(KExpr::Var(Var::Free(halt))),
+            Rc::new(UExpr::Lit(Ignore(Literal::Int(5)))),
+        );
+
+        assert!(CCall::term_eq(&specialize(call), &expected));
+    }
+
+    #[test]
+    fn specialize_leaves_unknown_heads_alone() {
+        let f = FreeVar::fresh_named("f");
+        let halt = FreeVar::fresh_named("halt");
+
+        let call = CCall::UCall(
+            Rc::new(UExpr::Var(Var::Free(f))),
+            Rc::new(UExpr::Lit(Ignore(Literal::Int(5)))),
+            Rc::new(KExpr::Var(Var::Free(halt))),
+        );
+
+        assert!(CCall::term_eq(&specialize(call.clone()), &call));
+    }
+
+    #[test]
+    fn unused_binding_is_removed() {
+        let unused = FreeVar::fresh_named("unused");
+        let halt = FreeVar::fresh_named("halt");
+
+        // let unused = 5 in (halt 1)
+        let call = CCall::kcall(
+            KExpr::lam(
+                unused,
+                CCall::kcall(
+                    KExpr::Var(Var::Free(halt.clone())),
+                    UExpr::Lit(Ignore(Literal::Int(1))),
+                ),
+            ),
+            UExpr::Lit(Ignore(Literal::Int(5))),
+        );
+
+        let expected = CCall::kcall(
+            KExpr::Var(Var::Free(halt)),
+            UExpr::Lit(Ignore(Literal::Int(1))),
+        );
+
+        assert!(CCall::term_eq(&dead_code(call), &expected));
+    }
+
+    #[test]
+    fn used_binding_is_kept() {
+        let x = FreeVar::fresh_named("x");
+        let halt = FreeVar::fresh_named("halt");
+
+        // let x = 5 in (halt x)
+        let call = CCall::kcall(
+            KExpr::lam(
+                x.clone(),
+                CCall::kcall(KExpr::Var(Var::Free(halt)), UExpr::Var(Var::Free(x))),
+            ),
+            UExpr::Lit(Ignore(Literal::Int(5))),
+        );
+
+        assert!(CCall::term_eq(&dead_code(call.clone()), &call));
+    }
+
+    #[test]
+    fn escaping_continuation_argument_is_kept() {
+        let k = FreeVar::fresh_named("k");
+        let f = FreeVar::fresh_named("f");
+        let x = FreeVar::fresh_named("x");
+
+        // let k = ... in (f x k): k escapes into the call, so it stays
+        let call = CCall::UCall(
+            Rc::new(UExpr::Var(Var::Free(f))),
+            Rc::new(UExpr::Var(Var::Free(x))),
+            Rc::new(KExpr::lam(
+                k.clone(),
+                CCall::kcall(
+                    KExpr::Var(Var::Free(FreeVar::fresh_named("halt"))),
+                    UExpr::Var(Var::Free(k)),
+                ),
+            )),
+        );
+
+        assert!(CCall::term_eq(&dead_code(call.clone()), &call));
+    }
+
+    #[cfg(feature = "eval")]
+    #[test]
+    fn the_pipeline_shrinks_a_call_chain_and_preserves_its_result() {
+        use crate::cont_expr::{t_k, BinOp, PrimOp};
+        use crate::eval::{run_ccall, Env, Value};
+        use crate::prelude::{app, lit, var};
+
+        // f (g 3), with f and g host-bound primitives
+        let f = FreeVar::fresh_named("f");
+        let g = FreeVar::fresh_named("g");
+        let expr = app(var(&f), app(var(&g), lit(Literal::Int(3))));
+
+        let halt = FreeVar::fresh_named("halt");
+        let lower = |e| t_k(e, Rc::new(KExpr::Var(Var::Free(halt.clone()))));
+
+        let plain = lower(expr);
+        let optimized = optimize_to_fixpoint(
+            plain.clone(),
+            &[Pass::Beta, Pass::AdminRedex, Pass::DeadCode, Pass::HashCons],
+            8,
+        );
+
+        assert!(optimized.subterms().count() < plain.subterms().count());
+
+        let env = Env::new()
+            .insert(halt, Value::Halt)
+            .insert(f, Value::PrimOp(PrimOp::BinaryWith(BinOp::Mul, Literal::Int(2))))
+            .insert(g, Value::PrimOp(PrimOp::BinaryWith(BinOp::Add, Literal::Int(1))));
+
+        let a = run_ccall(plain, env.clone()).unwrap();
+        let b = run_ccall(optimized, env).unwrap();
+
+        match (a, b) {
+            (Value::Lit(Literal::Int(8)), Value::Lit(Literal::Int(8))) => {}
+            r => panic!("expected 8 from both, got {:?}", r),
+        }
+    }
+}
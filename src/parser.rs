@@ -0,0 +1,456 @@
+// An infix front-end for source programs: a Pratt parser over a
+// host-configurable operator table, so embedders decide how `+`, `*`,
+// and friends bind. `1 + 2 * 3` parses into the nested `Expr::Bin` form
+// the lowering expects. The table maps operator spellings to a `BinOp`,
+// a precedence (higher binds tighter), and an associativity; the
+// default table gives the conventional arithmetic layering.
+//
+// Identifiers become free variables, shared by spelling across the
+// parse the way the `text` parser shares them.
+
+use std::collections::HashMap;
+use std::rc::Rc;
+
+use moniker::{FreeVar, Ignore, Var};
+
+use crate::cont_expr::BinOp;
+use crate::expr::Expr;
+use crate::literals::Literal;
+
+#[derive(Debug)]
+pub struct ParseError {
+    pub message: String,
+    // byte offset into the input where the error was noticed
+    pub offset: usize,
+}
+
+impl std::fmt::Display for ParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{} at byte {}", self.message, self.offset)
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Assoc {
+    Left,
+    Right,
+}
+
+#[derive(Debug, Clone)]
+struct OpEntry {
+    op: BinOp,
+    precedence: u8,
+    assoc: Assoc,
+}
+
+#[derive(Debug, Clone)]
+pub struct OpTable {
+    entries: HashMap<String, OpEntry>,
+}
+
+impl Default for OpTable {
+    fn default() -> OpTable {
+        let mut table = OpTable {
+            entries: HashMap::new(),
+        };
+
+        table.define("*", BinOp::Mul, 20, Assoc::Left);
+        table.define("/", BinOp::Div, 20, Assoc::Left);
+        table.define("+", BinOp::Add, 10, Assoc::Left);
+        table.define("-", BinOp::Sub, 10, Assoc::Left);
+        table.define("==", BinOp::Eq, 5, Assoc::Left);
+        table
+    }
+}
+
+impl OpTable {
+    // Adds or replaces an operator spelling; higher precedences bind
+    // tighter.
+    pub fn define(&mut self, token: &str, op: BinOp, precedence: u8, assoc: Assoc) {
+        self.entries.insert(
+            token.to_owned(),
+            OpEntry {
+                op,
+                precedence,
+                assoc,
+            },
+        );
+    }
+}
+
+pub fn parse_expr(input: &str) -> Result<Expr, ParseError> {
+    parse_expr_with(input, &OpTable::default())
+}
+
+pub fn parse_expr_with(input: &str, table: &OpTable) -> Result<Expr, ParseError> {
+    let tokens = tokenize(input, None)?;
+    let mut parser = Parser {
+        tokens,
+        pos: 0,
+        input_len: input.len(),
+        free: HashMap::new(),
+        table,
+        errors: None,
+    };
+
+    let expr = parser.expr_bp(0)?;
+    if parser.pos != parser.tokens.len() {
+        return Err(ParseError {
+            message: "trailing input after expression".to_owned(),
+            offset: parser.tokens[parser.pos].0,
+        });
+    }
+    Ok(expr)
+}
+
+// Parses with error recovery, for editors: instead of aborting at the
+// first problem the parser records a diagnostic, stands an `Expr::Error`
+// marker in for the fragment it could not read, and keeps going. A tree
+// always comes back; an empty diagnostic list means it is exactly what
+// `parse_expr` would have produced.
+pub fn parse_expr_recover(input: &str) -> (Expr, Vec<ParseError>) {
+    parse_expr_recover_with(input, &OpTable::default())
+}
+
+pub fn parse_expr_recover_with(input: &str, table: &OpTable) -> (Expr, Vec<ParseError>) {
+    let mut errors = Vec::new();
+    // recovering tokenization cannot fail: bad characters are recorded
+    // and skipped
+    let tokens = tokenize(input, Some(&mut errors)).unwrap_or_default();
+    let mut parser = Parser {
+        tokens,
+        pos: 0,
+        input_len: input.len(),
+        free: HashMap::new(),
+        table,
+        errors: Some(errors),
+    };
+
+    // with recovery on, the only error `expr_bp` can surface is running
+    // out of input, and `fail` has already turned even that into a
+    // marker
+    let expr = parser
+        .expr_bp(0)
+        .unwrap_or(Expr::Error(Ignore("unexpected end of input".to_owned())));
+    let mut errors = parser.errors.take().unwrap_or_default();
+    if parser.pos != parser.tokens.len() {
+        errors.push(ParseError {
+            message: "trailing input after expression".to_owned(),
+            offset: parser.tokens[parser.pos].0,
+        });
+    }
+    (expr, errors)
+}
+
+#[derive(Debug, Clone)]
+enum Token {
+    LParen,
+    RParen,
+    Int(u64),
+    Ident(String),
+    Op(String),
+}
+
+// With `errors` supplied, unexpected characters are recorded there and
+// skipped instead of failing the whole tokenization.
+fn tokenize(
+    input: &str,
+    mut errors: Option<&mut Vec<ParseError>>,
+) -> Result<Vec<(usize, Token)>, ParseError> {
+    let mut tokens = Vec::new();
+    let mut chars = input.char_indices().peekable();
+
+    let is_op_char = |c: char| "+-*/=<>!&|%^~".contains(c);
+
+    while let Some(&(offset, c)) = chars.peek() {
+        match c {
+            c if c.is_whitespace() => {
+                chars.next();
+            }
+            '(' => {
+                chars.next();
+                tokens.push((offset, Token::LParen));
+            }
+            ')' => {
+                chars.next();
+                tokens.push((offset, Token::RParen));
+            }
+            c if c.is_ascii_digit() => {
+                let mut digits = String::new();
+                while let Some(&(_, c)) = chars.peek() {
+                    if !c.is_ascii_digit() {
+                        break;
+                    }
+                    digits.push(c);
+                    chars.next();
+                }
+                let i = digits.parse().map_err(|_| ParseError {
+                    message: format!("integer out of range: {}", digits),
+                    offset,
+                })?;
+                tokens.push((offset, Token::Int(i)));
+            }
+            c if c.is_alphabetic() || c == '_' => {
+                let mut name = String::new();
+                while let Some(&(_, c)) = chars.peek() {
+                    if !(c.is_alphanumeric() || c == '_') {
+                        break;
+                    }
+                    name.push(c);
+                    chars.next();
+                }
+                tokens.push((offset, Token::Ident(name)));
+            }
+            c if is_op_char(c) => {
+                let mut op = String::new();
+                while let Some(&(_, c)) = chars.peek() {
+                    if !is_op_char(c) {
+                        break;
+                    }
+                    op.push(c);
+                    chars.next();
+                }
+                tokens.push((offset, Token::Op(op)));
+            }
+            c => {
+                let err = ParseError {
+                    message: format!("unexpected character {:?}", c),
+                    offset,
+                };
+                match errors.as_deref_mut() {
+                    Some(errors) => {
+                        errors.push(err);
+                        chars.next();
+                    }
+                    None => return Err(err),
+                }
+            }
+        }
+    }
+
+    Ok(tokens)
+}
+
+struct Parser<'t> {
+    tokens: Vec<(usize, Token)>,
+    pos: usize,
+    input_len: usize,
+    free: HashMap<String, FreeVar<String>>,
+    table: &'t OpTable,
+    // `Some` when recovering: diagnostics land here and error markers
+    // stand in for whatever failed
+    errors: Option<Vec<ParseError>>,
+}
+
+impl Parser<'_> {
+    // In recovering mode, records the diagnostic and answers an error
+    // marker; otherwise fails the parse.
+    fn fail(&mut self, err: ParseError) -> Result<Expr, ParseError> {
+        match &mut self.errors {
+            Some(errors) => {
+                let marker = Expr::Error(Ignore(err.message.clone()));
+                errors.push(err);
+                Ok(marker)
+            }
+            None => Err(err),
+        }
+    }
+
+    fn next(&mut self) -> Result<(usize, Token), ParseError> {
+        let tok = self.tokens.get(self.pos).cloned().ok_or(ParseError {
+            message: "unexpected end of input".to_owned(),
+            offset: self.input_len,
+        })?;
+        self.pos += 1;
+        Ok(tok)
+    }
+
+    // The Pratt loop: parse an atom, then fold in operators while their
+    // precedence stays at or above `min_bp`. Left associativity bumps
+    // the right-hand bound so `a - b - c` nests leftward.
+    fn expr_bp(&mut self, min_bp: u8) -> Result<Expr, ParseError> {
+        let mut lhs = self.atom()?;
+
+        while let Some((offset, Token::Op(op))) = self.tokens.get(self.pos).cloned() {
+            let entry = match self.table.entries.get(&op).cloned() {
+                Some(entry) => entry,
+                None => {
+                    let err = ParseError {
+                        message: format!("unknown operator {}", op),
+                        offset,
+                    };
+                    // in recovering mode, pretend the operator was not
+                    // there and carry on after it
+                    self.fail(err)?;
+                    self.pos += 1;
+                    continue;
+                }
+            };
+
+            if entry.precedence < min_bp {
+                break;
+            }
+            self.pos += 1;
+
+            let next_min = match entry.assoc {
+                Assoc::Left => entry.precedence + 1,
+                Assoc::Right => entry.precedence,
+            };
+            let rhs = self.expr_bp(next_min)?;
+            lhs = Expr::Bin(Ignore(entry.op), Rc::new(lhs), Rc::new(rhs));
+        }
+
+        Ok(lhs)
+    }
+
+    fn atom(&mut self) -> Result<Expr, ParseError> {
+        let tok = match self.next() {
+            Ok(tok) => tok,
+            Err(err) => return self.fail(err),
+        };
+
+        match tok {
+            (_, Token::Int(i)) => Ok(Expr::Lit(Ignore(Literal::Int(i)))),
+            (_, Token::Ident(name)) => {
+                let var = self
+                    .free
+                    .entry(name.clone())
+                    .or_insert_with(|| FreeVar::fresh_named(name))
+                    .clone();
+                Ok(Expr::Var(Var::Free(var)))
+            }
+            (_, Token::LParen) => {
+                let inner = self.expr_bp(0)?;
+                match self.next() {
+                    Ok((_, Token::RParen)) => Ok(inner),
+                    Ok((offset, _)) => {
+                        self.fail(ParseError {
+                            message: "expected a closing paren".to_owned(),
+                            offset,
+                        })?;
+                        // skip to the paren that balances the open one,
+                        // keeping what parsed so far
+                        self.sync_to_rparen();
+                        Ok(inner)
+                    }
+                    Err(err) => {
+                        self.fail(err)?;
+                        Ok(inner)
+                    }
+                }
+            }
+            (offset, _) => self.fail(ParseError {
+                message: "expected a literal, identifier, or paren".to_owned(),
+                offset,
+            }),
+        }
+    }
+
+    // Skips forward past the next closing paren at this nesting depth,
+    // or to the end of input; only reachable when recovering.
+    fn sync_to_rparen(&mut self) {
+        let mut depth = 0usize;
+        while let Some((_, tok)) = self.tokens.get(self.pos) {
+            match tok {
+                Token::LParen => depth += 1,
+                Token::RParen => {
+                    self.pos += 1;
+                    if depth == 0 {
+                        return;
+                    }
+                    depth -= 1;
+                    continue;
+                }
+                _ => {}
+            }
+            self.pos += 1;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use moniker::BoundTerm;
+
+    use crate::prelude::lit;
+
+    fn bin(op: BinOp, a: Expr, b: Expr) -> Expr {
+        Expr::Bin(Ignore(op), Rc::new(a), Rc::new(b))
+    }
+
+    #[test]
+    fn multiplication_binds_tighter_by_default() {
+        let parsed = parse_expr("1 + 2 * 3").unwrap();
+        let expected = bin(
+            BinOp::Add,
+            lit(Literal::Int(1)),
+            bin(BinOp::Mul, lit(Literal::Int(2)), lit(Literal::Int(3))),
+        );
+        assert!(Expr::term_eq(&parsed, &expected));
+
+        // subtraction chains leftward
+        let parsed = parse_expr("1 - 2 - 3").unwrap();
+        let expected = bin(
+            BinOp::Sub,
+            bin(BinOp::Sub, lit(Literal::Int(1)), lit(Literal::Int(2))),
+            lit(Literal::Int(3)),
+        );
+        assert!(Expr::term_eq(&parsed, &expected));
+    }
+
+    #[test]
+    fn a_custom_table_reverses_the_nesting() {
+        let mut table = OpTable::default();
+        table.define("+", BinOp::Add, 30, Assoc::Left);
+
+        let parsed = parse_expr_with("1 + 2 * 3", &table).unwrap();
+        let expected = bin(
+            BinOp::Mul,
+            bin(BinOp::Add, lit(Literal::Int(1)), lit(Literal::Int(2))),
+            lit(Literal::Int(3)),
+        );
+        assert!(Expr::term_eq(&parsed, &expected));
+    }
+
+    #[test]
+    fn parens_override_any_table() {
+        let parsed = parse_expr("(1 + 2) * 3").unwrap();
+        let expected = bin(
+            BinOp::Mul,
+            bin(BinOp::Add, lit(Literal::Int(1)), lit(Literal::Int(2))),
+            lit(Literal::Int(3)),
+        );
+        assert!(Expr::term_eq(&parsed, &expected));
+    }
+
+    #[test]
+    fn a_bad_token_yields_a_partial_tree_and_a_diagnostic() {
+        // the stray paren cannot start an atom; the rest still parses
+        let (parsed, errors) = parse_expr_recover("1 + ) * 3");
+
+        assert_eq!(errors.len(), 1);
+        assert!(errors[0].message.contains("expected a literal"));
+
+        // the tree keeps its shape — `1 + (<error> * 3)` — with a
+        // marker standing where the atom failed
+        match &parsed {
+            Expr::Bin(Ignore(BinOp::Add), lhs, rhs) => {
+                assert!(Expr::term_eq(lhs, &lit(Literal::Int(1))));
+                assert!(matches!(&**rhs, Expr::Bin(Ignore(BinOp::Mul), e, n)
+                    if matches!(&**e, Expr::Error(_))
+                        && Expr::term_eq(n, &lit(Literal::Int(3)))));
+            }
+            tree => panic!("expected a binary tree, got {:?}", tree),
+        }
+    }
+
+    #[test]
+    fn a_clean_parse_recovers_with_no_diagnostics() {
+        let (parsed, errors) = parse_expr_recover("1 + 2 * 3");
+        assert!(errors.is_empty());
+        assert!(Expr::term_eq(&parsed, &parse_expr("1 + 2 * 3").unwrap()));
+    }
+}
@@ -0,0 +1,287 @@
+use moniker::{Binder, BoundTerm, FreeVar, Scope, Var};
+
+use std::rc::Rc;
+
+use crate::expr::Expr;
+use crate::literals::Literal;
+
+// Pre-built combinators, handy as examples and as fixtures in tests.
+// Each call builds a fresh term (with fresh binders), so the results are
+// safe to splice into larger programs.
+
+pub fn lam(v: FreeVar<String>, body: Expr) -> Expr {
+    Expr::Lam(Scope::new(Binder(v), Rc::new(body)))
+}
+
+// A rest lambda: under `apply`, `v` binds the list of every remaining
+// argument.
+pub fn lam_rest(v: FreeVar<String>, body: Expr) -> Expr {
+    Expr::LamRest(Scope::new(Binder(v), Rc::new(body)))
+}
+
+// `Scope::new` with a debug-build sanity check: closing the body must
+// not leave behind a free variable spelled like the binder. Such a
+// leftover almost always means the body was built against a different
+// `FreeVar::fresh_named` of the same name, which closes over nothing —
+// a silent bug in hand-built terms and custom passes.
+pub fn scope<T: BoundTerm<String>>(binder: FreeVar<String>, body: T) -> Scope<Binder<String>, T> {
+    let scope = Scope::new(Binder(binder), body);
+    #[cfg(debug_assertions)]
+    {
+        let Binder(bound) = &scope.unsafe_pattern;
+        let mut stale = None;
+        scope.unsafe_body.visit_vars(&mut |v| {
+            if let Var::Free(fv) = v {
+                if fv.pretty_name == bound.pretty_name && stale.is_none() {
+                    stale = Some(fv.clone());
+                }
+            }
+        });
+        debug_assert!(
+            stale.is_none(),
+            "scope binding {} leaves {} free in its body; was the body built against a different fresh var?",
+            bound,
+            stale.unwrap(),
+        );
+    }
+    scope
+}
+
+pub fn app(f: Expr, e: Expr) -> Expr {
+    Expr::App(Rc::new(f), Rc::new(e))
+}
+
+pub fn tuple(es: Vec<Expr>) -> Expr {
+    Expr::Tuple(es.into_iter().map(Rc::new).collect())
+}
+
+pub fn proj(i: usize, e: Expr) -> Expr {
+    Expr::Proj(moniker::Ignore(i), Rc::new(e))
+}
+
+// let v = value in body
+pub fn let_in(v: FreeVar<String>, value: Expr, body: Expr) -> Expr {
+    Expr::Let(Rc::new(value), Scope::new(Binder(v), Rc::new(body)))
+}
+
+pub fn var(v: &FreeVar<String>) -> Expr {
+    Expr::Var(Var::Free(v.clone()))
+}
+
+pub fn lit(l: Literal) -> Expr {
+    Expr::Lit(moniker::Ignore(l))
+}
+
+pub fn fresh(name: &str) -> FreeVar<String> {
+    FreeVar::fresh_named(name)
+}
+
+// Wraps a term so `assert_eq!` compares up to alpha-equivalence: bound
+// variables match by binding structure rather than by name or id, so
+// two independently built terms with fresh binders compare equal.
+// Works for any `BoundTerm` — `Expr`, `CCall`, `FExpr` — and, unlike a
+// bare `assert!(term_eq(..))`, a failing assertion prints both sides.
+#[derive(Debug, Clone)]
+pub struct AlphaEq<T>(pub T);
+
+impl<T: BoundTerm<String>> PartialEq for AlphaEq<T> {
+    fn eq(&self, other: &AlphaEq<T>) -> bool {
+        T::term_eq(&self.0, &other.0)
+    }
+}
+
+impl<T: BoundTerm<String>> Eq for AlphaEq<T> {}
+
+// Concise construction of `Expr` trees; binders introduced by `lam` are
+// ordinary Rust bindings holding a fresh `FreeVar`, so `var x` under a
+// `lam x ->` refers back to it and unbound names fail to compile:
+//
+//     expr!(lam x -> app(var x, lit 1))
+#[macro_export]
+macro_rules! expr {
+    (lam $x:ident -> $($body:tt)+) => {{
+        let $x = $crate::prelude::fresh(stringify!($x));
+        $crate::prelude::lam($x.clone(), $crate::expr!($($body)+))
+    }};
+    (app ( $($args:tt)+ )) => {
+        $crate::expr!(@app [] $($args)+)
+    };
+    (var $x:ident) => {
+        $crate::prelude::var(&$x)
+    };
+    (lit $l:literal) => {
+        $crate::prelude::lit($crate::literals::Literal::from($l))
+    };
+    (( $($inner:tt)+ )) => {
+        $crate::expr!($($inner)+)
+    };
+    // munch the function tokens of an `app` up to the top-level comma
+    (@app [$($f:tt)*] , $($e:tt)+) => {
+        $crate::prelude::app($crate::expr!($($f)*), $crate::expr!($($e)+))
+    };
+    (@app [$($f:tt)*] $next:tt $($rest:tt)*) => {
+        $crate::expr!(@app [$($f)* $next] $($rest)*)
+    };
+}
+
+// λx. x
+pub fn identity() -> Expr {
+    let x = FreeVar::fresh_named("x");
+    lam(x.clone(), var(&x))
+}
+
+// λx. λy. x
+pub fn constant() -> Expr {
+    let x = FreeVar::fresh_named("x");
+    let y = FreeVar::fresh_named("y");
+    lam(x.clone(), lam(y, var(&x)))
+}
+
+// λf. λg. λx. f (g x)
+pub fn compose() -> Expr {
+    let f = FreeVar::fresh_named("f");
+    let g = FreeVar::fresh_named("g");
+    let x = FreeVar::fresh_named("x");
+    lam(
+        f.clone(),
+        lam(g.clone(), lam(x.clone(), app(var(&f), app(var(&g), var(&x))))),
+    )
+}
+
+// λf. (λx. f (x x)) (λx. f (x x)) — diverges under call-by-value, kept
+// for completeness alongside the eta-expanded `z_combinator`
+pub fn y_combinator() -> Expr {
+    let f = FreeVar::fresh_named("f");
+    let x = FreeVar::fresh_named("x");
+    let half = lam(x.clone(), app(var(&f), app(var(&x), var(&x))));
+    lam(f, app(half.clone(), half))
+}
+
+// λf. (λx. f (λv. x x v)) (λx. f (λv. x x v)) — the call-by-value fixpoint
+pub fn z_combinator() -> Expr {
+    let f = FreeVar::fresh_named("f");
+    let x = FreeVar::fresh_named("x");
+    let v = FreeVar::fresh_named("v");
+    let half = lam(
+        x.clone(),
+        app(
+            var(&f),
+            lam(v.clone(), app(app(var(&x), var(&x)), var(&v))),
+        ),
+    );
+    lam(f, app(half.clone(), half))
+}
+
+// λt. λf. t
+pub fn church_true() -> Expr {
+    let t = FreeVar::fresh_named("t");
+    let f = FreeVar::fresh_named("f");
+    lam(t.clone(), lam(f, var(&t)))
+}
+
+// λt. λf. f
+pub fn church_false() -> Expr {
+    let t = FreeVar::fresh_named("t");
+    let f = FreeVar::fresh_named("f");
+    lam(t, lam(f.clone(), var(&f)))
+}
+
+// λf. λx. f (f ... (f x))
+pub fn church_num(n: usize) -> Expr {
+    let f = FreeVar::fresh_named("f");
+    let x = FreeVar::fresh_named("x");
+
+    let mut body = var(&x);
+    for _ in 0..n {
+        body = app(var(&f), body);
+    }
+
+    lam(f, lam(x, body))
+}
+
+#[cfg(all(test, feature = "eval"))]
+mod tests {
+    use super::*;
+    use crate::eval::{run, Value};
+    use crate::literals::Literal;
+    use moniker::{BoundTerm, Ignore};
+
+    #[test]
+    fn identity_returns_its_argument() {
+        let expr = app(identity(), Expr::Lit(Ignore(Literal::Int(7))));
+
+        match run(expr).unwrap() {
+            Value::Lit(Literal::Int(7)) => {}
+            v => panic!("expected 7, got {:?}", v),
+        }
+    }
+
+    #[test]
+    fn constant_ignores_its_second_argument() {
+        let expr = app(
+            app(constant(), Expr::Lit(Ignore(Literal::Int(1)))),
+            Expr::Lit(Ignore(Literal::Int(2))),
+        );
+
+        match run(expr).unwrap() {
+            Value::Lit(Literal::Int(1)) => {}
+            v => panic!("expected 1, got {:?}", v),
+        }
+    }
+
+    #[test]
+    fn macro_terms_match_hand_built() {
+        let built = expr!(lam x -> app(var x, lit 1));
+
+        let x = FreeVar::fresh_named("x");
+        let hand = lam(x.clone(), app(var(&x), lit(Literal::Int(1))));
+
+        assert!(Expr::term_eq(&built, &hand));
+    }
+
+    #[test]
+    fn macro_lambdas_nest() {
+        let built = expr!(lam f -> lam x -> app(var f, app(var f, var x)));
+
+        assert!(Expr::term_eq(&built, &church_num(2)));
+    }
+
+    #[test]
+    fn combinators_are_freshly_built() {
+        // two builds are alpha-equal but bind distinct variables
+        assert!(Expr::term_eq(&identity(), &identity()));
+        assert!(Expr::term_eq(&church_num(2), &church_num(2)));
+        assert!(!Expr::term_eq(&church_num(2), &church_num(3)));
+    }
+
+    #[test]
+    fn alpha_eq_ignores_binder_names() {
+        // `identity` binds an `x`; this binds a `y` — equal up to alpha
+        let y = fresh("y");
+        assert_eq!(AlphaEq(identity()), AlphaEq(lam(y.clone(), var(&y))));
+    }
+
+    #[test]
+    fn alpha_eq_distinguishes_structure() {
+        assert_ne!(AlphaEq(identity()), AlphaEq(constant()));
+        assert_ne!(AlphaEq(church_num(2)), AlphaEq(church_num(3)));
+    }
+
+    #[test]
+    fn the_scope_builder_accepts_a_well_formed_body() {
+        let x = FreeVar::fresh_named("x");
+        let s = scope(x.clone(), Rc::new(var(&x)));
+
+        assert!(Expr::term_eq(&Expr::Lam(s), &identity()));
+    }
+
+    #[test]
+    #[should_panic(expected = "different fresh var")]
+    fn a_mismatched_binder_trips_the_debug_assertion() {
+        // same pretty name, different fresh var: the body's `x` stays
+        // free and the binder closes over nothing
+        let binder = FreeVar::fresh_named("x");
+        let other = FreeVar::fresh_named("x");
+        let _ = scope(binder, Rc::new(var(&other)));
+    }
+}
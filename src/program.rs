@@ -0,0 +1,97 @@
+// A whole script: a sequence of named top-level definitions plus the
+// main expression that uses them. Definitions desugar to a `let*`-style
+// chain of immediately-applied lambdas around `main`, so each definition
+// sees the ones before it and `main` sees them all; there is no new
+// binding construct for lowering or evaluation to learn about.
+
+use std::rc::Rc;
+
+use moniker::{Binder, FreeVar, Scope, Var};
+
+use crate::cont_expr::{t_k, CCall, KExpr};
+use crate::expr::Expr;
+
+#[derive(Debug, Clone)]
+pub struct Program {
+    pub defs: Vec<(FreeVar<String>, Expr)>,
+    pub main: Expr,
+}
+
+impl Program {
+    // Collapses the program into a single expression:
+    //
+    //   ((λd1. ((λd2. main) e2)) e1)
+    //
+    // which is `let* d1 = e1; d2 = e2 in main`.
+    pub fn into_expr(self) -> Expr {
+        self.defs
+            .into_iter()
+            .rev()
+            .fold(self.main, |acc, (name, def)| {
+                Expr::App(
+                    Rc::new(Expr::Lam(Scope::new(Binder(name), Rc::new(acc)))),
+                    Rc::new(def),
+                )
+            })
+    }
+
+    // Lowers the whole program against `k`; definitions become ordinary
+    // `UCall`s evaluated before `main`'s body runs.
+    pub fn lower_with(self, k: Rc<KExpr>) -> CCall {
+        t_k(self.into_expr(), k)
+    }
+
+    // As `lower_with`, against a fresh halt continuation variable — the
+    // same shape `eval::run` lowers to.
+    pub fn lower(self) -> CCall {
+        let halt = FreeVar::fresh_named("halt");
+        self.lower_with(Rc::new(KExpr::Var(Var::Free(halt))))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cont_expr::{BinOp, SubTerm, UExpr};
+    use crate::prelude::{app, fresh, lam, lit, var};
+    use crate::literals::Literal;
+    use moniker::Ignore;
+
+    fn doubler_program() -> Program {
+        // double = λx. x + x; main = double 21
+        let double = fresh("double");
+        let x = fresh("x");
+
+        Program {
+            defs: vec![(
+                double.clone(),
+                lam(
+                    x.clone(),
+                    Expr::Bin(Ignore(BinOp::Add), Rc::new(var(&x)), Rc::new(var(&x))),
+                ),
+            )],
+            main: app(var(&double), lit(Literal::Int(21))),
+        }
+    }
+
+    #[test]
+    fn definitions_lower_to_an_enclosing_scope() {
+        let call = doubler_program().lower();
+
+        // the definition's lambda survives lowering as a `UExpr::Lam`
+        assert!(call
+            .subterms()
+            .any(|t| matches!(t, SubTerm::U(UExpr::Lam(_)))));
+    }
+
+    #[cfg(feature = "eval")]
+    #[test]
+    fn main_sees_a_helper_definition() {
+        use crate::eval::{run, Value};
+
+        match run(doubler_program().into_expr()).unwrap() {
+            Value::Lit(Literal::Int(42)) => {}
+            v => panic!("expected 42, got {:?}", v),
+        }
+    }
+}
@@ -0,0 +1,267 @@
+// One home for every rendering option. The `pretty` doc builders take a
+// `PrettyConfig` so new knobs don't each grow another method variant;
+// `PrettyConfig::default()` reproduces the historical output of the
+// methods it replaces, which stay as thin delegators.
+
+#[derive(Debug, Clone)]
+pub struct PrettyConfig {
+    // wrap at this many columns; 0 means never wrap
+    pub width: usize,
+    // allow color codes when the writer supports them; the default
+    // honours the `NO_COLOR` convention
+    pub color: bool,
+    // group integer digits in threes with `_`
+    pub separators: bool,
+    // annotate calls in tail position (`CCall` rendering only)
+    pub tail_hints: bool,
+    // what one level of nesting indents by when a group breaks
+    pub indent: Indent,
+}
+
+// The unit of indentation. The doc layer only counts columns, so tabs
+// are produced by rewriting the leading spaces of each rendered line,
+// one tab per nesting level; see `IndentWriter`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Indent {
+    // this many spaces per nesting level
+    Spaces(usize),
+    // one tab per nesting level
+    Tabs,
+}
+
+impl Default for PrettyConfig {
+    fn default() -> PrettyConfig {
+        PrettyConfig {
+            width: 70,
+            color: !no_color_requested(),
+            separators: false,
+            tail_hints: false,
+            indent: Indent::Spaces(1),
+        }
+    }
+}
+
+// The `NO_COLOR` convention (https://no-color.org): a non-empty value
+// in the environment asks for plain output, read afresh for each
+// default so long-lived processes see changes.
+fn no_color_requested() -> bool {
+    std::env::var_os("NO_COLOR").is_some_and(|v| !v.is_empty())
+}
+
+// Serializes tests that touch `NO_COLOR`, so a test can set it without
+// racing the tests that assert colored output.
+#[cfg(test)]
+pub(crate) static COLOR_ENV_LOCK: std::sync::Mutex<()> = std::sync::Mutex::new(());
+
+impl PrettyConfig {
+    // the column budget handed to the renderer
+    pub(crate) fn render_width(&self) -> usize {
+        if self.width == 0 {
+            usize::MAX
+        } else {
+            self.width
+        }
+    }
+
+    // the offset handed to each `nest`; tabs lay out as one column per
+    // level and widen in the writer
+    pub(crate) fn nest_offset(&self) -> isize {
+        match self.indent {
+            Indent::Spaces(n) => n as isize,
+            Indent::Tabs => 1,
+        }
+    }
+}
+
+// Wraps the output writer for `pretty_print_conf`. Under
+// `Indent::Spaces` it passes everything through; under `Indent::Tabs`
+// it turns each leading space of a line into a tab, which is exactly
+// the indentation because the docs nest one column per level and
+// nothing else renders at the start of a broken line.
+pub(crate) struct IndentWriter<W> {
+    inner: W,
+    tabs: bool,
+    at_line_start: bool,
+}
+
+impl<W> IndentWriter<W> {
+    pub(crate) fn new(inner: W, indent: Indent) -> IndentWriter<W> {
+        IndentWriter {
+            inner,
+            tabs: indent == Indent::Tabs,
+            at_line_start: true,
+        }
+    }
+}
+
+impl<W: std::io::Write> std::io::Write for IndentWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        if !self.tabs {
+            return self.inner.write(buf);
+        }
+        let mut out = Vec::with_capacity(buf.len());
+        for &b in buf {
+            if b == b'\n' {
+                self.at_line_start = true;
+                out.push(b);
+            } else if self.at_line_start && b == b' ' {
+                out.push(b'\t');
+            } else {
+                self.at_line_start = false;
+                out.push(b);
+            }
+        }
+        self.inner.write_all(&out)?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+impl<W: termcolor::WriteColor> termcolor::WriteColor for IndentWriter<W> {
+    fn supports_color(&self) -> bool {
+        self.inner.supports_color()
+    }
+
+    fn set_color(&mut self, spec: &termcolor::ColorSpec) -> std::io::Result<()> {
+        self.inner.set_color(spec)
+    }
+
+    fn reset(&mut self) -> std::io::Result<()> {
+        self.inner.reset()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::rc::Rc;
+
+    use crate::cont_expr::BinOp;
+    use crate::expr::Expr;
+    use crate::literals::Literal;
+    use crate::prelude::lit;
+    use moniker::Ignore;
+
+    fn render(expr: &Expr, config: &PrettyConfig) -> String {
+        let mut buf = termcolor::Buffer::no_color();
+        expr.pretty_print_conf(config, &mut buf).unwrap();
+        String::from_utf8(buf.into_inner()).unwrap()
+    }
+
+    #[test]
+    fn config_fields_change_the_output() {
+        let term = Expr::Bin(
+            Ignore(BinOp::Add),
+            Rc::new(lit(Literal::Int(1_000_000))),
+            Rc::new(lit(Literal::Int(1))),
+        );
+
+        let plain = render(&term, &PrettyConfig::default());
+        assert_eq!(plain, "(add 1000000 1)");
+
+        let separated = render(
+            &term,
+            &PrettyConfig {
+                separators: true,
+                ..PrettyConfig::default()
+            },
+        );
+        assert_eq!(separated, "(add 1_000_000 1)");
+    }
+
+    #[test]
+    fn a_zero_width_never_wraps() {
+        let wide = Expr::Bin(
+            Ignore(BinOp::Add),
+            Rc::new(lit(Literal::String("a".repeat(100)))),
+            Rc::new(lit(Literal::Int(1))),
+        );
+
+        let unwrapped = render(
+            &wide,
+            &PrettyConfig {
+                width: 0,
+                ..PrettyConfig::default()
+            },
+        );
+        assert!(!unwrapped.contains('\n'));
+    }
+
+    #[test]
+    fn no_color_in_the_environment_disables_color_by_default() {
+        let _guard = COLOR_ENV_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+
+        std::env::set_var("NO_COLOR", "1");
+        let config = PrettyConfig::default();
+        std::env::remove_var("NO_COLOR");
+        assert!(!config.color);
+
+        // even a color-capable sink stays plain under the default
+        let mut buf = termcolor::Buffer::ansi();
+        lit(Literal::Int(1)).pretty_print_conf(&config, &mut buf).unwrap();
+        assert!(!buf.as_slice().contains(&0x1b));
+
+        // an empty value does not count as set, per the convention
+        std::env::set_var("NO_COLOR", "");
+        let config = PrettyConfig::default();
+        std::env::remove_var("NO_COLOR");
+        assert!(config.color);
+    }
+
+    #[test]
+    fn the_indent_unit_changes_broken_lines_only() {
+        // wide enough that the lambda body breaks onto its own line
+        let term = crate::prelude::lam(
+            moniker::FreeVar::fresh_named("x"),
+            Expr::Bin(
+                Ignore(BinOp::Add),
+                Rc::new(lit(Literal::String("a".repeat(100)))),
+                Rc::new(lit(Literal::Int(1))),
+            ),
+        );
+
+        let spaced = render(
+            &term,
+            &PrettyConfig {
+                indent: Indent::Spaces(2),
+                ..PrettyConfig::default()
+            },
+        );
+        let tabbed = render(
+            &term,
+            &PrettyConfig {
+                indent: Indent::Tabs,
+                ..PrettyConfig::default()
+            },
+        );
+
+        assert!(spaced.lines().nth(1).unwrap().starts_with("  "), "got {:?}", spaced);
+        assert!(tabbed.lines().nth(1).unwrap().starts_with('\t'), "got {:?}", tabbed);
+        // the two layouts agree exactly, one tab per two spaces
+        assert_eq!(tabbed.replace('\t', "  "), spaced);
+    }
+
+    #[test]
+    fn oneline_mode_emits_no_newlines() {
+        // a lambda body is a break point, so the default width wraps this
+        let wide = crate::prelude::lam(
+            moniker::FreeVar::fresh_named("x"),
+            Expr::Bin(
+                Ignore(BinOp::Add),
+                Rc::new(lit(Literal::String("a".repeat(100)))),
+                Rc::new(lit(Literal::Int(1))),
+            ),
+        );
+
+        let mut oneline = termcolor::Buffer::no_color();
+        wide.pretty_print_oneline(&mut oneline).unwrap();
+        assert!(!String::from_utf8(oneline.into_inner()).unwrap().contains('\n'));
+
+        let mut wrapped = termcolor::Buffer::no_color();
+        wide.pretty_print(&mut wrapped).unwrap();
+        assert!(String::from_utf8(wrapped.into_inner()).unwrap().contains('\n'));
+    }
+}
@@ -0,0 +1,157 @@
+// Binder resolution for editor tooling: maps every variable occurrence
+// in a source program to the binder it refers to, which is what
+// go-to-definition and highlight-uses need. The tree carries no spans,
+// so occurrences are keyed by node address — the editor layer that owns
+// the span table can look a node's verdict up with `Rc::as_ptr`, the
+// same convention `escape::classify` uses.
+//
+// moniker already did the hard part: bound occurrences are de Bruijn
+// indices under raw traversal, so resolution is a walk with a stack of
+// the binders passed on the way down. Free occurrences resolve to their
+// own `FreeVar`, which plays the role of a top-level definition site.
+
+use std::collections::HashMap;
+
+use moniker::{FreeVar, Var};
+
+use crate::expr::Expr;
+
+pub fn resolve(expr: &Expr) -> HashMap<*const Expr, FreeVar<String>> {
+    let mut out = HashMap::new();
+    visit(expr, &mut Vec::new(), &mut out);
+    out
+}
+
+fn visit(
+    expr: &Expr,
+    scopes: &mut Vec<Vec<FreeVar<String>>>,
+    out: &mut HashMap<*const Expr, FreeVar<String>>,
+) {
+    match expr {
+        Expr::Var(Var::Free(v)) => {
+            out.insert(expr as *const Expr, v.clone());
+        }
+        Expr::Var(Var::Bound(bv)) => {
+            // the offset counts scopes outward from the occurrence, the
+            // stack grows inward from the root; within a scope the
+            // binder index picks the variable (only `LetRecMany` binds
+            // more than one)
+            let idx = scopes.len() - 1 - bv.scope.0 as usize;
+            out.insert(
+                expr as *const Expr,
+                scopes[idx][bv.binder.0 as usize].clone(),
+            );
+        }
+        Expr::Lit(_) | Expr::Error(_) | Expr::Fail => {}
+        Expr::Lam(s) | Expr::LamRest(s) | Expr::Fix(s) => {
+            scopes.push(vec![s.unsafe_pattern.0.clone()]);
+            visit(&s.unsafe_body, scopes, out);
+            scopes.pop();
+        }
+        Expr::App(f, e) | Expr::Apply(f, e) => {
+            visit(f, scopes, out);
+            visit(e, scopes, out);
+        }
+        Expr::Let(v, s) => {
+            visit(v, scopes, out);
+            scopes.push(vec![s.unsafe_pattern.0.clone()]);
+            visit(&s.unsafe_body, scopes, out);
+            scopes.pop();
+        }
+        Expr::Assert(c, _) => visit(c, scopes, out),
+        Expr::Not(e) | Expr::Cast(_, e) | Expr::Delay(e) | Expr::Force(e) => {
+            visit(e, scopes, out)
+        }
+        Expr::Tuple(es) => {
+            for e in es {
+                visit(e, scopes, out);
+            }
+        }
+        Expr::Proj(_, e) => visit(e, scopes, out),
+        Expr::Bin(_, a, b) => {
+            visit(a, scopes, out);
+            visit(b, scopes, out);
+        }
+        Expr::If(c, t, e) => {
+            visit(c, scopes, out);
+            visit(t, scopes, out);
+            visit(e, scopes, out);
+        }
+        Expr::Cond(clauses, els) => {
+            for (test, body) in clauses {
+                visit(test, scopes, out);
+                visit(body, scopes, out);
+            }
+            visit(els, scopes, out);
+        }
+        Expr::While(c, b) | Expr::TryFinally(c, b) | Expr::Amb(c, b) => {
+            visit(c, scopes, out);
+            visit(b, scopes, out);
+        }
+        Expr::LetRecMany(s) => {
+            let (defs, body) = &s.unsafe_body;
+            scopes.push(s.unsafe_pattern.iter().map(|b| b.0.clone()).collect());
+            for d in defs {
+                visit(d, scopes, out);
+            }
+            visit(body, scopes, out);
+            scopes.pop();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::rc::Rc;
+
+    use crate::prelude::{app, fresh, lam, var};
+
+    #[test]
+    fn both_uses_resolve_to_the_same_binder() {
+        let x = fresh("x");
+        let term = lam(x.clone(), app(var(&x), var(&x)));
+
+        // closing the scope rewrites the occurrences, so take the nodes
+        // out of the finished term rather than keeping the built ones
+        let (f_use, v_use) = match &term {
+            Expr::Lam(s) => match &*s.unsafe_body {
+                Expr::App(f, v) => (Rc::as_ptr(f), Rc::as_ptr(v)),
+                body => panic!("expected an application, got {:?}", body),
+            },
+            term => panic!("expected a lambda, got {:?}", term),
+        };
+
+        let uses = resolve(&term);
+        assert_eq!(uses[&f_use], x);
+        assert_eq!(uses[&v_use], x);
+    }
+
+    #[test]
+    fn shadowing_resolves_to_the_innermost_binder() {
+        let outer = fresh("x");
+        let inner = fresh("x");
+        let term = lam(outer.clone(), lam(inner.clone(), var(&inner)));
+
+        let occurrence = match &term {
+            Expr::Lam(s) => match &*s.unsafe_body {
+                Expr::Lam(s) => Rc::as_ptr(&s.unsafe_body),
+                body => panic!("expected a lambda, got {:?}", body),
+            },
+            term => panic!("expected a lambda, got {:?}", term),
+        };
+
+        let uses = resolve(&term);
+        assert_eq!(uses[&occurrence], inner);
+        assert_ne!(uses[&occurrence], outer);
+    }
+
+    #[test]
+    fn free_variables_resolve_to_themselves() {
+        let halt = fresh("halt");
+        let term = var(&halt);
+
+        let uses = resolve(&term);
+        assert_eq!(uses[&(&term as *const Expr)], halt);
+    }
+}
@@ -0,0 +1,219 @@
+// An adapter from `lexpr`'s generic S-expression values, for embedders
+// whose sources already arrive parsed by the wider S-expression
+// ecosystem rather than by this crate's own front-ends. The conversion
+// understands
+//
+//   (lambda (x y ...) body)   curried `Expr::Lam`s
+//   (if c t e)                `Expr::If`
+//   (f a b ...)               left-nested `Expr::App`s
+//
+// plus the self-evaluating atoms: numbers, strings, characters,
+// booleans, and `()` for void. Symbols become variables — binders
+// introduced by an enclosing `lambda` resolve to them, and anything
+// else becomes a free variable shared by spelling across the
+// conversion, the same convention the other parsers use.
+
+use std::collections::HashMap;
+use std::rc::Rc;
+
+use moniker::{Binder, FreeVar, Ignore, Scope, Var};
+
+use crate::expr::Expr;
+use crate::literals::Literal;
+
+// `lexpr` values carry no source positions, so unlike the other parse
+// errors this one has no offset.
+#[derive(Debug)]
+pub struct ParseError {
+    pub message: String,
+}
+
+impl std::fmt::Display for ParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+pub fn from_sexpr(value: &lexpr::Value) -> Result<Expr, ParseError> {
+    let mut converter = Converter {
+        free: HashMap::new(),
+    };
+    converter.expr(value, &[])
+}
+
+fn error<T>(message: String) -> Result<T, ParseError> {
+    Err(ParseError { message })
+}
+
+// Walks a chain of cons cells into its elements; `None` for improper
+// lists, which have no meaning as programs.
+fn list_items(value: &lexpr::Value) -> Option<Vec<&lexpr::Value>> {
+    let mut items = Vec::new();
+    let mut rest = value;
+    loop {
+        match rest {
+            lexpr::Value::Cons(c) => {
+                items.push(c.car());
+                rest = c.cdr();
+            }
+            lexpr::Value::Nil | lexpr::Value::Null => return Some(items),
+            _ => return None,
+        }
+    }
+}
+
+struct Converter {
+    free: HashMap<String, FreeVar<String>>,
+}
+
+impl Converter {
+    fn expr(
+        &mut self,
+        value: &lexpr::Value,
+        env: &[(String, FreeVar<String>)],
+    ) -> Result<Expr, ParseError> {
+        match value {
+            lexpr::Value::Number(n) => self.number(n),
+            lexpr::Value::String(s) => Ok(Expr::Lit(Ignore(Literal::String(s.to_string())))),
+            lexpr::Value::Char(c) => Ok(Expr::Lit(Ignore(Literal::Char(*c)))),
+            lexpr::Value::Bool(b) => Ok(Expr::Lit(Ignore(Literal::Bool(*b)))),
+            lexpr::Value::Nil | lexpr::Value::Null => Ok(Expr::Lit(Ignore(Literal::Void))),
+            lexpr::Value::Symbol(name) => {
+                let var = match env.iter().rev().find(|(n, _)| n.as_str() == &**name) {
+                    Some((_, var)) => var.clone(),
+                    None => self
+                        .free
+                        .entry(name.to_string())
+                        .or_insert_with(|| FreeVar::fresh_named(name.to_string()))
+                        .clone(),
+                };
+                Ok(Expr::Var(Var::Free(var)))
+            }
+            lexpr::Value::Cons(_) => {
+                let items = list_items(value).ok_or_else(|| ParseError {
+                    message: "an improper list is not an expression".to_owned(),
+                })?;
+                self.form(&items, env)
+            }
+            other => error(format!("cannot convert {} into an expression", other)),
+        }
+    }
+
+    fn form(
+        &mut self,
+        items: &[&lexpr::Value],
+        env: &[(String, FreeVar<String>)],
+    ) -> Result<Expr, ParseError> {
+        match items {
+            [lexpr::Value::Symbol(head), rest @ ..] if &**head == "lambda" => {
+                let (params, body) = match rest {
+                    [params, body] => (params, body),
+                    _ => return error("lambda takes a parameter list and one body".to_owned()),
+                };
+
+                let params = list_items(params)
+                    .filter(|ps| !ps.is_empty())
+                    .ok_or_else(|| ParseError {
+                        message: "lambda needs a proper, non-empty parameter list".to_owned(),
+                    })?;
+
+                let mut env = env.to_vec();
+                let mut binders = Vec::new();
+                for param in params {
+                    let name = match param {
+                        lexpr::Value::Symbol(name) => name.to_string(),
+                        other => {
+                            return error(format!("{} is not a lambda parameter", other));
+                        }
+                    };
+                    let var = FreeVar::fresh_named(name.clone());
+                    env.push((name, var.clone()));
+                    binders.push(var);
+                }
+
+                let mut expr = self.expr(body, &env)?;
+                for var in binders.into_iter().rev() {
+                    expr = Expr::Lam(Scope::new(Binder(var), Rc::new(expr)));
+                }
+                Ok(expr)
+            }
+            [lexpr::Value::Symbol(head), rest @ ..] if &**head == "if" => match rest {
+                [c, t, e] => Ok(Expr::If(
+                    Rc::new(self.expr(c, env)?),
+                    Rc::new(self.expr(t, env)?),
+                    Rc::new(self.expr(e, env)?),
+                )),
+                _ => error("if takes a condition and two branches".to_owned()),
+            },
+            [func, args @ ..] => {
+                if args.is_empty() {
+                    return error("an application needs at least one argument".to_owned());
+                }
+                let mut expr = self.expr(func, env)?;
+                for arg in args {
+                    expr = Expr::App(Rc::new(expr), Rc::new(self.expr(arg, env)?));
+                }
+                Ok(expr)
+            }
+            [] => error("the empty list is not an expression".to_owned()),
+        }
+    }
+
+    fn number(&self, n: &lexpr::Number) -> Result<Expr, ParseError> {
+        if let Some(i) = n.as_u64() {
+            Ok(Expr::Lit(Ignore(Literal::Int(i))))
+        } else if let Some(i) = n.as_i64() {
+            // only reachable for negatives: non-negatives matched above
+            #[cfg(feature = "bignum")]
+            {
+                Ok(Expr::Lit(Ignore(Literal::BigInt(i.into()))))
+            }
+            #[cfg(not(feature = "bignum"))]
+            {
+                error(format!("the negative integer {} needs the bignum feature", i))
+            }
+        } else if let Some(f) = n.as_f64() {
+            Ok(Expr::Lit(Ignore(Literal::Float(f))))
+        } else {
+            error(format!("cannot convert the number {}", n))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::prelude::{church_num, AlphaEq};
+
+    #[test]
+    fn a_parsed_lambda_converts_to_the_matching_term() {
+        // the two-parameter lambda curries into nested `Lam`s
+        let value = lexpr::from_str("(lambda (f x) (f (f x)))").unwrap();
+
+        assert_eq!(AlphaEq(from_sexpr(&value).unwrap()), AlphaEq(church_num(2)));
+    }
+
+    #[test]
+    fn atoms_convert_to_literals() {
+        let value = lexpr::from_str(r#"(if #t "yes" 3)"#).unwrap();
+
+        match from_sexpr(&value).unwrap() {
+            Expr::If(c, t, e) => {
+                assert!(matches!(&*c, Expr::Lit(Ignore(Literal::Bool(true)))));
+                assert!(matches!(&*t, Expr::Lit(Ignore(Literal::String(s))) if s == "yes"));
+                assert!(matches!(&*e, Expr::Lit(Ignore(Literal::Int(3)))));
+            }
+            expr => panic!("expected an if, got {:?}", expr),
+        }
+    }
+
+    #[test]
+    fn an_improper_list_is_rejected() {
+        let value = lexpr::from_str("(f . 3)").unwrap();
+
+        let err = from_sexpr(&value).unwrap_err();
+        assert!(err.message.contains("improper list"));
+    }
+}
@@ -0,0 +1,832 @@
+// A concise, human-editable textual format for `FExpr`, so flattened
+// programs can be stored on disk and hand-edited. Every form is a
+// parenthesised list with a keyword head:
+//
+//   (lam1 x#0 body)          FExpr::LamOne
+//   (lam2 x#0 k#1 body)      FExpr::LamTwo
+//   (fix f#0 body)           FExpr::Fix
+//   (call1 f v)              FExpr::CallOne
+//   (call2 f v c)            FExpr::CallTwo
+//   (if c t e)               FExpr::If
+//   (prim assert "msg")      FExpr::Prim
+//   (prim binary add)
+//   (prim binary-with add 5)
+//   (prim apply)
+//   (prim not)
+//   (prim rest)
+//   (prim tuple 3)
+//   (prim proj 0)
+//
+// Binders print as `name#index`, with the index unique across the whole
+// term, so shadowed names stay unambiguous and the output is stable and
+// diffable. Parsing rebuilds the scopes, so a round trip is
+// alpha-equivalent rather than identical. Variables that are free in the
+// printed term come back as fresh free variables.
+
+use std::collections::HashMap;
+use std::rc::Rc;
+
+use moniker::{Binder, FreeVar, Ignore, Scope, Var};
+
+use crate::cont_expr::{BinOp, CastKind, PrimOp};
+use crate::flat_expr::FExpr;
+use crate::literals::Literal;
+#[derive(Debug)]
+pub struct PrintError(pub String);
+
+impl std::fmt::Display for PrintError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl std::error::Error for PrintError {}
+
+#[derive(Debug)]
+pub struct ParseError {
+    pub message: String,
+    // byte offset into the input where the error was noticed
+    pub offset: usize,
+}
+
+impl std::fmt::Display for ParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{} at byte {}", self.message, self.offset)
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+pub fn print(expr: &FExpr) -> Result<String, PrintError> {
+    let mut printer = Printer {
+        out: String::new(),
+        counter: 0,
+    };
+    printer.expr(expr, &[])?;
+    Ok(printer.out)
+}
+
+pub fn parse(input: &str) -> Result<FExpr, ParseError> {
+    #[cfg(feature = "tracing")]
+    let _span = tracing::debug_span!("parse", input_len = input.len()).entered();
+
+    let tokens = tokenize(input)?;
+    let mut parser = Parser {
+        tokens,
+        pos: 0,
+        input_len: input.len(),
+        free: HashMap::new(),
+    };
+    let expr = parser.expr(&[])?;
+    if parser.pos != parser.tokens.len() {
+        return Err(ParseError {
+            message: "trailing input after expression".to_owned(),
+            offset: parser.tokens[parser.pos].0,
+        });
+    }
+    Ok(expr)
+}
+
+// Deterministic spellings for `FreeVar`s in diagnostics and assertion
+// messages. `FreeVar`'s own debug output exposes its global unique id,
+// which changes from run to run; a table assigns each variable its
+// pretty name instead, suffixing `#1`, `#2`, … in first-seen order only
+// when a second distinct variable shares the name. The spelling is
+// stable: asking for the same variable again always answers the same
+// string, and a fixed registration order gives a fixed output.
+#[derive(Debug, Default)]
+pub struct NameTable {
+    assigned: HashMap<FreeVar<String>, String>,
+    taken: HashMap<String, usize>,
+}
+
+impl NameTable {
+    pub fn new() -> NameTable {
+        NameTable::default()
+    }
+
+    pub fn name(&mut self, v: &FreeVar<String>) -> String {
+        if let Some(spelled) = self.assigned.get(v) {
+            return spelled.clone();
+        }
+
+        let base = v
+            .pretty_name
+            .clone()
+            .unwrap_or_else(|| "_".to_owned());
+        let seen = self.taken.entry(base.clone()).or_insert(0);
+        let spelled = if *seen == 0 {
+            base
+        } else {
+            format!("{}#{}", base, seen)
+        };
+        *seen += 1;
+        self.assigned.insert(v.clone(), spelled.clone());
+        spelled
+    }
+}
+
+struct Printer {
+    out: String,
+    counter: usize,
+}
+
+impl Printer {
+    // env maps each in-scope binder to its printed `name#index` spelling
+    fn expr(&mut self, expr: &FExpr, env: &[(FreeVar<String>, String)]) -> Result<(), PrintError> {
+        match expr {
+            FExpr::LamOne(s) => {
+                let (Binder(param), body) = s.clone().unbind();
+                let name = self.binder(&param);
+
+                self.out.push_str("(lam1 ");
+                self.out.push_str(&name);
+                self.out.push(' ');
+                let env = push(env, param, name);
+                self.expr(&body, &env)?;
+                self.out.push(')');
+            }
+            FExpr::LamTwo(s) => {
+                let (Binder(param), inner) = s.clone().unbind();
+                let (Binder(cont), body) = inner.unbind();
+                let p_name = self.binder(&param);
+                let k_name = self.binder(&cont);
+
+                self.out.push_str("(lam2 ");
+                self.out.push_str(&p_name);
+                self.out.push(' ');
+                self.out.push_str(&k_name);
+                self.out.push(' ');
+                let env = push(&push(env, param, p_name), cont, k_name);
+                self.expr(&body, &env)?;
+                self.out.push(')');
+            }
+            FExpr::Fix(s) => {
+                let (Binder(f), body) = s.clone().unbind();
+                let name = self.binder(&f);
+
+                self.out.push_str("(fix ");
+                self.out.push_str(&name);
+                self.out.push(' ');
+                let env = push(env, f, name);
+                self.expr(&body, &env)?;
+                self.out.push(')');
+            }
+            FExpr::Var(Var::Free(fv)) => match env.iter().rev().find(|(v, _)| v == fv) {
+                Some((_, name)) => self.out.push_str(name),
+                None => self
+                    .out
+                    .push_str(fv.pretty_name.as_deref().unwrap_or("_")),
+            },
+            FExpr::Var(v @ Var::Bound(_)) => {
+                return Err(PrintError(format!(
+                    "bound variable {} outside its scope",
+                    v
+                )))
+            }
+            FExpr::Lit(Ignore(l)) => self.literal(l)?,
+            FExpr::Prim(Ignore(p)) => match p {
+                PrimOp::Assert(msg) => {
+                    self.out.push_str("(prim assert ");
+                    self.out.push_str(&format!("{:?}", msg));
+                    self.out.push(')');
+                }
+                PrimOp::Binary(op) => {
+                    self.out.push_str(&format!("(prim binary {})", op));
+                }
+                PrimOp::BinaryWith(op, l) => {
+                    self.out.push_str(&format!("(prim binary-with {} ", op));
+                    self.literal(l)?;
+                    self.out.push(')');
+                }
+                PrimOp::Not => {
+                    self.out.push_str("(prim not)");
+                }
+                PrimOp::Cast(kind) => {
+                    self.out.push_str(&format!("(prim cast {})", kind));
+                }
+                PrimOp::Delay => {
+                    self.out.push_str("(prim delay)");
+                }
+                PrimOp::Force => {
+                    self.out.push_str("(prim force)");
+                }
+                PrimOp::Finally => {
+                    self.out.push_str("(prim finally)");
+                }
+                PrimOp::Amb => {
+                    self.out.push_str("(prim amb)");
+                }
+                PrimOp::Fail => {
+                    self.out.push_str("(prim fail)");
+                }
+                PrimOp::Rest => {
+                    self.out.push_str("(prim rest)");
+                }
+                PrimOp::Apply => {
+                    self.out.push_str("(prim apply)");
+                }
+                PrimOp::ApplyWith(l) => {
+                    self.out.push_str("(prim apply-with ");
+                    self.literal(l)?;
+                    self.out.push(')');
+                }
+                PrimOp::Tuple(n) => {
+                    self.out.push_str(&format!("(prim tuple {})", n));
+                }
+                PrimOp::Proj(i) => {
+                    self.out.push_str(&format!("(prim proj {})", i));
+                }
+            },
+            FExpr::CallOne(f, v) => {
+                self.out.push_str("(call1 ");
+                self.expr(f, env)?;
+                self.out.push(' ');
+                self.expr(v, env)?;
+                self.out.push(')');
+            }
+            FExpr::CallTwo(f, v, c) => {
+                self.out.push_str("(call2 ");
+                self.expr(f, env)?;
+                self.out.push(' ');
+                self.expr(v, env)?;
+                self.out.push(' ');
+                self.expr(c, env)?;
+                self.out.push(')');
+            }
+            FExpr::If(c, t, e) => {
+                self.out.push_str("(if ");
+                self.expr(c, env)?;
+                self.out.push(' ');
+                self.expr(t, env)?;
+                self.out.push(' ');
+                self.expr(e, env)?;
+                self.out.push(')');
+            }
+        }
+
+        Ok(())
+    }
+
+    fn binder(&mut self, var: &FreeVar<String>) -> String {
+        let name = format!(
+            "{}#{}",
+            var.pretty_name.as_deref().unwrap_or("_"),
+            self.counter
+        );
+        self.counter += 1;
+        name
+    }
+
+    fn literal(&mut self, lit: &Literal) -> Result<(), PrintError> {
+        match lit {
+            // {:?} on floats and strings round-trips exactly, and a float
+            // always contains a `.`, `e`, or `nan`/`inf`, so the parser
+            // can tell it from an int
+            Literal::String(s) => self.out.push_str(&format!("{:?}", s)),
+            Literal::Char(c) => self.out.push_str(&format!("{:?}", c)),
+            Literal::Int(i) => self.out.push_str(&i.to_string()),
+            #[cfg(feature = "bignum")]
+            Literal::BigInt(i) => self.out.push_str(&i.to_string()),
+            // always `num/den`, so the parser can tell it from an int
+            #[cfg(feature = "rational")]
+            Literal::Rational(r) => {
+                self.out.push_str(&format!("{}/{}", r.numer(), r.denom()))
+            }
+            Literal::Float(f) => self.out.push_str(&format!("{:?}", f)),
+            Literal::Bool(b) => self.out.push_str(&b.to_string()),
+            Literal::Void => self.out.push_str("void"),
+            Literal::Quoted(_) => {
+                return Err(PrintError(
+                    "quoted expressions have no textual form".to_owned(),
+                ))
+            }
+            Literal::List(_) => {
+                return Err(PrintError("lists have no textual form".to_owned()))
+            }
+        }
+
+        Ok(())
+    }
+}
+
+fn push(
+    env: &[(FreeVar<String>, String)],
+    var: FreeVar<String>,
+    name: String,
+) -> Vec<(FreeVar<String>, String)> {
+    let mut env = env.to_vec();
+    env.push((var, name));
+    env
+}
+
+#[derive(Debug, Clone)]
+enum Token {
+    LParen,
+    RParen,
+    Atom(String),
+    Str(String),
+    Char(char),
+}
+
+fn tokenize(input: &str) -> Result<Vec<(usize, Token)>, ParseError> {
+    let mut tokens = Vec::new();
+    let mut chars = input.char_indices().peekable();
+
+    while let Some(&(offset, c)) = chars.peek() {
+        match c {
+            c if c.is_whitespace() => {
+                chars.next();
+            }
+            '(' => {
+                chars.next();
+                tokens.push((offset, Token::LParen));
+            }
+            ')' => {
+                chars.next();
+                tokens.push((offset, Token::RParen));
+            }
+            '"' => {
+                chars.next();
+                let mut s = String::new();
+                loop {
+                    match chars.next() {
+                        Some((_, '"')) => break,
+                        Some((esc_at, '\\')) => s.push(unescape(chars.next(), esc_at)?),
+                        Some((_, c)) => s.push(c),
+                        None => {
+                            return Err(ParseError {
+                                message: "unterminated string literal".to_owned(),
+                                offset,
+                            })
+                        }
+                    }
+                }
+                tokens.push((offset, Token::Str(s)));
+            }
+            '\'' => {
+                chars.next();
+                let c = match chars.next() {
+                    Some((esc_at, '\\')) => unescape(chars.next(), esc_at)?,
+                    Some((_, c)) => c,
+                    None => {
+                        return Err(ParseError {
+                            message: "unterminated char literal".to_owned(),
+                            offset,
+                        })
+                    }
+                };
+                match chars.next() {
+                    Some((_, '\'')) => {}
+                    _ => {
+                        return Err(ParseError {
+                            message: "unterminated char literal".to_owned(),
+                            offset,
+                        })
+                    }
+                }
+                tokens.push((offset, Token::Char(c)));
+            }
+            _ => {
+                let mut atom = String::new();
+                while let Some(&(_, c)) = chars.peek() {
+                    if c.is_whitespace() || c == '(' || c == ')' {
+                        break;
+                    }
+                    atom.push(c);
+                    chars.next();
+                }
+                tokens.push((offset, Token::Atom(atom)));
+            }
+        }
+    }
+
+    Ok(tokens)
+}
+
+fn unescape(next: Option<(usize, char)>, offset: usize) -> Result<char, ParseError> {
+    match next {
+        Some((_, 'n')) => Ok('\n'),
+        Some((_, 't')) => Ok('\t'),
+        Some((_, 'r')) => Ok('\r'),
+        Some((_, '0')) => Ok('\0'),
+        Some((_, c @ ('\\' | '"' | '\''))) => Ok(c),
+        Some((_, c)) => Err(ParseError {
+            message: format!("unknown escape \\{}", c),
+            offset,
+        }),
+        None => Err(ParseError {
+            message: "dangling escape".to_owned(),
+            offset,
+        }),
+    }
+}
+
+struct Parser {
+    tokens: Vec<(usize, Token)>,
+    pos: usize,
+    input_len: usize,
+    // genuinely free variables, shared across occurrences so the parsed
+    // term stays well-scoped
+    free: HashMap<String, FreeVar<String>>,
+}
+
+impl Parser {
+    // env maps printed binder spellings back to the free vars this parse
+    // invented for them; innermost binding wins
+    fn expr(&mut self, env: &[(String, FreeVar<String>)]) -> Result<FExpr, ParseError> {
+        let (offset, token) = self.next()?;
+        match token {
+            Token::Str(s) => Ok(FExpr::Lit(Ignore(Literal::String(s)))),
+            Token::Char(c) => Ok(FExpr::Lit(Ignore(Literal::Char(c)))),
+            Token::Atom(atom) => self.atom(atom, offset, env),
+            Token::LParen => {
+                let head = match self.next()? {
+                    (_, Token::Atom(head)) => head,
+                    (offset, _) => {
+                        return Err(ParseError {
+                            message: "expected a keyword after `(`".to_owned(),
+                            offset,
+                        })
+                    }
+                };
+
+                let expr = match head.as_str() {
+                    "lam1" => {
+                        let (name, var) = self.binder()?;
+                        let body = self.expr(&parse_push(env, name, var.clone()))?;
+                        FExpr::LamOne(Scope::new(Binder(var), Rc::new(body)))
+                    }
+                    "lam2" => {
+                        let (p_name, p_var) = self.binder()?;
+                        let (k_name, k_var) = self.binder()?;
+                        let env = parse_push(
+                            &parse_push(env, p_name, p_var.clone()),
+                            k_name,
+                            k_var.clone(),
+                        );
+                        let body = self.expr(&env)?;
+                        FExpr::LamTwo(Scope::new(
+                            Binder(p_var),
+                            Scope::new(Binder(k_var), Rc::new(body)),
+                        ))
+                    }
+                    "fix" => {
+                        let (name, var) = self.binder()?;
+                        let body = self.expr(&parse_push(env, name, var.clone()))?;
+                        FExpr::Fix(Scope::new(Binder(var), Rc::new(body)))
+                    }
+                    "call1" => {
+                        let f = self.expr(env)?;
+                        let v = self.expr(env)?;
+                        FExpr::CallOne(Rc::new(f), Rc::new(v))
+                    }
+                    "call2" => {
+                        let f = self.expr(env)?;
+                        let v = self.expr(env)?;
+                        let c = self.expr(env)?;
+                        FExpr::CallTwo(Rc::new(f), Rc::new(v), Rc::new(c))
+                    }
+                    "if" => {
+                        let c = self.expr(env)?;
+                        let t = self.expr(env)?;
+                        let e = self.expr(env)?;
+                        FExpr::If(Rc::new(c), Rc::new(t), Rc::new(e))
+                    }
+                    "prim" => FExpr::Prim(Ignore(self.prim()?)),
+                    head => {
+                        return Err(ParseError {
+                            message: format!("unknown form ({} ...)", head),
+                            offset,
+                        })
+                    }
+                };
+
+                self.expect_rparen()?;
+                Ok(expr)
+            }
+            Token::RParen => Err(ParseError {
+                message: "unexpected `)`".to_owned(),
+                offset,
+            }),
+        }
+    }
+
+    fn atom(
+        &mut self,
+        atom: String,
+        offset: usize,
+        env: &[(String, FreeVar<String>)],
+    ) -> Result<FExpr, ParseError> {
+        if let Some(lit) = parse_literal(&atom) {
+            return Ok(FExpr::Lit(Ignore(lit)));
+        }
+
+        if atom.contains('#') {
+            return match env.iter().rev().find(|(name, _)| *name == atom) {
+                Some((_, var)) => Ok(FExpr::Var(Var::Free(var.clone()))),
+                None => Err(ParseError {
+                    message: format!("binder reference {} is not in scope", atom),
+                    offset,
+                }),
+            };
+        }
+
+        let var = self
+            .free
+            .entry(atom.clone())
+            .or_insert_with(|| FreeVar::fresh_named(atom))
+            .clone();
+        Ok(FExpr::Var(Var::Free(var)))
+    }
+
+    fn binder(&mut self) -> Result<(String, FreeVar<String>), ParseError> {
+        match self.next()? {
+            (_, Token::Atom(name)) => {
+                let pretty = name.split('#').next().unwrap_or("_").to_owned();
+                let var = FreeVar::fresh_named(pretty);
+                Ok((name, var))
+            }
+            (offset, _) => Err(ParseError {
+                message: "expected a binder".to_owned(),
+                offset,
+            }),
+        }
+    }
+
+    fn prim(&mut self) -> Result<PrimOp, ParseError> {
+        match self.next()? {
+            (_, Token::Atom(kind)) if kind == "assert" => match self.next()? {
+                (_, Token::Str(msg)) => Ok(PrimOp::Assert(msg)),
+                (offset, _) => Err(ParseError {
+                    message: "expected an assertion message string".to_owned(),
+                    offset,
+                }),
+            },
+            (offset, Token::Atom(kind)) if kind == "binary" => {
+                Ok(PrimOp::Binary(self.bin_op(offset)?))
+            }
+            (offset, Token::Atom(kind)) if kind == "binary-with" => {
+                let op = self.bin_op(offset)?;
+                Ok(PrimOp::BinaryWith(op, self.literal()?))
+            }
+            (_, Token::Atom(kind)) if kind == "not" => Ok(PrimOp::Not),
+            (_, Token::Atom(kind)) if kind == "cast" => match self.next()? {
+                (_, Token::Atom(which)) if which == "int->float" => {
+                    Ok(PrimOp::Cast(CastKind::IntToFloat))
+                }
+                (_, Token::Atom(which)) if which == "int->string" => {
+                    Ok(PrimOp::Cast(CastKind::IntToString))
+                }
+                (_, Token::Atom(which)) if which == "bool->int" => {
+                    Ok(PrimOp::Cast(CastKind::BoolToInt))
+                }
+                (offset, _) => Err(ParseError {
+                    message: "expected int->float, int->string, or bool->int".to_owned(),
+                    offset,
+                }),
+            },
+            (_, Token::Atom(kind)) if kind == "delay" => Ok(PrimOp::Delay),
+            (_, Token::Atom(kind)) if kind == "force" => Ok(PrimOp::Force),
+            (_, Token::Atom(kind)) if kind == "finally" => Ok(PrimOp::Finally),
+            (_, Token::Atom(kind)) if kind == "amb" => Ok(PrimOp::Amb),
+            (_, Token::Atom(kind)) if kind == "fail" => Ok(PrimOp::Fail),
+            (_, Token::Atom(kind)) if kind == "rest" => Ok(PrimOp::Rest),
+            (_, Token::Atom(kind)) if kind == "apply" => Ok(PrimOp::Apply),
+            (_, Token::Atom(kind)) if kind == "apply-with" => {
+                Ok(PrimOp::ApplyWith(self.literal()?))
+            }
+            (offset, Token::Atom(kind)) if kind == "tuple" => Ok(PrimOp::Tuple(self.index(offset)?)),
+            (offset, Token::Atom(kind)) if kind == "proj" => Ok(PrimOp::Proj(self.index(offset)?)),
+            (offset, _) => Err(ParseError {
+                message:
+                    "expected assert, binary, binary-with, not, cast, delay, force, finally, amb, fail, rest, apply, apply-with, tuple, or proj"
+                        .to_owned(),
+                offset,
+            }),
+        }
+    }
+
+    fn literal(&mut self) -> Result<Literal, ParseError> {
+        match self.next()? {
+            (_, Token::Str(s)) => Ok(Literal::String(s)),
+            (_, Token::Char(c)) => Ok(Literal::Char(c)),
+            (offset, Token::Atom(atom)) => match parse_literal(&atom) {
+                Some(lit) => Ok(lit),
+                None => Err(ParseError {
+                    message: format!("expected a literal, got {}", atom),
+                    offset,
+                }),
+            },
+            (offset, _) => Err(ParseError {
+                message: "expected a literal".to_owned(),
+                offset,
+            }),
+        }
+    }
+
+    fn index(&mut self, offset: usize) -> Result<usize, ParseError> {
+        match self.literal()? {
+            Literal::Int(n) => Ok(n as usize),
+            l => Err(ParseError {
+                message: format!("expected a tuple arity or index, got {:?}", l),
+                offset,
+            }),
+        }
+    }
+
+    fn bin_op(&mut self, offset: usize) -> Result<BinOp, ParseError> {
+        match self.next()? {
+            (offset, Token::Atom(op)) => match op.as_str() {
+                "char-at" => Ok(BinOp::CharAt),
+                "div" => Ok(BinOp::Div),
+                "add" => Ok(BinOp::Add),
+                "sub" => Ok(BinOp::Sub),
+                "mul" => Ok(BinOp::Mul),
+                "eq" => Ok(BinOp::Eq),
+                op => Err(ParseError {
+                    message: format!("unknown binary operator {}", op),
+                    offset,
+                }),
+            },
+            _ => Err(ParseError {
+                message: "expected a binary operator".to_owned(),
+                offset,
+            }),
+        }
+    }
+
+    fn next(&mut self) -> Result<(usize, Token), ParseError> {
+        if self.pos >= self.tokens.len() {
+            return Err(ParseError {
+                message: "unexpected end of input".to_owned(),
+                offset: self.input_len,
+            });
+        }
+        let (offset, ref token) = self.tokens[self.pos];
+        self.pos += 1;
+        Ok((offset, token.clone()))
+    }
+
+    fn expect_rparen(&mut self) -> Result<(), ParseError> {
+        match self.next()? {
+            (_, Token::RParen) => Ok(()),
+            (offset, _) => Err(ParseError {
+                message: "expected `)`".to_owned(),
+                offset,
+            }),
+        }
+    }
+}
+
+fn parse_push(
+    env: &[(String, FreeVar<String>)],
+    name: String,
+    var: FreeVar<String>,
+) -> Vec<(String, FreeVar<String>)> {
+    let mut env = env.to_vec();
+    env.push((name, var));
+    env
+}
+
+fn parse_literal(atom: &str) -> Option<Literal> {
+    match atom {
+        "true" => return Some(Literal::Bool(true)),
+        "false" => return Some(Literal::Bool(false)),
+        "void" => return Some(Literal::Void),
+        _ => {}
+    }
+
+    if atom.chars().all(|c| c.is_ascii_digit()) {
+        if let Ok(i) = atom.parse() {
+            return Some(Literal::Int(i));
+        }
+        // too wide for the fixed-width type
+        #[cfg(feature = "bignum")]
+        return atom.parse().ok().map(Literal::BigInt);
+    }
+
+    #[cfg(feature = "bignum")]
+    if let Some(rest) = atom.strip_prefix('-') {
+        if !rest.is_empty() && rest.chars().all(|c| c.is_ascii_digit()) {
+            return atom.parse().ok().map(Literal::BigInt);
+        }
+    }
+
+    // `num/den`, optionally signed; a zero denominator is no literal at
+    // all (`Ratio`'s parser would panic on it)
+    #[cfg(feature = "rational")]
+    if let Some((n, d)) = atom.strip_prefix('-').unwrap_or(atom).split_once('/') {
+        if !n.is_empty()
+            && n.chars().all(|c| c.is_ascii_digit())
+            && !d.is_empty()
+            && d.chars().all(|c| c.is_ascii_digit())
+            && d.chars().any(|c| c != '0')
+        {
+            return atom.parse().ok().map(|r| Literal::Rational(Box::new(r)));
+        }
+    }
+
+    // floats always print with a `.`, an exponent, or as inf/NaN, so a
+    // bare digit run above can never be one
+    if atom.starts_with(|c: char| c.is_ascii_digit() || c == '-')
+        && atom.contains(['.', 'e', 'E'])
+    {
+        return atom.parse().ok().map(Literal::Float);
+    }
+
+    if atom == "inf" || atom == "-inf" || atom == "NaN" {
+        return atom.parse().ok().map(Literal::Float);
+    }
+
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cont_expr::{t_k, KExpr};
+    use crate::prelude::{app, lam, lit, var};
+    use moniker::BoundTerm;
+
+    #[test]
+    fn flattened_programs_round_trip_through_text() {
+        let x = FreeVar::fresh_named("x");
+        let y = FreeVar::fresh_named("y");
+        let halt = FreeVar::fresh_named("halt");
+
+        let expr = app(
+            lam(x.clone(), lam(y.clone(), var(&x))),
+            lit(Literal::Int(5)),
+        );
+        // close over the halt continuation: free variables come back as
+        // fresh vars, so only closed terms round-trip alpha-equivalently
+        let flat = FExpr::LamOne(Scope::new(
+            Binder(halt.clone()),
+            Rc::new(t_k(expr, Rc::new(KExpr::Var(Var::Free(halt)))).into()),
+        ));
+
+        let text = print(&flat).unwrap();
+        let reparsed = parse(&text).unwrap();
+
+        assert!(FExpr::term_eq(&flat, &reparsed));
+    }
+
+    #[test]
+    fn shadowed_binders_stay_distinct() {
+        let text = "(lam1 x#0 (lam1 x#1 x#0))";
+        let parsed = parse(text).unwrap();
+
+        let outer = FreeVar::fresh_named("x");
+        let inner = FreeVar::fresh_named("x");
+        let expected = FExpr::LamOne(Scope::new(
+            Binder(outer.clone()),
+            Rc::new(FExpr::LamOne(Scope::new(
+                Binder(inner),
+                Rc::new(FExpr::Var(Var::Free(outer))),
+            ))),
+        ));
+
+        assert!(FExpr::term_eq(&parsed, &expected));
+    }
+
+    #[test]
+    fn parse_errors_carry_an_offset() {
+        let err = parse("(lam1 x#0 y#9)").unwrap_err();
+        assert!(err.message.contains("y#9"));
+        assert_eq!(err.offset, 10);
+    }
+
+    #[test]
+    fn literals_and_prims_survive_a_round_trip() {
+        let expr = FExpr::CallTwo(
+            Rc::new(FExpr::Prim(Ignore(PrimOp::BinaryWith(
+                BinOp::Add,
+                Literal::Int(2),
+            )))),
+            Rc::new(FExpr::Lit(Ignore(Literal::Float(1.5)))),
+            Rc::new(FExpr::Lit(Ignore(Literal::String("a b".to_owned())))),
+        );
+
+        let text = print(&expr).unwrap();
+        let reparsed = parse(&text).unwrap();
+
+        assert!(FExpr::term_eq(&expr, &reparsed));
+    }
+
+    #[test]
+    fn same_named_variables_get_stable_suffixes() {
+        let a = FreeVar::fresh_named("x");
+        let b = FreeVar::fresh_named("x");
+        let c = FreeVar::fresh_named("y");
+
+        let mut names = NameTable::new();
+        assert_eq!(names.name(&a), "x");
+        assert_eq!(names.name(&b), "x#1");
+        assert_eq!(names.name(&c), "y");
+
+        // the spelling never changes once assigned
+        assert_eq!(names.name(&b), "x#1");
+        assert_eq!(names.name(&a), "x");
+    }
+}
@@ -3,3 +3,13 @@ use std::rc::Rc;
 pub fn clone_rc<T: Clone>(r: Rc<T>) -> T {
     Rc::try_unwrap(r).unwrap_or_else(|t| t.as_ref().clone())
 }
+
+// The terms we work with can get very deep (t_k on a long application
+// spine produces a continuation chain as deep as the input), so anything
+// that recurses structurally needs its stack guarded.
+const STACK_RED_ZONE: usize = 64 * 1024;
+const STACK_GROW_SIZE: usize = 4 * 1024 * 1024;
+
+pub(crate) fn grow_stack<R>(f: impl FnOnce() -> R) -> R {
+    stacker::maybe_grow(STACK_RED_ZONE, STACK_GROW_SIZE, f)
+}
@@ -0,0 +1,553 @@
+// A WebAssembly text (WAT) backend for the first-order integer subset
+// of `FExpr`: recursive functions over machine integers, arithmetic,
+// comparison, and `if`. The input is the CPS that `t_k` produces, and
+// the backend recognises its shapes directly instead of reconstructing
+// direct style —
+//
+//   (call1 (lam1 x rest) v)       a let: v lands in a local, or becomes
+//                                 a wasm function when it is a `fix` or
+//                                 a `lam2`
+//   (call2 (prim binary op) a
+//     (lam1 p (call2 p b k)))     the two-stage primitive application
+//   (call2 f v k)                 a first-order call
+//   (call1 k v)                   sending v through a continuation
+//   (if c t e)
+//
+// Every script function compiles to a wasm function of type
+// `i64 -> i64`; the program's halt continuation becomes the return of
+// an exported `$main`. Script integers are `u64`, so arithmetic maps
+// onto the wrapping `i64` instructions with unsigned division — the
+// bignum promotion the interpreter performs has no wasm counterpart.
+// Comparisons produce wasm's `i32` booleans, which may be stored in
+// locals and tested by `if` but cannot be a function's result.
+//
+// Anything outside the subset — higher-order values, closures over
+// locals, non-integer literals, the remaining primitives — is reported
+// as a `CompileError` rather than miscompiled.
+
+use std::collections::HashMap;
+use std::rc::Rc;
+
+use moniker::{Binder, FreeVar, Ignore, Var};
+
+use crate::cont_expr::{BinOp, PrimOp};
+use crate::flat_expr::FExpr;
+use crate::literals::Literal;
+use crate::utils::{clone_rc, grow_stack};
+
+#[derive(Debug)]
+pub struct CompileError(pub String);
+
+impl std::fmt::Display for CompileError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl std::error::Error for CompileError {}
+
+fn error<T>(message: String) -> Result<T, CompileError> {
+    Err(CompileError(message))
+}
+
+// The two value types the subset needs: script integers and the `i32`
+// booleans wasm comparisons produce.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Ty {
+    I64,
+    I32,
+}
+
+impl Ty {
+    fn name(self) -> &'static str {
+        match self {
+            Ty::I64 => "i64",
+            Ty::I32 => "i32",
+        }
+    }
+}
+
+// What a variable in scope compiles to.
+#[derive(Clone)]
+enum Binding {
+    // a local (or parameter) of the function being emitted
+    Local(usize, Ty),
+    // a variable let-bound to a `fix`/`lam2`, callable by name
+    Func(String),
+    // the current function's continuation parameter: reaching it with a
+    // value means returning that value
+    Return,
+    // a local of an enclosing function; kept around so a use reports
+    // closure capture instead of an unbound variable
+    Captured,
+}
+
+type Env = HashMap<FreeVar<String>, Binding>;
+
+fn spelled(v: &FreeVar<String>) -> &str {
+    v.pretty_name.as_deref().unwrap_or("_")
+}
+
+// Compiles `program` to the text of a wasm module. `halt` is the free
+// variable standing for the program's final continuation — the same one
+// handed to `t_k` — and the value it receives becomes the result of the
+// module's exported `main`.
+pub fn compile(program: &FExpr, halt: &FreeVar<String>) -> Result<String, CompileError> {
+    let mut compiler = Compiler::new();
+    let mut env = Env::new();
+    env.insert(halt.clone(), Binding::Return);
+
+    let mut main = FuncBuilder::new("$main".to_owned(), 0);
+    compiler.stmt(program, &env, &mut main)?;
+    compiler.funcs.push(main.finish());
+
+    let mut out = String::from("(module\n");
+    for func in &compiler.funcs {
+        out.push_str(func);
+    }
+    out.push_str("  (export \"main\" (func $main))\n)\n");
+    Ok(out)
+}
+
+// Accumulates one wasm function: parameters and locals share an index
+// space, parameters first, and the body is a flat instruction sequence.
+// Every path through a body ends in `return`, so `finish` caps it with
+// `unreachable` to satisfy validation.
+struct FuncBuilder {
+    name: String,
+    params: usize,
+    locals: Vec<Ty>,
+    body: String,
+    indent: usize,
+}
+
+impl FuncBuilder {
+    fn new(name: String, params: usize) -> FuncBuilder {
+        FuncBuilder {
+            name,
+            params,
+            locals: Vec::new(),
+            body: String::new(),
+            indent: 0,
+        }
+    }
+
+    fn push(&mut self, instr: &str) {
+        for _ in 0..self.indent + 2 {
+            self.body.push_str("  ");
+        }
+        self.body.push_str(instr);
+        self.body.push('\n');
+    }
+
+    fn new_local(&mut self, ty: Ty) -> usize {
+        let index = self.params + self.locals.len();
+        self.locals.push(ty);
+        index
+    }
+
+    fn finish(self) -> String {
+        let mut out = format!("  (func {}", self.name);
+        for _ in 0..self.params {
+            out.push_str(" (param i64)");
+        }
+        out.push_str(" (result i64)\n");
+        if !self.locals.is_empty() {
+            out.push_str("    (local");
+            for ty in &self.locals {
+                out.push(' ');
+                out.push_str(ty.name());
+            }
+            out.push_str(")\n");
+        }
+        out.push_str(&self.body);
+        out.push_str("    unreachable)\n");
+        out
+    }
+}
+
+struct Compiler {
+    funcs: Vec<String>,
+    // wasm function names already handed out, by sanitized base name
+    taken: HashMap<String, usize>,
+}
+
+impl Compiler {
+    fn new() -> Compiler {
+        let mut taken = HashMap::new();
+        // `$main` belongs to the entry point
+        taken.insert("main".to_owned(), 1);
+        Compiler {
+            funcs: Vec::new(),
+            taken,
+        }
+    }
+
+    // Compiles a call tree in statement position: every leaf hands a
+    // value to some continuation, so every path emitted here ends in
+    // `return`.
+    fn stmt(&mut self, expr: &FExpr, env: &Env, fb: &mut FuncBuilder) -> Result<(), CompileError> {
+        grow_stack(|| match expr {
+            FExpr::CallOne(k, v) => {
+                // a let whose right-hand side is a function — inline or
+                // a variable already naming one — binds a wasm function
+                // rather than a local
+                if let FExpr::LamOne(s) = &**k {
+                    let name = match &**v {
+                        FExpr::Fix(_) | FExpr::LamTwo(_) => Some(self.function(v, env)?),
+                        FExpr::Var(Var::Free(fv)) => match env.get(fv) {
+                            Some(Binding::Func(name)) => Some(name.clone()),
+                            _ => None,
+                        },
+                        _ => None,
+                    };
+                    if let Some(name) = name {
+                        let (Binder(x), rest) = s.clone().unbind();
+                        let mut env = env.clone();
+                        env.insert(x, Binding::Func(name));
+                        return self.stmt(&rest, &env, fb);
+                    }
+                }
+                let ty = push_value(v, env, fb)?;
+                self.continue_with(k, ty, env, fb)
+            }
+            FExpr::CallTwo(f, v, k) => match &**f {
+                FExpr::Prim(Ignore(PrimOp::Binary(op))) => {
+                    // the two-stage shape: `k` receives the partial
+                    // application and immediately applies it to the
+                    // second operand
+                    let s = match &**k {
+                        FExpr::LamOne(s) => s,
+                        _ => {
+                            return error(
+                                "a binary primitive must be applied to both operands in the \
+                                 shape `t_k` produces"
+                                    .to_owned(),
+                            )
+                        }
+                    };
+                    let (Binder(p), rest) = s.clone().unbind();
+                    let (rhs, k2) = match &*rest {
+                        FExpr::CallTwo(pf, rhs, k2)
+                            if matches!(&**pf, FExpr::Var(Var::Free(pv)) if *pv == p) =>
+                        {
+                            (rhs, k2)
+                        }
+                        _ => {
+                            return error(
+                                "a binary primitive must be applied to both operands in the \
+                                 shape `t_k` produces"
+                                    .to_owned(),
+                            )
+                        }
+                    };
+                    let ty = bin_op(*op, v, rhs, env, fb)?;
+                    self.continue_with(k2, ty, env, fb)
+                }
+                FExpr::Prim(Ignore(PrimOp::BinaryWith(op, lit))) => {
+                    let rhs = FExpr::Lit(Ignore(lit.clone()));
+                    let ty = bin_op(*op, v, &Rc::new(rhs), env, fb)?;
+                    self.continue_with(k, ty, env, fb)
+                }
+                FExpr::Prim(Ignore(p)) => {
+                    error(format!("the primitive {} has no wasm translation", p))
+                }
+                _ => {
+                    let name = self.callee(f, env)?;
+                    if push_value(v, env, fb)? != Ty::I64 {
+                        return error("a function argument must be an integer".to_owned());
+                    }
+                    fb.push(&format!("call {}", name));
+                    self.continue_with(k, Ty::I64, env, fb)
+                }
+            },
+            FExpr::If(c, t, e) => {
+                if push_value(c, env, fb)? != Ty::I32 {
+                    return error("the condition of an if must be a boolean".to_owned());
+                }
+                fb.push("if");
+                fb.indent += 1;
+                self.stmt(t, env, fb)?;
+                fb.indent -= 1;
+                fb.push("else");
+                fb.indent += 1;
+                self.stmt(e, env, fb)?;
+                fb.indent -= 1;
+                fb.push("end");
+                Ok(())
+            }
+            _ => error("a value cannot appear in statement position".to_owned()),
+        })
+    }
+
+    // Sends the value on top of the wasm operand stack through the
+    // continuation `k`: either return it, or store it in a fresh local
+    // and carry on with the continuation's body.
+    fn continue_with(
+        &mut self,
+        k: &FExpr,
+        ty: Ty,
+        env: &Env,
+        fb: &mut FuncBuilder,
+    ) -> Result<(), CompileError> {
+        match k {
+            FExpr::LamOne(s) => {
+                let (Binder(x), rest) = s.clone().unbind();
+                let index = fb.new_local(ty);
+                fb.push(&format!("local.set {}", index));
+                let mut env = env.clone();
+                env.insert(x, Binding::Local(index, ty));
+                self.stmt(&rest, &env, fb)
+            }
+            FExpr::Var(Var::Free(v)) => match env.get(v) {
+                Some(Binding::Return) => {
+                    if ty != Ty::I64 {
+                        return error(
+                            "a compiled function can only return an integer, not a boolean"
+                                .to_owned(),
+                        );
+                    }
+                    fb.push("return");
+                    Ok(())
+                }
+                _ => error(format!("{} is not a continuation", spelled(v))),
+            },
+            _ => error("unsupported continuation shape".to_owned()),
+        }
+    }
+
+    // The name of the wasm function a callee compiles to; an inline
+    // `fix`/`lam2` is emitted on the spot.
+    fn callee(&mut self, f: &FExpr, env: &Env) -> Result<String, CompileError> {
+        match f {
+            FExpr::Var(Var::Free(v)) => match env.get(v) {
+                Some(Binding::Func(name)) => Ok(name.clone()),
+                Some(Binding::Captured) => error(format!(
+                    "{} is captured from an enclosing function; closures are not supported",
+                    spelled(v)
+                )),
+                Some(_) => error(format!("{} is not a function", spelled(v))),
+                None => error(format!("unbound variable {}", spelled(v))),
+            },
+            FExpr::Fix(_) | FExpr::LamTwo(_) => self.function(f, env),
+            _ => error("the callee of a call is not a function".to_owned()),
+        }
+    }
+
+    // Compiles a `fix`/`lam2` value into its own wasm function and
+    // answers its name. Only `Func` bindings remain visible inside:
+    // anything else from the defining scope turns into `Captured`, so a
+    // body that would need a closure reports it as such.
+    fn function(&mut self, expr: &FExpr, env: &Env) -> Result<String, CompileError> {
+        let (self_var, lam) = match expr {
+            FExpr::Fix(s) => {
+                let (Binder(f), body) = s.clone().unbind();
+                (Some(f), clone_rc(body))
+            }
+            _ => (None, expr.clone()),
+        };
+        let s = match &lam {
+            FExpr::LamTwo(s) => s,
+            _ => {
+                return error(
+                    "only functions of one integer argument can be compiled".to_owned(),
+                )
+            }
+        };
+        let (Binder(param), rest) = s.clone().unbind();
+        let (Binder(cont), body) = rest.unbind();
+
+        let base = self_var
+            .as_ref()
+            .and_then(|v| v.pretty_name.as_deref())
+            .unwrap_or("fn");
+        let name = self.fresh_name(base);
+
+        let mut inner: Env = env
+            .iter()
+            .map(|(v, b)| {
+                let b = match b {
+                    Binding::Func(name) => Binding::Func(name.clone()),
+                    _ => Binding::Captured,
+                };
+                (v.clone(), b)
+            })
+            .collect();
+        if let Some(f) = self_var {
+            inner.insert(f, Binding::Func(name.clone()));
+        }
+        inner.insert(param, Binding::Local(0, Ty::I64));
+        inner.insert(cont, Binding::Return);
+
+        let mut fb = FuncBuilder::new(name.clone(), 1);
+        self.stmt(&body, &inner, &mut fb)?;
+        self.funcs.push(fb.finish());
+        Ok(name)
+    }
+
+    // A fresh `$name`, sanitized to wasm's identifier alphabet and
+    // suffixed on reuse so shadowed script names stay distinct.
+    fn fresh_name(&mut self, base: &str) -> String {
+        let base: String = base
+            .chars()
+            .map(|c| {
+                if c.is_ascii_alphanumeric() || c == '_' || c == '-' {
+                    c
+                } else {
+                    '_'
+                }
+            })
+            .collect();
+        let base = if base.is_empty() { "fn".to_owned() } else { base };
+        let seen = self.taken.entry(base.clone()).or_insert(0);
+        let name = if *seen == 0 {
+            format!("${}", base)
+        } else {
+            format!("${}_{}", base, seen)
+        };
+        *seen += 1;
+        name
+    }
+}
+
+// Pushes both operands and the instruction for `op`, answering the type
+// left on the stack.
+fn bin_op(
+    op: BinOp,
+    lhs: &Rc<FExpr>,
+    rhs: &Rc<FExpr>,
+    env: &Env,
+    fb: &mut FuncBuilder,
+) -> Result<Ty, CompileError> {
+    let (instr, ty) = match op {
+        BinOp::Add => ("i64.add", Ty::I64),
+        BinOp::Sub => ("i64.sub", Ty::I64),
+        BinOp::Mul => ("i64.mul", Ty::I64),
+        BinOp::Div => ("i64.div_u", Ty::I64),
+        BinOp::Eq => ("i64.eq", Ty::I32),
+        BinOp::CharAt => return error("char-at has no wasm translation".to_owned()),
+    };
+    if push_value(lhs, env, fb)? != Ty::I64 || push_value(rhs, env, fb)? != Ty::I64 {
+        return error("binary arithmetic needs integer operands".to_owned());
+    }
+    fb.push(instr);
+    Ok(ty)
+}
+
+// Pushes a value expression onto the wasm operand stack and answers its
+// type. Only integers, booleans, and variables holding them qualify —
+// a function in value position is the higher-order escape hatch the
+// backend refuses.
+fn push_value(expr: &FExpr, env: &Env, fb: &mut FuncBuilder) -> Result<Ty, CompileError> {
+    match expr {
+        FExpr::Lit(Ignore(Literal::Int(i))) => {
+            fb.push(&format!("i64.const {}", i));
+            Ok(Ty::I64)
+        }
+        FExpr::Lit(Ignore(Literal::Bool(b))) => {
+            fb.push(&format!("i32.const {}", *b as i32));
+            Ok(Ty::I32)
+        }
+        FExpr::Lit(Ignore(l)) => error(format!("the literal {:?} has no wasm representation", l)),
+        FExpr::Var(Var::Free(v)) => match env.get(v) {
+            Some(Binding::Local(index, ty)) => {
+                fb.push(&format!("local.get {}", index));
+                Ok(*ty)
+            }
+            Some(Binding::Func(_)) | Some(Binding::Return) => error(format!(
+                "{} escapes as a value; only first-order programs are supported",
+                spelled(v)
+            )),
+            Some(Binding::Captured) => error(format!(
+                "{} is captured from an enclosing function; closures are not supported",
+                spelled(v)
+            )),
+            None => error(format!("unbound variable {}", spelled(v))),
+        },
+        FExpr::LamOne(_) | FExpr::LamTwo(_) | FExpr::Fix(_) => error(
+            "a function escapes as a value; only first-order programs are supported".to_owned(),
+        ),
+        FExpr::Prim(Ignore(p)) => error(format!("the primitive {} escapes as a value", p)),
+        _ => error("a call cannot appear in value position".to_owned()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cont_expr::{t_k, KExpr};
+    use crate::expr::Expr;
+    use crate::prelude::{app, lam, lit, var};
+    use moniker::Scope;
+
+    fn lower(program: Expr, halt: &FreeVar<String>) -> FExpr {
+        t_k(program, Rc::new(KExpr::Var(Var::Free(halt.clone())))).into_fexpr()
+    }
+
+    // fix f. lambda n. if n == 0 then 1 else n * f (n - 1)
+    fn factorial() -> Expr {
+        let f = FreeVar::fresh_named("f");
+        let n = FreeVar::fresh_named("n");
+
+        let body = Expr::If(
+            Rc::new(Expr::Bin(
+                Ignore(BinOp::Eq),
+                Rc::new(var(&n)),
+                Rc::new(lit(Literal::Int(0))),
+            )),
+            Rc::new(lit(Literal::Int(1))),
+            Rc::new(Expr::Bin(
+                Ignore(BinOp::Mul),
+                Rc::new(var(&n)),
+                Rc::new(app(
+                    var(&f),
+                    Expr::Bin(
+                        Ignore(BinOp::Sub),
+                        Rc::new(var(&n)),
+                        Rc::new(lit(Literal::Int(1))),
+                    ),
+                )),
+            )),
+        );
+
+        Expr::Fix(Scope::new(Binder(f), Rc::new(lam(n, body))))
+    }
+
+    #[test]
+    fn a_compiled_factorial_is_valid_wasm() {
+        let halt = FreeVar::fresh_named("halt");
+        let flat = lower(app(factorial(), lit(Literal::Int(5))), &halt);
+
+        let module = compile(&flat, &halt).unwrap();
+        assert!(module.contains("call $f"));
+        assert!(module.contains("i64.mul"));
+
+        let binary = wat::parse_str(&module).unwrap();
+        wasmparser::validate(&binary).unwrap();
+    }
+
+    #[test]
+    fn a_higher_order_program_is_rejected() {
+        // (lambda x. lambda y. x) 1 2: the inner application answers a
+        // function, which has no first-order translation
+        let x = FreeVar::fresh_named("x");
+        let y = FreeVar::fresh_named("y");
+        let program = app(
+            app(lam(x.clone(), lam(y, var(&x))), lit(Literal::Int(1))),
+            lit(Literal::Int(2)),
+        );
+
+        let halt = FreeVar::fresh_named("halt");
+        let err = compile(&lower(program, &halt), &halt).unwrap_err();
+        assert!(err.0.contains("first-order"));
+    }
+
+    #[test]
+    fn a_string_literal_is_rejected() {
+        let halt = FreeVar::fresh_named("halt");
+        let flat = lower(lit(Literal::String("hi".to_owned())), &halt);
+
+        let err = compile(&flat, &halt).unwrap_err();
+        assert!(err.0.contains("no wasm representation"));
+    }
+}
@@ -0,0 +1,29 @@
+// Smoke-tests the `run_file` example's pipeline on the bundled sample
+// script: parse, read back to direct style, lower, evaluate.
+#![cfg(feature = "eval")]
+
+use std::convert::TryFrom;
+use std::rc::Rc;
+
+use moniker::{FreeVar, Var};
+
+use some_embedded_scripting_language::cont_expr::{t_k, KExpr};
+use some_embedded_scripting_language::eval::{run_ccall, Env, Value};
+use some_embedded_scripting_language::expr::Expr;
+use some_embedded_scripting_language::literals::Literal;
+use some_embedded_scripting_language::text;
+
+#[test]
+fn the_sample_script_compiles_and_runs() {
+    let path = concat!(env!("CARGO_MANIFEST_DIR"), "/examples/sample.scm");
+    let source = std::fs::read_to_string(path).unwrap();
+
+    let flat = text::parse(&source).unwrap();
+    let expr = Expr::try_from(flat).unwrap();
+
+    let halt = FreeVar::fresh_named("halt");
+    let call = t_k(expr, Rc::new(KExpr::Var(Var::Free(halt.clone()))));
+
+    let value = run_ccall(call, Env::new().insert(halt, Value::Halt)).unwrap();
+    assert!(matches!(value, Value::Lit(Literal::Int(42))));
+}